@@ -0,0 +1,163 @@
+//! Compara la agregacion de las muertes de cada jugador con cada arma con fold/reduce,
+//! donde cada hilo arma su propio mapa y despues se combinan, con el mapa particionado
+//! que actualizan todos los hilos (`--aggregation sharded`).
+//!
+//! cargo bench --bench players_weapons [-- <input-path> <repeticiones>]
+//!
+//! Las muertes del dataset se cargan en memoria antes de medir, para no medir la lectura.
+//! Si el dataset no existe, se generan muertes de muchos jugadores distintos.
+
+use std::{
+    collections::HashMap,
+    fs,
+    hint::black_box,
+    io::{BufRead, BufReader},
+    path::Path,
+    time::Instant,
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tp_fork_join::{
+    csvs_processor::{KILLER_NAME, WEAPON},
+    sharded_map::ShardedMap,
+};
+
+const DEFAULT_INPUT_PATH: &str = "deaths";
+const DEFAULT_REPETITIONS: usize = 5;
+const GENERATED_KILLS: usize = 2_000_000;
+const GENERATED_PLAYERS: u64 = 500_000;
+const WEAPONS: [&str; 8] = [
+    "M416", "AKM", "SCAR-L", "Kar98k", "UMP9", "Punch", "Grenade", "S1897",
+];
+
+type PlayersWeapons = HashMap<String, HashMap<String, i32>>;
+
+/// Carga el asesino y el arma de cada linea de los CSV del directorio.
+fn load_kills(input_path: &Path) -> Vec<(String, String)> {
+    let mut kills = Vec::new();
+    for entry in fs::read_dir(input_path).expect("Error al leer el directorio") {
+        let file = fs::File::open(entry.expect("Error al leer el directorio").path())
+            .expect("Error al abrir el archivo");
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let fields: Vec<&str> = line.split(',').collect();
+            if let (Some(weapon), Some(killer)) = (fields.get(WEAPON), fields.get(KILLER_NAME)) {
+                if !killer.is_empty() {
+                    kills.push((killer.to_string(), weapon.to_string()));
+                }
+            }
+        }
+    }
+    kills
+}
+
+/// Genera muertes de muchos jugadores, cada uno con pocas muertes, como en el dataset real.
+fn generate_kills() -> Vec<(String, String)> {
+    let mut seed: u64 = 42;
+    let mut next_random = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        seed >> 33
+    };
+    (0..GENERATED_KILLS)
+        .map(|_| {
+            let player = format!("player{}", next_random() % GENERATED_PLAYERS);
+            let weapon = WEAPONS[(next_random() % WEAPONS.len() as u64) as usize];
+            (player, weapon.to_string())
+        })
+        .collect()
+}
+
+fn add_kill(players_weapons: &mut PlayersWeapons, player: &str, weapon: &str) {
+    *players_weapons
+        .entry(player.to_string())
+        .or_default()
+        .entry(weapon.to_string())
+        .or_default() += 1;
+}
+
+/// Cada hilo agrega en su propio mapa y los mapas se combinan de a pares.
+fn fold_reduce(kills: &[(String, String)]) -> PlayersWeapons {
+    kills
+        .par_iter()
+        .fold(HashMap::new, |mut players_weapons, (player, weapon)| {
+            add_kill(&mut players_weapons, player, weapon);
+            players_weapons
+        })
+        .reduce(HashMap::new, |mut acc, players_weapons| {
+            players_weapons.into_iter().for_each(|(player, weapons)| {
+                let acc_weapons = acc.entry(player).or_default();
+                weapons.into_iter().for_each(|(weapon, count)| {
+                    *acc_weapons.entry(weapon).or_default() += count;
+                });
+            });
+            acc
+        })
+}
+
+/// Todos los hilos agregan en el mismo mapa particionado.
+fn sharded(kills: &[(String, String)]) -> PlayersWeapons {
+    let players_weapons = ShardedMap::new(rayon::current_num_threads());
+    kills.par_iter().for_each(|(player, weapon)| {
+        players_weapons.update(player, |weapons: &mut HashMap<String, i32>| {
+            *weapons.entry(weapon.to_string()).or_default() += 1;
+        });
+    });
+    players_weapons.take_entries()
+}
+
+/// Ejecuta la funcion varias veces y devuelve el tiempo promedio en milisegundos.
+fn measure(repetitions: usize, mut f: impl FnMut()) -> f64 {
+    f();
+    let start = Instant::now();
+    (0..repetitions).for_each(|_| f());
+    start.elapsed().as_secs_f64() * 1000.0 / repetitions as f64
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let input_path = Path::new(args.first().map_or(DEFAULT_INPUT_PATH, |a| a.as_str()));
+    let repetitions = args
+        .get(1)
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(DEFAULT_REPETITIONS);
+
+    let kills = match input_path.is_dir() {
+        true => load_kills(input_path),
+        false => {
+            println!(
+                "No se encontro el dataset en {}, se generan {} muertes",
+                input_path.display(),
+                GENERATED_KILLS
+            );
+            generate_kills()
+        }
+    };
+
+    let expected = fold_reduce(&kills);
+    assert_eq!(sharded(&kills), expected, "las agregaciones no coinciden");
+
+    let fold_reduce_ms = measure(repetitions, || {
+        black_box(fold_reduce(black_box(&kills)));
+    });
+    let sharded_ms = measure(repetitions, || {
+        black_box(sharded(black_box(&kills)));
+    });
+
+    println!(
+        "{} muertes de {} jugadores, {} repeticiones, {} hilos",
+        kills.len(),
+        expected.len(),
+        repetitions,
+        rayon::current_num_threads()
+    );
+    println!("fold/reduce:        {:>10.2} ms", fold_reduce_ms);
+    println!("mapa particionado:  {:>10.2} ms", sharded_ms);
+    println!(
+        "mejora:             {:>10.2}x",
+        fold_reduce_ms / sharded_ms
+    );
+}
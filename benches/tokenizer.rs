@@ -0,0 +1,150 @@
+//! Compara la separacion de las lineas y los campos de los CSV con el tokenizer SIMD
+//! (`memchr`), con el escalar, que compara de a un byte, y con `split` de la biblioteca estandar.
+//!
+//! cargo bench --bench tokenizer [-- <input-path> <repeticiones>]
+//!
+//! Los archivos del dataset se cargan en memoria antes de medir, para no medir la lectura.
+//! Si el dataset no existe, se generan lineas con el formato de las muertes.
+
+use std::{fs, hint::black_box, path::Path, time::Instant};
+
+use tp_fork_join::{csvs_processor::VICTIM_POSITION_Y, tokenizer};
+
+const DEFAULT_INPUT_PATH: &str = "deaths";
+const DEFAULT_REPETITIONS: usize = 5;
+const GENERATED_LINES: usize = 1_000_000;
+const NUMBER_OF_FIELDS: usize = VICTIM_POSITION_Y + 1;
+
+/// Carga el contenido de los CSV del directorio.
+fn load_files(input_path: &Path) -> Vec<Vec<u8>> {
+    fs::read_dir(input_path)
+        .expect("Error al leer el directorio")
+        .map(|entry| {
+            fs::read(entry.expect("Error al leer el directorio").path())
+                .expect("Error al leer el archivo")
+        })
+        .collect()
+}
+
+/// Genera un archivo con lineas parecidas a las del dataset.
+fn generate_file() -> Vec<Vec<u8>> {
+    let mut data = String::new();
+    for i in 0..GENERATED_LINES {
+        data.push_str(&format!(
+            "Punch,2U4GBNA0YmnNZYkzjkfgN4ev-hXSrak_BSey_YEG6kIuDG9fxFrrePqnqiM39pJO,player{},\
+             {},{}.5,{}.5,{},1,2,ERANGEL,1,player{},{},{}.25,{}.25\n",
+            i % 5000,
+            i % 100,
+            i,
+            i * 3,
+            i % 1800,
+            i % 7000,
+            i % 100,
+            i * 2,
+            i * 5
+        ));
+    }
+    vec![data.into_bytes()]
+}
+
+/// Cuenta los campos de todas las lineas, separadas con las funciones de un modulo del tokenizer.
+macro_rules! count_fields {
+    ($tokenizer:path, $files:expr) => {{
+        use $tokenizer as tokenizer;
+        let mut total = 0;
+        for data in $files {
+            for line in tokenizer::lines(data) {
+                let Ok(line) = std::str::from_utf8(line) else {
+                    continue;
+                };
+                let mut fields = [""; NUMBER_OF_FIELDS];
+                let number_of_fields = tokenizer::split_fields(line, &mut fields);
+                total += black_box(&fields[..number_of_fields]).len();
+            }
+        }
+        total
+    }};
+}
+
+/// Cuenta los campos de todas las lineas, separadas con `split` de la biblioteca estandar.
+fn count_fields_std(files: &[Vec<u8>]) -> usize {
+    let mut total = 0;
+    for data in files {
+        for line in data.split_inclusive(|&byte| byte == b'\n') {
+            // El \r solo se quita si esta antes del fin de linea, como en BufRead::lines
+            let line = match line.strip_suffix(b"\n") {
+                Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+                None => line,
+            };
+            let Ok(line) = std::str::from_utf8(line) else {
+                continue;
+            };
+            total += line
+                .split(',')
+                .take(NUMBER_OF_FIELDS)
+                .map(black_box)
+                .count();
+        }
+    }
+    total
+}
+
+/// Ejecuta la funcion varias veces y devuelve el tiempo promedio en milisegundos.
+fn measure(repetitions: usize, mut f: impl FnMut()) -> f64 {
+    f();
+    let start = Instant::now();
+    (0..repetitions).for_each(|_| f());
+    start.elapsed().as_secs_f64() * 1000.0 / repetitions as f64
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let input_path = Path::new(args.first().map_or(DEFAULT_INPUT_PATH, |a| a.as_str()));
+    let repetitions = args
+        .get(1)
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(DEFAULT_REPETITIONS);
+
+    let files = match input_path.is_dir() {
+        true => load_files(input_path),
+        false => {
+            println!(
+                "No se encontro el dataset en {}, se generan {} lineas",
+                input_path.display(),
+                GENERATED_LINES
+            );
+            generate_file()
+        }
+    };
+
+    let simd = |files: &[Vec<u8>]| count_fields!(tokenizer::simd, files);
+    let scalar = |files: &[Vec<u8>]| count_fields!(tokenizer::scalar, files);
+    let expected = count_fields_std(&files);
+    assert_eq!(simd(&files), expected, "los campos no coinciden");
+    assert_eq!(scalar(&files), expected, "los campos no coinciden");
+
+    let simd_ms = measure(repetitions, || {
+        black_box(simd(black_box(&files)));
+    });
+    let scalar_ms = measure(repetitions, || {
+        black_box(scalar(black_box(&files)));
+    });
+    let std_ms = measure(repetitions, || {
+        black_box(count_fields_std(black_box(&files)));
+    });
+
+    let bytes: usize = files.iter().map(Vec::len).sum();
+    println!(
+        "{:.1} MB, {} campos, {} repeticiones",
+        bytes as f64 / (1024.0 * 1024.0),
+        expected,
+        repetitions
+    );
+    println!("simd:               {:>10.2} ms", simd_ms);
+    println!("escalar:            {:>10.2} ms", scalar_ms);
+    println!("split:              {:>10.2} ms", std_ms);
+    println!("mejora vs split:    {:>10.2}x", std_ms / simd_ms);
+}
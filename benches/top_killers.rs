@@ -0,0 +1,117 @@
+//! Compara la seleccion del top de asesinos con el ordenamiento completo que se usaba antes.
+//!
+//! cargo bench --bench top_killers [-- <jugadores> <repeticiones>]
+
+use std::{cmp::Ordering, collections::HashMap, hint::black_box, time::Instant};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tp_fork_join::top_calculator::select_top_players_by_kills;
+
+const DEFAULT_NUMBER_OF_PLAYERS: usize = 1_000_000;
+const DEFAULT_REPETITIONS: usize = 5;
+const TOP_PLAYERS: usize = 10;
+const WEAPONS: [&str; 8] = [
+    "M416", "AKM", "SCAR-L", "Kar98k", "UMP9", "Punch", "Grenade", "S1897",
+];
+
+/// Ordena a todos los jugadores sumando las muertes dentro del comparador,
+/// como lo hacia `sort_players_by_kills`.
+fn full_sort_top_players(
+    player_kills: &HashMap<String, HashMap<String, i32>>,
+    n: usize,
+) -> Vec<(&String, &HashMap<String, i32>)> {
+    let mut players_weapons_vec: Vec<(&String, &HashMap<String, i32>)> =
+        player_kills.par_iter().collect();
+    players_weapons_vec.sort_unstable_by(|a, b| {
+        let sum_a = a.1.values().sum::<i32>();
+        let sum_b = b.1.values().sum::<i32>();
+        let sum_cmp = sum_b.cmp(&sum_a);
+        if sum_cmp == Ordering::Equal {
+            a.0.cmp(b.0)
+        } else {
+            sum_cmp
+        }
+    });
+    players_weapons_vec.truncate(n);
+    players_weapons_vec
+}
+
+/// Genera jugadores con pocas muertes y muchos empates, como en el dataset real.
+fn generate_player_kills(number_of_players: usize) -> HashMap<String, HashMap<String, i32>> {
+    let mut seed: u64 = 42;
+    let mut next_random = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        seed >> 33
+    };
+    (0..number_of_players)
+        .map(|player| {
+            let weapons = (0..1 + next_random() % 4)
+                .map(|_| {
+                    let weapon = WEAPONS[(next_random() % WEAPONS.len() as u64) as usize];
+                    (weapon.to_string(), 1 + (next_random() % 20) as i32)
+                })
+                .collect();
+            (format!("player{}", player), weapons)
+        })
+        .collect()
+}
+
+/// Ejecuta la funcion varias veces y devuelve el tiempo promedio en milisegundos.
+fn measure(repetitions: usize, mut f: impl FnMut()) -> f64 {
+    f();
+    let start = Instant::now();
+    (0..repetitions).for_each(|_| f());
+    start.elapsed().as_secs_f64() * 1000.0 / repetitions as f64
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let number_of_players = args
+        .first()
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(DEFAULT_NUMBER_OF_PLAYERS);
+    let repetitions = args
+        .get(1)
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(DEFAULT_REPETITIONS);
+
+    let player_kills = generate_player_kills(number_of_players);
+
+    let expected: Vec<_> = full_sort_top_players(&player_kills, TOP_PLAYERS)
+        .into_iter()
+        .map(|(player, _)| player)
+        .collect();
+    let selected: Vec<_> = select_top_players_by_kills(&player_kills, TOP_PLAYERS)
+        .into_iter()
+        .map(|(player, _, _)| player)
+        .collect();
+    assert_eq!(selected, expected, "los tops no coinciden");
+
+    let full_sort_ms = measure(repetitions, || {
+        black_box(full_sort_top_players(black_box(&player_kills), TOP_PLAYERS));
+    });
+    let selection_ms = measure(repetitions, || {
+        black_box(select_top_players_by_kills(
+            black_box(&player_kills),
+            TOP_PLAYERS,
+        ));
+    });
+
+    println!(
+        "{} jugadores, {} repeticiones, {} hilos",
+        number_of_players,
+        repetitions,
+        rayon::current_num_threads()
+    );
+    println!("ordenamiento completo: {:>10.2} ms", full_sort_ms);
+    println!("seleccion del top:     {:>10.2} ms", selection_ms);
+    println!(
+        "mejora:                {:>10.2}x",
+        full_sort_ms / selection_ms
+    );
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Version of the format of the state file. It changes when the aggregates change.
+const STATE_VERSION: u32 = 3;
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+const CHECKSUM_BUFFER_SIZE: usize = 1 << 16;
+
+/// Aggregates of an input file, with the checksum of the content they were computed from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileState<T> {
+    path: String,
+    checksum: u64,
+    data: T,
+}
+
+impl<T> FileState<T> {
+    pub fn new(path: &Path, checksum: u64, data: T) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            checksum,
+            data,
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_data(&self) -> &T {
+        &self.data
+    }
+}
+
+/// First line of the state file, with the version of the format and the options.
+#[derive(Debug, Serialize, Deserialize)]
+struct StateHeader {
+    version: u32,
+    options: serde_json::Value,
+}
+
+/// Aggregates of the processed input files, persisted between runs so that only the
+/// new and the changed files have to be processed. The aggregates of each file are kept
+/// apart, so the aggregates of a changed file can be replaced instead of added twice.
+///
+/// The state file is a log of JSON lines: the first one has the version and the options,
+/// and each of the next ones has the aggregates of a file, so a processed file is saved
+/// by appending its line with a `StateLog`, without writing the whole state again.
+#[derive(Debug)]
+pub struct AnalysisState<T> {
+    version: u32,
+    options: serde_json::Value,
+    files: Vec<FileState<T>>,
+}
+
+impl<T: Serialize + DeserializeOwned> AnalysisState<T> {
+    /// Creates an empty state for the aggregates computed with the given options.
+    pub fn new(options: serde_json::Value) -> Self {
+        Self {
+            version: STATE_VERSION,
+            options,
+            files: Vec::new(),
+        }
+    }
+
+    /// Loads the state from a file. If a file appears more than once in the log,
+    /// its last aggregates are kept, and a last line cut short because the program
+    /// stopped while appending it is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the state file.
+    ///
+    /// # Returns
+    ///
+    /// The state, `None` if the file does not exist yet,
+    /// or a message describing why the file could not be loaded.
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut lines = BufReader::new(file).lines().peekable();
+        let header = match lines.next() {
+            Some(line) => line.map_err(|e| e.to_string())?,
+            None => return Err("el archivo esta vacio".to_string()),
+        };
+        let header: StateHeader = serde_json::from_str(&header).map_err(|e| e.to_string())?;
+        if header.version != STATE_VERSION {
+            return Err(format!(
+                "el archivo tiene la version {} y se esperaba la {}",
+                header.version, STATE_VERSION
+            ));
+        }
+        let mut state = Self::new(header.options);
+        while let Some(line) = lines.next() {
+            let line = line.map_err(|e| e.to_string())?;
+            match serde_json::from_str(&line) {
+                Ok(file_state) => state.add_file(file_state),
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        state.remove_replaced_files();
+        Ok(Some(state))
+    }
+
+    /// Keeps only the last aggregates of each file.
+    fn remove_replaced_files(&mut self) {
+        let mut seen_paths = HashSet::new();
+        let mut files: Vec<FileState<T>> = std::mem::take(&mut self.files)
+            .into_iter()
+            .rev()
+            .filter(|file| seen_paths.insert(file.path.clone()))
+            .collect();
+        files.reverse();
+        self.files = files;
+    }
+
+    /// Saves the whole state to a file, compacting the log. The state is written to
+    /// a temporary file first, so the previous state is kept if the program stops while writing.
+    /// The files processed after it are appended with a `StateLog`.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let temporary_path = format!("{}.tmp", path);
+        let file = File::create(&temporary_path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::new(file);
+        let header = StateHeader {
+            version: self.version,
+            options: self.options.clone(),
+        };
+        write_line(&mut writer, &header)?;
+        self.files
+            .iter()
+            .try_for_each(|file_state| write_line(&mut writer, file_state))?;
+        writer.flush().map_err(|e| e.to_string())?;
+        drop(writer);
+        fs::rename(&temporary_path, path).map_err(|e| e.to_string())
+    }
+
+    /// Returns an error if the state was computed with other options.
+    pub fn check_options(&self, options: &serde_json::Value) -> Result<(), String> {
+        if &self.options == options {
+            Ok(())
+        } else {
+            Err(format!(
+                "el estado se calculo con las opciones {} y ahora se pidieron {}",
+                self.options, options
+            ))
+        }
+    }
+
+    /// Returns an error if any of the files of the state changed or is not an input file anymore.
+    pub fn check_unchanged_files(&self, checksums: &[(PathBuf, u64)]) -> Result<(), String> {
+        let current_checksums: HashMap<String, u64> = checksums
+            .iter()
+            .map(|(path, checksum)| (path.to_string_lossy().to_string(), *checksum))
+            .collect();
+        match self
+            .files
+            .iter()
+            .find(|file| current_checksums.get(&file.path) != Some(&file.checksum))
+        {
+            Some(file) => Err(format!("el archivo {} cambio", file.path)),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps only the files whose content did not change.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksums` - Current checksum of each input file.
+    ///
+    /// # Returns
+    ///
+    /// The input files that are not in the state, or whose content changed.
+    pub fn retain_unchanged_files(&mut self, checksums: &[(PathBuf, u64)]) -> Vec<PathBuf> {
+        let current_checksums: HashMap<String, u64> = checksums
+            .iter()
+            .map(|(path, checksum)| (path.to_string_lossy().to_string(), *checksum))
+            .collect();
+        self.files
+            .retain(|file| current_checksums.get(&file.path) == Some(&file.checksum));
+        checksums
+            .iter()
+            .filter(|(path, _)| !self.contains_file(path))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn contains_file(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    /// Adds the aggregates of a file that is not in the state.
+    pub fn add_file(&mut self, file_state: FileState<T>) {
+        self.files.push(file_state);
+    }
+
+    pub fn get_files(&self) -> &[FileState<T>] {
+        &self.files
+    }
+}
+
+/// How often the processed files are written to the state file, given with `--checkpoint-interval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckpointInterval {
+    /// Each time the given number of files was processed.
+    Files(usize),
+    /// Each time the given time passed since the last write.
+    Time(Duration),
+}
+
+impl Default for CheckpointInterval {
+    fn default() -> Self {
+        CheckpointInterval::Files(1)
+    }
+}
+
+impl CheckpointInterval {
+    /// Parses the interval given with `--checkpoint-interval`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - A number of files, like `10`, or of seconds, like `30s`.
+    ///   By default the files are written as soon as each one is processed.
+    ///
+    /// # Returns
+    ///
+    /// The interval, or a message describing why it is not valid.
+    pub fn parse(interval: Option<&str>) -> Result<Self, String> {
+        let Some(interval) = interval else {
+            return Ok(Self::default());
+        };
+        let parsed = match interval.strip_suffix('s') {
+            Some(seconds) => seconds.parse().map(|seconds| {
+                (
+                    seconds,
+                    CheckpointInterval::Time(Duration::from_secs(seconds)),
+                )
+            }),
+            None => interval
+                .parse()
+                .map(|files| (files as u64, CheckpointInterval::Files(files))),
+        };
+        match parsed {
+            Ok((amount, interval)) if amount > 0 => Ok(interval),
+            _ => Err(format!(
+                "intervalo de checkpoint invalido '{}', tiene que ser una cantidad de archivos \
+                 (por ejemplo 10) o de segundos (por ejemplo 30s) mayor a 0",
+                interval
+            )),
+        }
+    }
+}
+
+/// Lines of the log that were not written yet.
+#[derive(Debug)]
+struct PendingLines {
+    file: File,
+    lines: Vec<u8>,
+    files: usize,
+    last_write: Instant,
+}
+
+impl PendingLines {
+    fn write(&mut self) -> Result<(), String> {
+        self.files = 0;
+        self.last_write = Instant::now();
+        // Las lineas se escriben de una vez, asi que si el programa se detiene solo la ultima
+        // puede quedar cortada
+        let result = self.file.write_all(&self.lines).map_err(|e| e.to_string());
+        self.lines.clear();
+        result
+    }
+}
+
+/// Log where the aggregates of the files are appended after the state was saved, each time
+/// the checkpoint interval passes. Each file is serialized before taking the lock of the log,
+/// so the threads only wait for each other while its line is buffered or written.
+#[derive(Debug)]
+pub struct StateLog {
+    interval: CheckpointInterval,
+    pending: Mutex<PendingLines>,
+}
+
+impl StateLog {
+    /// Opens the log of a state file that was already saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the state file.
+    /// * `interval` - How often the processed files are written.
+    ///
+    /// # Returns
+    ///
+    /// The log, or a message describing why the file could not be opened.
+    pub fn open(path: &str, interval: CheckpointInterval) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            interval,
+            pending: Mutex::new(PendingLines {
+                file,
+                lines: Vec::new(),
+                files: 0,
+                last_write: Instant::now(),
+            }),
+        })
+    }
+
+    /// Appends the aggregates of a file to the log. They are written when the interval passed,
+    /// and until then they are lost if the program stops.
+    pub fn append<T: Serialize>(&self, file_state: &FileState<T>) -> Result<(), String> {
+        let line = serde_json::to_vec(file_state).map_err(|e| e.to_string())?;
+        // Si otro hilo entro en panico con el lock tomado, el log sigue siendo valido
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.lines.extend_from_slice(&line);
+        pending.lines.push(b'\n');
+        pending.files += 1;
+        let is_due = match self.interval {
+            CheckpointInterval::Files(files) => pending.files >= files,
+            CheckpointInterval::Time(time) => pending.last_write.elapsed() >= time,
+        };
+        match is_due {
+            true => pending.write(),
+            false => Ok(()),
+        }
+    }
+
+    /// Writes the files that are waiting for the interval, at the end of the run.
+    pub fn flush(&self) -> Result<(), String> {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write()
+    }
+}
+
+/// Writes a value as a JSON line.
+fn write_line(writer: &mut impl Write, value: &impl Serialize) -> Result<(), String> {
+    serde_json::to_writer(&mut *writer, value).map_err(|e| e.to_string())?;
+    writer.write_all(b"\n").map_err(|e| e.to_string())
+}
+
+/// Calculates the FNV-1a checksum of the content of a file.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file.
+///
+/// # Returns
+///
+/// The checksum, or the error found while reading the file.
+pub fn calculate_checksum(path: &Path) -> std::io::Result<u64> {
+    let mut reader = File::open(path)?;
+    let mut buffer = vec![0; CHECKSUM_BUFFER_SIZE];
+    let mut checksum = FNV_OFFSET_BASIS;
+    loop {
+        let read_bytes = reader.read(&mut buffer)?;
+        if read_bytes == 0 {
+            return Ok(checksum);
+        }
+        checksum = buffer[..read_bytes]
+            .iter()
+            .fold(checksum, |checksum, &byte| {
+                (checksum ^ byte as u64).wrapping_mul(FNV_PRIME)
+            });
+    }
+}
@@ -1,32 +1,143 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::PathBuf,
+};
 
-const INPUT_PATH_CONSOLE_ARGUMENT: usize = 1;
-const NUMBER_OF_THREADS_CONSOLE_ARGUMENT: usize = 2;
-const OUTPUT_FILE_CONSOLE_ARGUMENT: usize = 3;
-const EXPECTED_ARGS: usize = 4;
+const INPUT_PATH_CONSOLE_ARGUMENT: usize = 0;
+const NUMBER_OF_THREADS_CONSOLE_ARGUMENT: usize = 1;
+const OUTPUT_FILE_CONSOLE_ARGUMENT: usize = 2;
+const EXPECTED_POSITIONAL_ARGS: usize = 3;
+const OPTION_PREFIX: &str = "--";
+const PLAYER_COMMAND: &str = "player";
+const WEAPON_COMMAND: &str = "weapon";
+const QUERY_COMMAND: &str = "query";
+const BENCH_COMMAND: &str = "bench";
+
+const CATEGORIES_SWITCH: &str = "categories";
+const CATEGORIES_FILE_OPTION: &str = "categories-file";
+const PLACEMENT_SWITCH: &str = "placement";
+const RIVALRIES_SWITCH: &str = "rivalries";
+const GRAPH_EXPORT_OPTION: &str = "graph-export";
+const FILTER_OPTION: &str = "filter";
+const APPROX_SWITCH: &str = "approx";
+const APPROX_CAPACITY_OPTION: &str = "approx-capacity";
+const STATE_OPTION: &str = "state";
+const CHECKPOINT_OPTION: &str = "checkpoint";
+const CHECKPOINT_INTERVAL_OPTION: &str = "checkpoint-interval";
+const RESUME_SWITCH: &str = "resume";
+const PARTIAL_SWITCH: &str = "partial";
+const PROGRESS_OPTION: &str = "progress";
+const METRICS_SWITCH: &str = "metrics";
+const THREADS_OPTION: &str = "threads";
+const WARMUP_OPTION: &str = "warmup";
+const REPETITIONS_OPTION: &str = "repetitions";
+const BACKEND_OPTION: &str = "backend";
+const IO_THREADS_OPTION: &str = "io-threads";
+const AGGREGATOR_SHARDS_OPTION: &str = "aggregator-shards";
+const CHANNEL_CAPACITY_OPTION: &str = "channel-capacity";
+const AGGREGATION_OPTION: &str = "aggregation";
+const MEMORY_LIMIT_OPTION: &str = "memory-limit";
+const THREAD_NAME_OPTION: &str = "thread-name";
+const STACK_SIZE_OPTION: &str = "stack-size";
+const PIN_THREADS_SWITCH: &str = "pin-threads";
+const IO_OPTION: &str = "io";
+const INPUT_FORMAT_OPTION: &str = "input-format";
+const DISTINCT_OPTION: &str = "distinct";
+const HLL_PRECISION_OPTION: &str = "hll-precision";
+const GROUP_BY_OPTION: &str = "group-by";
+const AGGREGATE_OPTION: &str = "aggregate";
+const DEFAULT_AGGREGATES: &str = "count";
+
+/// Options that do not take a value.
+const SWITCHES: [&str; 8] = [
+    CATEGORIES_SWITCH,
+    PLACEMENT_SWITCH,
+    RIVALRIES_SWITCH,
+    APPROX_SWITCH,
+    RESUME_SWITCH,
+    PARTIAL_SWITCH,
+    METRICS_SWITCH,
+    PIN_THREADS_SWITCH,
+];
+/// Options that take a value, either as `--option value` or `--option=value`.
+const VALUED_OPTIONS: [&str; 24] = [
+    CATEGORIES_FILE_OPTION,
+    GRAPH_EXPORT_OPTION,
+    FILTER_OPTION,
+    STATE_OPTION,
+    CHECKPOINT_OPTION,
+    CHECKPOINT_INTERVAL_OPTION,
+    APPROX_CAPACITY_OPTION,
+    DISTINCT_OPTION,
+    HLL_PRECISION_OPTION,
+    GROUP_BY_OPTION,
+    AGGREGATE_OPTION,
+    THREADS_OPTION,
+    WARMUP_OPTION,
+    REPETITIONS_OPTION,
+    BACKEND_OPTION,
+    IO_THREADS_OPTION,
+    AGGREGATOR_SHARDS_OPTION,
+    CHANNEL_CAPACITY_OPTION,
+    AGGREGATION_OPTION,
+    MEMORY_LIMIT_OPTION,
+    THREAD_NAME_OPTION,
+    STACK_SIZE_OPTION,
+    IO_OPTION,
+    INPUT_FORMAT_OPTION,
+];
+/// Options whose value can be left out, with the value they take without it.
+/// Their value can only be given as `--option=value`.
+const OPTIONAL_VALUE_OPTIONS: [(&str, &str); 1] = [(PROGRESS_OPTION, "auto")];
+
+/// The analysis requested by the user.
+pub enum Command {
+    /// The report with the top killers and the top weapons.
+    Report,
+    /// The profile of the player with the given name.
+    Player(String),
+    /// The detail of the weapon with the given name.
+    Weapon(String),
+    /// A query with custom group-by columns and aggregates.
+    Query,
+    /// The report run with several numbers of threads, measuring the time of each phase.
+    Bench,
+}
 
 pub struct ArgumentParser {
+    command: Command,
     input_path: String,
     num_threads: usize,
     output_file_name: String,
+    switches: HashSet<String>,
+    options: HashMap<String, String>,
 }
 
 impl ArgumentParser {
     pub fn new() -> Self {
-        let args: Vec<String> = env::args().collect();
-        if args.len() != EXPECTED_ARGS {
-            eprintln!("Uso: cargo run <input-path> <num-threads> <output-file-name>");
-            std::process::exit(1);
+        let args: Vec<String> = env::args().skip(1).collect();
+        let (positional, switches, options) = split_arguments(&args);
+        let (command, positional) = split_command(positional);
+        if positional.len() != EXPECTED_POSITIONAL_ARGS {
+            exit_with_usage();
         }
         Self {
-            input_path: args[INPUT_PATH_CONSOLE_ARGUMENT].clone(),
-            num_threads: args[NUMBER_OF_THREADS_CONSOLE_ARGUMENT]
+            command,
+            input_path: positional[INPUT_PATH_CONSOLE_ARGUMENT].clone(),
+            num_threads: positional[NUMBER_OF_THREADS_CONSOLE_ARGUMENT]
                 .parse()
                 .expect("El segundo argumento debe ser un entero"),
-            output_file_name: args[OUTPUT_FILE_CONSOLE_ARGUMENT].clone(),
+            output_file_name: positional[OUTPUT_FILE_CONSOLE_ARGUMENT].clone(),
+            switches,
+            options,
         }
     }
 
+    pub fn get_command(&self) -> &Command {
+        &self.command
+    }
+
     fn get_input_path(&self) -> &str {
         &self.input_path
     }
@@ -39,9 +150,188 @@ impl ArgumentParser {
         &self.output_file_name
     }
 
+    /// Returns true if the weapon categories section was requested,
+    /// either with `--categories` or by giving a mapping file.
+    pub fn is_categories_enabled(&self) -> bool {
+        self.switches.contains(CATEGORIES_SWITCH) || self.get_categories_file().is_some()
+    }
+
+    /// Returns the path of the file that overrides the built-in weapon categories, if any.
+    pub fn get_categories_file(&self) -> Option<&str> {
+        self.options.get(CATEGORIES_FILE_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns true if the statistics by placement were requested with `--placement`.
+    pub fn is_placement_enabled(&self) -> bool {
+        self.switches.contains(PLACEMENT_SWITCH)
+    }
+
+    /// Returns true if the top rivalries were requested with `--rivalries`.
+    pub fn is_rivalries_enabled(&self) -> bool {
+        self.switches.contains(RIVALRIES_SWITCH)
+    }
+
+    /// Returns the path where the kill graph must be exported, if any.
+    pub fn get_graph_export_file(&self) -> Option<&str> {
+        self.options.get(GRAPH_EXPORT_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the filter expression given with `--filter`, if any.
+    pub fn get_filter(&self) -> Option<&str> {
+        self.options.get(FILTER_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the path of the file with the state of the previous runs given with `--state`, if any.
+    pub fn get_state_file(&self) -> Option<&str> {
+        self.options.get(STATE_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the path of the checkpoint file given with `--checkpoint`, if any.
+    pub fn get_checkpoint_file(&self) -> Option<&str> {
+        self.options.get(CHECKPOINT_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns how often the processed files are saved, given with `--checkpoint-interval`, if any.
+    pub fn get_checkpoint_interval(&self) -> Option<&str> {
+        self.options
+            .get(CHECKPOINT_INTERVAL_OPTION)
+            .map(|s| s.as_str())
+    }
+
+    /// Returns true if the run has to continue from the checkpoint, requested with `--resume`.
+    pub fn is_resume_enabled(&self) -> bool {
+        self.switches.contains(RESUME_SWITCH)
+    }
+
+    /// Returns how the progress is shown, given with `--progress`, if any.
+    pub fn get_progress_mode(&self) -> Option<&str> {
+        self.options.get(PROGRESS_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns true if the metrics of the run have to be added to the output, requested with `--metrics`.
+    pub fn is_metrics_enabled(&self) -> bool {
+        self.switches.contains(METRICS_SWITCH)
+    }
+
+    /// Returns true if a partial output has to be written when the run is cancelled,
+    /// requested with `--partial`.
+    pub fn is_partial_enabled(&self) -> bool {
+        self.switches.contains(PARTIAL_SWITCH)
+    }
+
+    /// Returns true if the approximate top killers were requested,
+    /// either with `--approx` or by giving the capacity of the summary.
+    pub fn is_approx_enabled(&self) -> bool {
+        self.switches.contains(APPROX_SWITCH) || self.get_approx_capacity().is_some()
+    }
+
+    /// Returns the number of players monitored by `--approx` given with `--approx-capacity`, if any.
+    pub fn get_approx_capacity(&self) -> Option<&str> {
+        self.options.get(APPROX_CAPACITY_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the mode of the distinct counts given with `--distinct`, if any.
+    pub fn get_distinct_mode(&self) -> Option<&str> {
+        self.options.get(DISTINCT_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the precision of the HyperLogLog sketches given with `--hll-precision`, if any.
+    pub fn get_hll_precision(&self) -> Option<&str> {
+        self.options.get(HLL_PRECISION_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the columns given with `--group-by`, separated by commas, if any.
+    pub fn get_group_by(&self) -> Option<&str> {
+        self.options.get(GROUP_BY_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the aggregate functions given with `--aggregate`, or `count` if none were given.
+    pub fn get_aggregates(&self) -> &str {
+        self.options
+            .get(AGGREGATE_OPTION)
+            .map_or(DEFAULT_AGGREGATES, |s| s.as_str())
+    }
+
+    /// Returns the name of the backend that processes the files, given with `--backend`, if any.
+    pub fn get_backend(&self) -> Option<&str> {
+        self.options.get(BACKEND_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns how the lines of the files are read, given with `--io`, if any.
+    pub fn get_io_mode(&self) -> Option<&str> {
+        self.options.get(IO_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the format of the input files, given with `--input-format`, if any.
+    pub fn get_input_format(&self) -> Option<&str> {
+        self.options.get(INPUT_FORMAT_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the threads that read the files apart from the workers, given with `--io-threads`,
+    /// if any.
+    pub fn get_io_threads(&self) -> Option<&str> {
+        self.options.get(IO_THREADS_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the aggregator shards of the pipeline, given with `--aggregator-shards`, if any.
+    pub fn get_aggregator_shards(&self) -> Option<&str> {
+        self.options
+            .get(AGGREGATOR_SHARDS_OPTION)
+            .map(|s| s.as_str())
+    }
+
+    /// Returns the capacity of the channels of the pipeline, given with `--channel-capacity`,
+    /// if any.
+    pub fn get_channel_capacity(&self) -> Option<&str> {
+        self.options
+            .get(CHANNEL_CAPACITY_OPTION)
+            .map(|s| s.as_str())
+    }
+
+    /// Returns how the kills of the players are aggregated, given with `--aggregation`, if any.
+    pub fn get_aggregation(&self) -> Option<&str> {
+        self.options.get(AGGREGATION_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the memory budget of the kills of the players, given with `--memory-limit`, if any.
+    pub fn get_memory_limit(&self) -> Option<&str> {
+        self.options.get(MEMORY_LIMIT_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the prefix of the names of the threads, given with `--thread-name`, if any.
+    pub fn get_thread_name(&self) -> Option<&str> {
+        self.options.get(THREAD_NAME_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the stack size of the threads, given with `--stack-size`, if any.
+    pub fn get_stack_size(&self) -> Option<&str> {
+        self.options.get(STACK_SIZE_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns true if each worker has to be pinned to a core, requested with `--pin-threads`.
+    pub fn is_pin_threads_enabled(&self) -> bool {
+        self.switches.contains(PIN_THREADS_SWITCH)
+    }
+
+    /// Returns the numbers of threads of the benchmark given with `--threads`,
+    /// separated by commas, if any.
+    pub fn get_thread_counts(&self) -> Option<&str> {
+        self.options.get(THREADS_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the warm-up runs of the benchmark given with `--warmup`, if any.
+    pub fn get_warmup(&self) -> Option<&str> {
+        self.options.get(WARMUP_OPTION).map(|s| s.as_str())
+    }
+
+    /// Returns the measured runs of the benchmark given with `--repetitions`, if any.
+    pub fn get_repetitions(&self) -> Option<&str> {
+        self.options.get(REPETITIONS_OPTION).map(|s| s.as_str())
+    }
+
     /// Open the directory given in the input path
     /// If the directory cannot be opened, the function exits the program
-    /// 
+    ///
     /// # Arguments
     ///
     /// * `path` - The path to the directory
@@ -76,3 +366,144 @@ impl ArgumentParser {
         valid_entries.map(|d| d.path()).collect::<Vec<PathBuf>>()
     }
 }
+
+impl Default for ArgumentParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Separates the positional arguments from the `--` options.
+/// If an option is unknown or lacks its value, the function exits the program
+///
+/// # Arguments
+///
+/// * `args` - The console arguments, without the program name
+///
+/// # Returns
+///
+/// A tuple with the positional arguments, the switches that were set
+/// and the options with their values
+fn split_arguments(args: &[String]) -> (Vec<String>, HashSet<String>, HashMap<String, String>) {
+    let mut positional = Vec::new();
+    let mut switches = HashSet::new();
+    let mut options = HashMap::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let Some(option) = arg.strip_prefix(OPTION_PREFIX) else {
+            positional.push(arg.clone());
+            continue;
+        };
+        // Admite tanto --opcion=valor como --opcion valor
+        let (name, inline_value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, None),
+        };
+        let optional_value = OPTIONAL_VALUE_OPTIONS
+            .iter()
+            .find(|(option_name, _)| *option_name == name);
+        if SWITCHES.contains(&name) && inline_value.is_none() {
+            switches.insert(name.to_string());
+        } else if let Some((_, default_value)) = optional_value {
+            // El valor no se toma del argumento siguiente, que puede ser otra opcion
+            let value = inline_value.unwrap_or_else(|| default_value.to_string());
+            options.insert(name.to_string(), value);
+        } else if VALUED_OPTIONS.contains(&name) {
+            match inline_value.or_else(|| args_iter.next().cloned()) {
+                Some(value) => {
+                    options.insert(name.to_string(), value);
+                }
+                None => {
+                    eprintln!("Falta el valor de la opcion --{}", name);
+                    exit_with_usage();
+                }
+            }
+        } else {
+            eprintln!("Opcion desconocida: {}", arg);
+            exit_with_usage();
+        }
+    }
+    (positional, switches, options)
+}
+
+/// Separates the subcommand and its arguments from the rest of the positional arguments.
+///
+/// # Arguments
+///
+/// * `positional` - The positional console arguments
+///
+/// # Returns
+///
+/// A tuple with the command and the positional arguments that are left
+fn split_command(mut positional: Vec<String>) -> (Command, Vec<String>) {
+    match positional.first().map(|command| command.as_str()) {
+        Some(PLAYER_COMMAND) if positional.len() > EXPECTED_POSITIONAL_ARGS => {
+            let player_name = positional.remove(1);
+            positional.remove(0);
+            (Command::Player(player_name), positional)
+        }
+        Some(QUERY_COMMAND) if positional.len() > EXPECTED_POSITIONAL_ARGS => {
+            positional.remove(0);
+            (Command::Query, positional)
+        }
+        Some(BENCH_COMMAND) if positional.len() > EXPECTED_POSITIONAL_ARGS => {
+            positional.remove(0);
+            (Command::Bench, positional)
+        }
+        Some(WEAPON_COMMAND) if positional.len() > EXPECTED_POSITIONAL_ARGS => {
+            let weapon_name = positional.remove(1);
+            positional.remove(0);
+            (Command::Weapon(weapon_name), positional)
+        }
+        _ => (Command::Report, positional),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("Uso: cargo run <input-path> <num-threads> <output-file-name> [opciones]");
+    eprintln!("     cargo run player <name> <input-path> <num-threads> <output-file-name>");
+    eprintln!("     cargo run weapon <name> <input-path> <num-threads> <output-file-name>");
+    eprintln!("     cargo run query <input-path> <num-threads> <output-file-name> [opciones]");
+    eprintln!("     cargo run bench <input-path> <max-threads> <results.csv|.json> [opciones]");
+    eprintln!("Opciones:");
+    eprintln!("  --categories                Agrega estadisticas por categoria de arma");
+    eprintln!("  --categories-file <path>    Archivo JSON/TOML que redefine las categorias");
+    eprintln!("  --placement                 Agrega estadisticas por posicion final del asesino");
+    eprintln!("  --rivalries                 Agrega los pares de jugadores que mas se mataron");
+    eprintln!("  --graph-export <path>       Exporta el grafo de muertes (.graphml, .dot o .csv)");
+    eprintln!("  --filter <expr>             Solo procesa las lineas que cumplen la expresion,");
+    eprintln!("                              por ejemplo 'map == \"ERANGEL\" && time > 600'");
+    eprintln!("  --state <path>              Solo procesa los archivos nuevos o modificados");
+    eprintln!("  --checkpoint <path>         Guarda el progreso de los archivos procesados");
+    eprintln!("  --checkpoint-interval <n>   Guarda cada n archivos, o cada n segundos con ns (1)");
+    eprintln!("  --resume                    Continua desde el checkpoint sin repetir archivos");
+    eprintln!("  --partial                   Si se cancela, escribe un reporte parcial");
+    eprintln!("  --progress[=<modo>]         Progreso en stderr: auto, text, json o none");
+    eprintln!("  --metrics                   Agrega los tiempos por fase a la salida");
+    eprintln!("  --approx                    Calcula el top de asesinos con memoria acotada");
+    eprintln!("  --approx-capacity <n>       Jugadores que sigue --approx (1000)");
+    eprintln!("  --distinct <exact|approx>   Cuenta jugadores, partidas y pares distintos");
+    eprintln!("  --hll-precision <4-18>      Precision de los sketches de --distinct approx");
+    eprintln!("  --group-by <columnas>       Columnas por las que agrupa query");
+    eprintln!("  --aggregate <funciones>     Funciones de query (count, sum, avg, ...)");
+    eprintln!("  --backend <nombre>          Reparto: rayon, scoped, channels o pipeline");
+    eprintln!("  --io <buffered|mmap>        Lectura con BufReader o archivos mapeados en memoria");
+    eprintln!("  --input-format <formato>    csv (por defecto), parquet o arrow (Arrow IPC)");
+    eprintln!("  --io-threads <n>            Hilos de I/O, solo con --backend pipeline (1)");
+    eprintln!("  --aggregator-shards <n>     Hilos que combinan los bloques en pipeline (2)");
+    eprintln!("  --channel-capacity <n>      Mensajes por canal de pipeline (16)");
+    eprintln!("  --aggregation <modo>        Muertes por jugador: reduce o sharded");
+    eprintln!("  --memory-limit <tamaño>     Limite de jugadores en memoria, ej. 512M");
+    eprintln!("  --thread-name <prefijo>     Prefijo de los nombres de los hilos (tp-worker)");
+    eprintln!("  --stack-size <tamaño>       Tamaño de la pila de cada hilo, ej. 8M");
+    eprintln!("  --pin-threads               Fija cada hilo de trabajo a un nucleo");
+    eprintln!("  --threads <n,n,...>         Hilos que mide bench (potencias de 2 hasta max)");
+    eprintln!(
+        "  --warmup <n>                Ejecuciones de bench sin medir por cantidad de hilos (1)"
+    );
+    eprintln!(
+        "  --repetitions <n>           Ejecuciones de bench medidas por cantidad de hilos (5)"
+    );
+    std::process::exit(1);
+}
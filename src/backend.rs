@@ -0,0 +1,474 @@
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{self, BufRead, BufReader, Lines},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, unbounded};
+use rayon::iter::{
+    IntoParallelIterator, IntoParallelRefIterator, ParallelBridge, ParallelIterator,
+};
+
+use crate::{
+    cancellation::CancellationToken,
+    metrics::Metrics,
+    mmap_reader::{self, IoMode, CHUNK_SIZE},
+    pipeline::{PipelineBackend, PipelineConfig},
+    thread_pool::ThreadPoolConfig,
+    tokenizer,
+};
+
+/// Lines of a file taken at once by a task of the `rayon` backend with buffered reading.
+const LINES_PER_BATCH: usize = 1024;
+
+thread_local! {
+    /// Index of the worker of the backends other than `rayon` that runs in this thread.
+    static WORKER_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Returns the index of the worker that runs in the current thread, for the thread pool of
+/// rayon or for the threads of the other backends, or `None` outside of the workers.
+pub fn current_worker_index() -> Option<usize> {
+    WORKER_INDEX
+        .with(Cell::get)
+        .or_else(rayon::current_thread_index)
+}
+
+/// Registers the index of the worker that runs in the current thread.
+pub(crate) fn set_current_worker_index(index: usize) {
+    WORKER_INDEX.with(|worker_index| worker_index.set(Some(index)));
+}
+
+/// Strategy used to spread the work of reading, parsing and aggregating the files
+/// among the threads. All the backends produce the same statistics.
+pub trait ExecutionBackend {
+    /// Folds the lines of each file into results, one for each task, and merges them.
+    /// The lines stop being read when the run is cancelled, and a file cut short is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Paths of the CSV files.
+    /// * `cancellation` - Token that stops the processing.
+    /// * `metrics` - Metrics where the time that each worker spends folding chunks of lines
+    ///   and merging results is added, or `None` to not measure it.
+    /// * `fold_line` - Function that adds a line to a result, or returns it as it is
+    ///   to discard the line.
+    /// * `identity` - Function that creates an empty result.
+    /// * `merge` - Function that merges two results.
+    /// * `on_file_done` - Function called with the result of each completed file
+    ///   and the time spent on it, as soon as the file is completed.
+    #[allow(clippy::too_many_arguments)]
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    );
+}
+
+/// The files and the lines of each file are processed in parallel by the thread pool
+/// of rayon, with work stealing.
+#[derive(Debug, Default)]
+pub struct RayonBackend {
+    io_mode: IoMode,
+}
+
+impl RayonBackend {
+    pub fn new(io_mode: IoMode) -> Self {
+        Self { io_mode }
+    }
+}
+
+impl ExecutionBackend for RayonBackend {
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    ) {
+        let merge = |acc, result| Metrics::measure_busy_time(metrics, || merge(acc, result));
+        paths.par_iter().for_each(|path| {
+            let start = Instant::now();
+            let result = match self.io_mode {
+                IoMode::Buffered => {
+                    let Some(lines) = get_line_iterator(path) else {
+                        return;
+                    };
+                    // Las lineas se reparten en lotes, y cada tarea suma cada lote a su resultado
+                    get_line_batches(lines)
+                        .par_bridge()
+                        .take_any_while(|_| !cancellation.is_cancelled())
+                        .fold(&identity, |acc, batch| {
+                            Metrics::measure_busy_time(metrics, || {
+                                batch
+                                    .into_iter()
+                                    .fold(acc, |acc, line| fold_read_line(acc, line, &fold_line))
+                            })
+                        })
+                        .reduce(&identity, &merge)
+                }
+                IoMode::Mmap => {
+                    let Some(mmap) = mmap_reader::map_file(path) else {
+                        return;
+                    };
+                    // Cada tarea recorre un pedazo del archivo mapeado, sin copiar sus lineas
+                    mmap_reader::split_chunks(&mmap, CHUNK_SIZE)
+                        .into_par_iter()
+                        .map(|chunk| {
+                            Metrics::measure_busy_time(metrics, || {
+                                tokenizer::lines(chunk)
+                                    .take_while(|_| !cancellation.is_cancelled())
+                                    .fold(identity(), |acc, line| {
+                                        mmap_reader::fold_line_bytes(acc, line, &fold_line)
+                                    })
+                            })
+                        })
+                        .reduce(&identity, &merge)
+                }
+            };
+            if !cancellation.is_cancelled() {
+                on_file_done(path, result, start.elapsed());
+            }
+        });
+    }
+}
+
+/// Each file is processed by a single thread. The threads are started with
+/// `std::thread::scope` and take the next file of a queue shared by all of them.
+#[derive(Debug)]
+pub struct ScopedBackend {
+    num_threads: usize,
+    thread_pool_config: ThreadPoolConfig,
+    io_mode: IoMode,
+}
+
+impl ScopedBackend {
+    pub fn new(num_threads: usize, thread_pool_config: ThreadPoolConfig, io_mode: IoMode) -> Self {
+        Self {
+            num_threads: num_threads.max(1),
+            thread_pool_config,
+            io_mode,
+        }
+    }
+}
+
+impl ExecutionBackend for ScopedBackend {
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        _merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    ) {
+        // La cola de archivos es el indice del proximo archivo a tomar
+        let next_file = AtomicUsize::new(0);
+        let thread_pool_config = &self.thread_pool_config;
+        thread::scope(|scope| {
+            (0..self.num_threads).for_each(|worker| {
+                let (next_file, fold_line, identity, on_file_done) =
+                    (&next_file, &fold_line, &identity, &on_file_done);
+                thread_pool_config.spawn_worker(scope, worker, move || {
+                    set_current_worker_index(worker);
+                    while let Some(path) = paths.get(next_file.fetch_add(1, Ordering::Relaxed)) {
+                        if cancellation.is_cancelled() {
+                            break;
+                        }
+                        let start = Instant::now();
+                        if let Some(result) = Metrics::measure_busy_time(metrics, || {
+                            fold_file(path, self.io_mode, cancellation, fold_line, identity)
+                        }) {
+                            on_file_done(path, result, start.elapsed());
+                        }
+                    }
+                });
+            });
+        });
+    }
+}
+
+/// A producer thread sends the files through a channel to the worker threads, which send
+/// the result of each file through another channel to the calling thread.
+#[derive(Debug)]
+pub struct ChannelsBackend {
+    num_threads: usize,
+    thread_pool_config: ThreadPoolConfig,
+    io_mode: IoMode,
+}
+
+impl ChannelsBackend {
+    pub fn new(num_threads: usize, thread_pool_config: ThreadPoolConfig, io_mode: IoMode) -> Self {
+        Self {
+            num_threads: num_threads.max(1),
+            thread_pool_config,
+            io_mode,
+        }
+    }
+}
+
+impl ExecutionBackend for ChannelsBackend {
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        _merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    ) {
+        let (path_sender, path_receiver) = bounded::<&PathBuf>(self.num_threads);
+        let (result_sender, result_receiver) = unbounded();
+        let thread_pool_config = &self.thread_pool_config;
+        thread::scope(|scope| {
+            thread_pool_config.spawn_helper(scope, "files", move || {
+                for path in paths {
+                    if cancellation.is_cancelled() || path_sender.send(path).is_err() {
+                        break;
+                    }
+                }
+            });
+            (0..self.num_threads).for_each(|worker| {
+                let (path_receiver, result_sender) = (path_receiver.clone(), result_sender.clone());
+                let (fold_line, identity) = (&fold_line, &identity);
+                thread_pool_config.spawn_worker(scope, worker, move || {
+                    set_current_worker_index(worker);
+                    for path in path_receiver {
+                        let start = Instant::now();
+                        if let Some(result) = Metrics::measure_busy_time(metrics, || {
+                            fold_file(path, self.io_mode, cancellation, fold_line, identity)
+                        }) {
+                            let _ = result_sender.send((path, result, start.elapsed()));
+                        }
+                    }
+                });
+            });
+            // El canal de resultados se cierra cuando terminan todos los workers
+            drop(result_sender);
+            for (path, result, duration) in result_receiver {
+                on_file_done(path, result, duration);
+            }
+        });
+    }
+}
+
+/// Backend selected with `--backend`.
+#[derive(Debug)]
+pub enum Backend {
+    Rayon(RayonBackend),
+    Scoped(ScopedBackend),
+    Channels(ChannelsBackend),
+    Pipeline(PipelineBackend),
+}
+
+impl Backend {
+    /// Parses the backend given with `--backend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `rayon` (by default), `scoped`, `channels` or `pipeline`.
+    /// * `num_threads` - Number of worker threads of the backends other than `rayon`,
+    ///   the parser threads of the `pipeline` one.
+    /// * `pipeline_config` - Sizes of the stages of the `pipeline` backend.
+    /// * `thread_pool_config` - Options of the threads of the backends other than `rayon`,
+    ///   whose pool is built apart.
+    /// * `io_mode` - How the lines of the files are read. The `pipeline` backend reads
+    ///   the files in blocks with its own I/O threads, so it only supports `buffered`.
+    ///
+    /// # Returns
+    ///
+    /// The backend, or a message describing why it is not valid.
+    pub fn parse(
+        name: Option<&str>,
+        num_threads: usize,
+        pipeline_config: PipelineConfig,
+        thread_pool_config: ThreadPoolConfig,
+        io_mode: IoMode,
+    ) -> Result<Self, String> {
+        match name.unwrap_or("rayon") {
+            "rayon" => Ok(Backend::Rayon(RayonBackend::new(io_mode))),
+            "scoped" => Ok(Backend::Scoped(ScopedBackend::new(
+                num_threads,
+                thread_pool_config,
+                io_mode,
+            ))),
+            "channels" => Ok(Backend::Channels(ChannelsBackend::new(
+                num_threads,
+                thread_pool_config,
+                io_mode,
+            ))),
+            "pipeline" if io_mode == IoMode::Mmap => Err(
+                "--io mmap no se puede usar con --backend pipeline, que lee los archivos en bloques"
+                    .to_string(),
+            ),
+            "pipeline" => Ok(Backend::Pipeline(PipelineBackend::new(
+                num_threads,
+                pipeline_config,
+                thread_pool_config,
+            ))),
+            other => Err(format!(
+                "backend invalido '{}', los backends validos son: rayon, scoped, channels y pipeline",
+                other
+            )),
+        }
+    }
+}
+
+impl ExecutionBackend for Backend {
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    ) {
+        match self {
+            Backend::Rayon(backend) => backend.map_reduce_files(
+                paths,
+                cancellation,
+                metrics,
+                fold_line,
+                identity,
+                merge,
+                on_file_done,
+            ),
+            Backend::Scoped(backend) => backend.map_reduce_files(
+                paths,
+                cancellation,
+                metrics,
+                fold_line,
+                identity,
+                merge,
+                on_file_done,
+            ),
+            Backend::Channels(backend) => backend.map_reduce_files(
+                paths,
+                cancellation,
+                metrics,
+                fold_line,
+                identity,
+                merge,
+                on_file_done,
+            ),
+            Backend::Pipeline(backend) => backend.map_reduce_files(
+                paths,
+                cancellation,
+                metrics,
+                fold_line,
+                identity,
+                merge,
+                on_file_done,
+            ),
+        }
+    }
+}
+
+/// Folds the lines of a file sequentially into a single result, in the current thread.
+///
+/// # Returns
+///
+/// The result of the file, or `None` if it could not be opened or was cut short by the cancellation.
+fn fold_file<T>(
+    path: &PathBuf,
+    io_mode: IoMode,
+    cancellation: &CancellationToken,
+    fold_line: impl Fn(T, &str) -> T,
+    identity: impl Fn() -> T,
+) -> Option<T> {
+    match io_mode {
+        IoMode::Buffered => fold_lines(
+            get_line_iterator(path)?,
+            cancellation,
+            identity,
+            |acc, line| fold_read_line(acc, line, &fold_line),
+        ),
+        IoMode::Mmap => {
+            let mmap = mmap_reader::map_file(path)?;
+            fold_lines(
+                tokenizer::lines(&mmap),
+                cancellation,
+                identity,
+                |acc, line| mmap_reader::fold_line_bytes(acc, line, &fold_line),
+            )
+        }
+    }
+}
+
+/// Folds the lines of a file into a single result.
+///
+/// # Returns
+///
+/// The result of the lines, or `None` if they were cut short by the cancellation.
+fn fold_lines<T, L>(
+    lines: impl Iterator<Item = L>,
+    cancellation: &CancellationToken,
+    identity: impl Fn() -> T,
+    fold: impl Fn(T, L) -> T,
+) -> Option<T> {
+    let mut result = identity();
+    for line in lines {
+        if cancellation.is_cancelled() {
+            return None;
+        }
+        result = fold(result, line);
+    }
+    Some(result)
+}
+
+/// Groups the lines read from a file in batches of `LINES_PER_BATCH` lines, so that
+/// the tasks take many lines at once from the file.
+fn get_line_batches(
+    mut lines: impl Iterator<Item = io::Result<String>>,
+) -> impl Iterator<Item = Vec<io::Result<String>>> {
+    std::iter::from_fn(move || {
+        let batch: Vec<_> = lines.by_ref().take(LINES_PER_BATCH).collect();
+        (!batch.is_empty()).then_some(batch)
+    })
+}
+
+/// Adds a line read from a file to a result. The lines that could not be read are discarded.
+fn fold_read_line<T>(acc: T, line: io::Result<String>, fold_line: impl Fn(T, &str) -> T) -> T {
+    match line {
+        Ok(line) => fold_line(acc, &line),
+        Err(e) => {
+            eprintln!("Error al leer la linea: {}", e);
+            acc
+        }
+    }
+}
+
+/// Opens a CSV file.
+///
+/// # Arguments
+///
+/// * `path` - Path of the CSV file.
+///
+/// # Returns
+///
+/// Returns an iterator over the lines of the file, or `None` if it could not be opened.
+fn get_line_iterator(path: &PathBuf) -> Option<Lines<BufReader<File>>> {
+    match File::open(path) {
+        Ok(file) => Some(BufReader::new(file).lines()),
+        Err(e) => {
+            eprintln!("Error al abrir el archivo: {}", e);
+            None
+        }
+    }
+}
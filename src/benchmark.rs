@@ -0,0 +1,256 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde_json::json;
+
+pub const DEFAULT_WARMUP: usize = 1;
+pub const DEFAULT_REPETITIONS: usize = 5;
+/// Phase with the time of the whole run.
+pub const TOTAL_PHASE: &str = "total";
+
+/// Statistics of the time of a phase over the repetitions, in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseStats {
+    mean: f64,
+    stddev: f64,
+    min: f64,
+}
+
+impl PhaseStats {
+    /// Calculates the statistics of the times of the repetitions.
+    /// The standard deviation is the sample one, 0 with a single repetition.
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let count = samples.len().max(1) as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        let variance = match samples.len() {
+            0 | 1 => 0.0,
+            n => samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64,
+        };
+        Self {
+            mean,
+            stddev: variance.sqrt(),
+            min: samples.iter().copied().reduce(f64::min).unwrap_or(0.0),
+        }
+    }
+
+    pub fn get_mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn get_stddev(&self) -> f64 {
+        self.stddev
+    }
+
+    pub fn get_min(&self) -> f64 {
+        self.min
+    }
+}
+
+/// Statistics of each phase with a number of threads.
+#[derive(Debug, Clone)]
+pub struct ThreadsResult {
+    threads: usize,
+    phases: Vec<(String, PhaseStats)>,
+}
+
+impl ThreadsResult {
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn get_phases(&self) -> &[(String, PhaseStats)] {
+        &self.phases
+    }
+
+    pub fn get_phase(&self, phase: &str) -> Option<&PhaseStats> {
+        self.phases
+            .iter()
+            .find(|(name, _)| name == phase)
+            .map(|(_, stats)| stats)
+    }
+}
+
+/// Runs the pipeline with each number of threads, discarding the warm-up runs,
+/// and calculates the statistics of the time of each phase over the repetitions.
+#[derive(Debug)]
+pub struct Benchmark {
+    thread_counts: Vec<usize>,
+    warmup: usize,
+    repetitions: usize,
+}
+
+impl Benchmark {
+    /// Creates a benchmark. The speedup is relative to one thread,
+    /// so one thread is always measured.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_counts` - Numbers of threads to measure.
+    /// * `warmup` - Runs before the measured ones with each number of threads.
+    /// * `repetitions` - Measured runs with each number of threads, at least 1.
+    pub fn new(mut thread_counts: Vec<usize>, warmup: usize, repetitions: usize) -> Self {
+        thread_counts.push(1);
+        thread_counts.retain(|&threads| threads > 0);
+        thread_counts.sort_unstable();
+        thread_counts.dedup();
+        Self {
+            thread_counts,
+            warmup,
+            repetitions: repetitions.max(1),
+        }
+    }
+
+    pub fn get_thread_counts(&self) -> &[usize] {
+        &self.thread_counts
+    }
+
+    /// Runs the benchmark.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_once` - Runs the pipeline once with the given number of threads
+    ///   and returns the time of each phase.
+    ///
+    /// # Returns
+    ///
+    /// The statistics of each number of threads, from fewer to more threads.
+    pub fn run(&self, run_once: impl Fn(usize) -> Vec<(String, Duration)>) -> Vec<ThreadsResult> {
+        self.thread_counts
+            .iter()
+            .map(|&threads| {
+                (0..self.warmup).for_each(|_| {
+                    run_once(threads);
+                });
+                let runs: Vec<Vec<(String, Duration)>> =
+                    (0..self.repetitions).map(|_| run_once(threads)).collect();
+                ThreadsResult {
+                    threads,
+                    phases: get_phase_stats(&runs),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Calculates the statistics of each phase, in the order of the first run.
+/// A phase missing in a run counts as 0 seconds in that run.
+fn get_phase_stats(runs: &[Vec<(String, Duration)>]) -> Vec<(String, PhaseStats)> {
+    let phase_names: Vec<&String> = runs
+        .first()
+        .map(|run| run.iter().map(|(name, _)| name).collect())
+        .unwrap_or_default();
+    phase_names
+        .into_iter()
+        .map(|phase| {
+            let samples: Vec<f64> = runs
+                .iter()
+                .map(|run| {
+                    run.iter()
+                        .find(|(name, _)| name == phase)
+                        .map_or(0.0, |(_, duration)| duration.as_secs_f64())
+                })
+                .collect();
+            (phase.clone(), PhaseStats::from_samples(&samples))
+        })
+        .collect()
+}
+
+/// Returns the speedup of a phase relative to one thread, the mean time with one thread
+/// divided by the mean time with the given threads, and the efficiency, the speedup per thread.
+///
+/// # Arguments
+///
+/// * `results` - Results of the benchmark, with one thread included.
+/// * `result` - Result of the number of threads to compare.
+/// * `phase` - Name of the phase.
+///
+/// # Returns
+///
+/// The speedup and the efficiency, or `None` if they cannot be calculated.
+pub fn get_speedup(
+    results: &[ThreadsResult],
+    result: &ThreadsResult,
+    phase: &str,
+) -> Option<(f64, f64)> {
+    let baseline = results.iter().find(|result| result.threads == 1)?;
+    let baseline_mean = baseline.get_phase(phase)?.mean;
+    let mean = result.get_phase(phase)?.mean;
+    if mean <= 0.0 {
+        return None;
+    }
+    let speedup = baseline_mean / mean;
+    Some((speedup, speedup / result.threads as f64))
+}
+
+/// Writes the results in CSV (`.csv`), with a row for each number of threads and phase,
+/// or in JSON (any other extension).
+///
+/// # Arguments
+///
+/// * `output_file_name` - Path of the output file.
+/// * `results` - Results of the benchmark.
+///
+/// # Returns
+///
+/// A Result with the result of the operation
+pub fn write_results(output_file_name: &str, results: &[ThreadsResult]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(output_file_name)?);
+    match Path::new(output_file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+    {
+        Some("csv") => write_csv(&mut file, results)?,
+        _ => serde_json::to_writer_pretty(&mut file, &results_to_json(results))?,
+    }
+    file.flush()
+}
+
+fn write_csv(file: &mut impl Write, results: &[ThreadsResult]) -> std::io::Result<()> {
+    writeln!(
+        file,
+        "threads,phase,mean_seconds,stddev_seconds,min_seconds,speedup,efficiency"
+    )?;
+    for result in results {
+        for (phase, stats) in &result.phases {
+            let (speedup, efficiency) = match get_speedup(results, result, phase) {
+                Some((speedup, efficiency)) => (speedup.to_string(), efficiency.to_string()),
+                None => (String::new(), String::new()),
+            };
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{}",
+                result.threads, phase, stats.mean, stats.stddev, stats.min, speedup, efficiency
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the results in a JSON format.
+pub fn results_to_json(results: &[ThreadsResult]) -> serde_json::Value {
+    json!(results
+        .iter()
+        .map(|result| {
+            let phases: serde_json::Map<String, serde_json::Value> = result
+                .phases
+                .iter()
+                .map(|(phase, stats)| {
+                    let speedup = get_speedup(results, result, phase);
+                    let phase_json = json!({
+                        "mean_seconds": stats.mean,
+                        "stddev_seconds": stats.stddev,
+                        "min_seconds": stats.min,
+                        "speedup": speedup.map(|(speedup, _)| speedup),
+                        "efficiency": speedup.map(|(_, efficiency)| efficiency),
+                    });
+                    (phase.clone(), phase_json)
+                })
+                .collect();
+            json!({ "threads": result.threads, "phases": phases })
+        })
+        .collect::<Vec<_>>())
+}
@@ -0,0 +1,55 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Exit code of a process stopped by SIGINT, by convention `128 + 2`.
+pub const CANCELLED_EXIT_CODE: i32 = 130;
+
+/// Flag shared by the workers to stop processing when the run is cancelled.
+/// The clones share the same flag, so cancelling one cancels all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cancels the run.
+    ///
+    /// # Returns
+    ///
+    /// True if the run was already cancelled.
+    pub fn cancel(&self) -> bool {
+        self.cancelled.swap(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Cancels the run when the process receives SIGINT or SIGTERM.
+    /// The workers stop at the next line, and a second signal exits immediately.
+    ///
+    /// # Returns
+    ///
+    /// A message describing why the signals could not be handled.
+    pub fn cancel_on_signals(&self) -> Result<(), String> {
+        let token = self.clone();
+        ctrlc::set_handler(move || {
+            if token.cancel() {
+                eprintln!("Segunda señal recibida, se termina inmediatamente");
+                std::process::exit(CANCELLED_EXIT_CODE);
+            }
+            eprintln!(
+                "Cancelando, se dejan de leer los archivos (otra señal termina inmediatamente)"
+            );
+        })
+        .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use arrow_array::{cast::AsArray, Array, ArrayRef, RecordBatch};
+use arrow_ipc::reader::FileReader;
+use arrow_schema::{ArrowError, DataType, Schema};
+use parquet::arrow::{
+    arrow_reader::{ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder},
+    ProjectionMask,
+};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    cancellation::CancellationToken,
+    csvs_processor::{
+        KILLER_NAME, KILLER_PLACEMENT, KILLER_POSITION_X, KILLER_POSITION_Y, MAP, MATCH_ID,
+        NUMBER_OF_FIELDS, TIME, VICTIM_NAME, VICTIM_PLACEMENT, VICTIM_POSITION_X,
+        VICTIM_POSITION_Y, WEAPON,
+    },
+    metrics::Metrics,
+};
+
+/// Field of the CSV line and name in the columnar files of each column of the dataset.
+pub const FIELD_NAMES: [(usize, &str); NUMBER_OF_FIELDS] = [
+    (WEAPON, "killed_by"),
+    (KILLER_NAME, "killer_name"),
+    (KILLER_PLACEMENT, "killer_placement"),
+    (KILLER_POSITION_X, "killer_position_x"),
+    (KILLER_POSITION_Y, "killer_position_y"),
+    (MAP, "map"),
+    (MATCH_ID, "match_id"),
+    (TIME, "time"),
+    (VICTIM_NAME, "victim_name"),
+    (VICTIM_PLACEMENT, "victim_placement"),
+    (VICTIM_POSITION_X, "victim_position_x"),
+    (VICTIM_POSITION_Y, "victim_position_y"),
+];
+
+/// Format of the columnar files, whose columns are read apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnarFormat {
+    /// Parquet files, read by row group.
+    Parquet,
+    /// Files in the Arrow IPC file format, read by record batch.
+    Arrow,
+}
+
+/// Format of the input files, selected with `--input-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputFormat {
+    /// CSV files, whose lines are read by the backend of the run.
+    #[default]
+    Csv,
+    Columnar(ColumnarFormat),
+}
+
+impl InputFormat {
+    /// Parses the format given with `--input-format`.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - `csv` (by default), `parquet` or `arrow`.
+    ///
+    /// # Returns
+    ///
+    /// The format, or a message describing why it is not valid.
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.unwrap_or("csv") {
+            "csv" => Ok(InputFormat::Csv),
+            "parquet" => Ok(InputFormat::Columnar(ColumnarFormat::Parquet)),
+            "arrow" => Ok(InputFormat::Columnar(ColumnarFormat::Arrow)),
+            other => Err(format!(
+                "formato de entrada invalido '{}', los formatos validos son: csv, parquet y arrow",
+                other
+            )),
+        }
+    }
+}
+
+/// Maps and reduces the rows of columnar files with the thread pool of rayon.
+/// The files are processed in parallel, and the row groups (or the record batches of the
+/// Arrow files) of each file too. Only the columns of the given fields are read, and each row
+/// is mapped as the fields of a CSV line: the other fields and the null values are empty.
+/// The rows stop being read when the run is cancelled, and a file cut short is discarded,
+/// as a file with a row group that cannot be read.
+///
+/// # Arguments
+///
+/// * `format` - Format of the files.
+/// * `paths` - Paths of the files.
+/// * `fields` - Fields of the CSV line that are read.
+/// * `cancellation` - Token that stops the processing.
+/// * `metrics` - Metrics where the time that each worker spends folding row groups
+///   and merging results is added, or `None` to not measure it.
+/// * `fold_row` - Function that adds the fields of a row and the bytes of the file that
+///   correspond to it to a result, or returns it as it is to discard the row.
+/// * `identity` - Function that creates an empty result.
+/// * `merge` - Function that merges two results.
+/// * `on_file_done` - Function called with the result of each completed file
+///   and the time spent on it, as soon as the file is completed.
+#[allow(clippy::too_many_arguments)]
+pub fn map_reduce_files<T: Send>(
+    format: ColumnarFormat,
+    paths: &[PathBuf],
+    fields: &[usize],
+    cancellation: &CancellationToken,
+    metrics: Option<&Metrics>,
+    fold_row: impl Fn(T, &[&str], usize) -> T + Sync + Send,
+    identity: impl Fn() -> T + Sync + Send,
+    merge: impl Fn(T, T) -> T + Sync + Send,
+    on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+) {
+    let rows = RowMapper {
+        cancellation,
+        metrics,
+        fold_row,
+        identity,
+        merge,
+    };
+    paths.par_iter().for_each(|path| {
+        let start = Instant::now();
+        let result = match format {
+            ColumnarFormat::Parquet => fold_parquet_file(path, fields, &rows),
+            ColumnarFormat::Arrow => fold_arrow_file(path, fields, &rows),
+        };
+        if let Some(result) = result.filter(|_| !cancellation.is_cancelled()) {
+            on_file_done(path, result, start.elapsed());
+        }
+    });
+}
+
+/// Functions that fold and merge the rows of the files.
+struct RowMapper<'a, F, I, G> {
+    cancellation: &'a CancellationToken,
+    metrics: Option<&'a Metrics>,
+    fold_row: F,
+    identity: I,
+    merge: G,
+}
+
+impl<T, F, I, G> RowMapper<'_, F, I, G>
+where
+    F: Fn(T, &[&str], usize) -> T,
+    I: Fn() -> T,
+    G: Fn(T, T) -> T,
+{
+    /// Folds the rows of the record batches of a row group into a single result.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file, for the errors.
+    /// * `batches` - Record batches with the columns of the fields.
+    /// * `columns` - Field and name of each column.
+    /// * `rows` - Rows of the row group.
+    /// * `bytes` - Bytes of the file that correspond to the row group.
+    ///
+    /// # Returns
+    ///
+    /// The result of the rows, or `None` if a batch could not be read
+    /// or the rows were cut short by the cancellation.
+    fn fold_batches(
+        &self,
+        path: &Path,
+        batches: impl Iterator<Item = Result<RecordBatch, ArrowError>>,
+        columns: &[(usize, &str)],
+        rows: usize,
+        bytes: usize,
+    ) -> Option<T> {
+        // El tiempo de los workers se mide por grupo de filas, no por fila
+        Metrics::measure_busy_time(self.metrics, || {
+            let mut result = (self.identity)();
+            let mut row = 0;
+            for batch in batches {
+                let batch = batch
+                    .map_err(|e| eprintln!("Error al leer el archivo {}: {}", path.display(), e))
+                    .ok()?;
+                let texts = get_text_columns(path, &batch, columns)?;
+                let texts: Vec<(usize, _)> = texts
+                    .iter()
+                    .map(|(field, text)| (*field, text.as_string::<i32>()))
+                    .collect();
+                for index in 0..batch.num_rows() {
+                    if self.cancellation.is_cancelled() {
+                        return None;
+                    }
+                    // Los campos se guardan en el stack, como los de una linea de un CSV
+                    let mut fields = [""; NUMBER_OF_FIELDS];
+                    for (field, text) in &texts {
+                        if text.is_valid(index) {
+                            fields[*field] = text.value(index);
+                        }
+                    }
+                    result = (self.fold_row)(result, &fields, share(bytes, row, rows));
+                    row += 1;
+                }
+            }
+            Some(result)
+        })
+    }
+
+    /// Merges the results of two row groups, or returns `None` if one of them could not be read.
+    fn merge_results(&self, acc: Option<T>, result: Option<T>) -> Option<T> {
+        let (acc, result) = (acc?, result?);
+        Metrics::measure_busy_time(self.metrics, || Some((self.merge)(acc, result)))
+    }
+}
+
+/// Maps and reduces the rows of a Parquet file, reading each row group in parallel.
+///
+/// # Returns
+///
+/// The result of the file, or `None` if it could not be read or was cut short by the cancellation.
+fn fold_parquet_file<T: Send>(
+    path: &Path,
+    fields: &[usize],
+    rows: &RowMapper<
+        impl Fn(T, &[&str], usize) -> T + Sync,
+        impl Fn() -> T + Sync,
+        impl Fn(T, T) -> T + Sync,
+    >,
+) -> Option<T> {
+    let file = open_file(path)?;
+    let file_bytes = get_file_bytes(&file);
+    // Los metadatos se leen una vez, y cada grupo de filas abre el archivo con ellos
+    let metadata = ArrowReaderMetadata::load(&file, ArrowReaderOptions::new())
+        .map_err(|e| eprintln!("Error al leer el archivo {}: {}", path.display(), e))
+        .ok()?;
+    let columns = find_columns(path, metadata.schema(), fields)?;
+    let projection = ProjectionMask::roots(
+        metadata.parquet_schema(),
+        columns.iter().map(|(index, _, _)| *index),
+    );
+    let columns: Vec<(usize, &str)> = columns
+        .into_iter()
+        .map(|(_, field, name)| (field, name))
+        .collect();
+    let row_groups = metadata.metadata().row_groups();
+    (0..row_groups.len())
+        .into_par_iter()
+        .map(|row_group| {
+            let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(
+                open_file(path)?,
+                metadata.clone(),
+            )
+            .with_projection(projection.clone())
+            .with_row_groups(vec![row_group])
+            .build()
+            .map_err(|e| eprintln!("Error al leer el archivo {}: {}", path.display(), e))
+            .ok()?;
+            let group_rows = row_groups[row_group].num_rows() as usize;
+            let group_bytes = share(file_bytes, row_group, row_groups.len());
+            rows.fold_batches(path, reader, &columns, group_rows, group_bytes)
+        })
+        .reduce(
+            || Some((rows.identity)()),
+            |acc, result| rows.merge_results(acc, result),
+        )
+}
+
+/// Maps and reduces the rows of an Arrow IPC file, reading each record batch in parallel.
+///
+/// # Returns
+///
+/// The result of the file, or `None` if it could not be read or was cut short by the cancellation.
+fn fold_arrow_file<T: Send>(
+    path: &Path,
+    fields: &[usize],
+    rows: &RowMapper<
+        impl Fn(T, &[&str], usize) -> T + Sync,
+        impl Fn() -> T + Sync,
+        impl Fn(T, T) -> T + Sync,
+    >,
+) -> Option<T> {
+    let open_reader = |file, projection| {
+        FileReader::try_new(BufReader::new(file), projection)
+            .map_err(|e| eprintln!("Error al leer el archivo {}: {}", path.display(), e))
+            .ok()
+    };
+    let file = open_file(path)?;
+    let file_bytes = get_file_bytes(&file);
+    let reader = open_reader(file, None)?;
+    let columns = find_columns(path, &reader.schema(), fields)?;
+    let projection: Vec<usize> = columns.iter().map(|(index, _, _)| *index).collect();
+    let columns: Vec<(usize, &str)> = columns
+        .into_iter()
+        .map(|(_, field, name)| (field, name))
+        .collect();
+    let batches = reader.num_batches();
+    (0..batches)
+        .into_par_iter()
+        .map(|batch| {
+            let mut reader = open_reader(open_file(path)?, Some(projection.clone()))?;
+            reader
+                .set_index(batch)
+                .map_err(|e| eprintln!("Error al leer el archivo {}: {}", path.display(), e))
+                .ok()?;
+            let batch_bytes = share(file_bytes, batch, batches);
+            // Un bloque del archivo tiene un solo lote de filas, que se conocen al leerlo
+            let batch = reader.next()?;
+            let batch_rows = batch.as_ref().map_or(0, RecordBatch::num_rows);
+            rows.fold_batches(
+                path,
+                std::iter::once(batch),
+                &columns,
+                batch_rows,
+                batch_bytes,
+            )
+        })
+        .reduce(
+            || Some((rows.identity)()),
+            |acc, result| rows.merge_results(acc, result),
+        )
+}
+
+/// Finds the columns of the given fields in the schema of a file.
+///
+/// # Returns
+///
+/// The index in the schema, the field and the name of each column,
+/// or `None` if a column is missing.
+fn find_columns(
+    path: &Path,
+    schema: &Schema,
+    fields: &[usize],
+) -> Option<Vec<(usize, usize, &'static str)>> {
+    let mut columns: Vec<(usize, usize, &str)> = Vec::new();
+    for &(field, name) in FIELD_NAMES
+        .iter()
+        .filter(|(field, _)| fields.contains(field))
+    {
+        match schema.index_of(name) {
+            Ok(index) => columns.push((index, field, name)),
+            Err(_) => {
+                eprintln!("El archivo {} no tiene la columna {}", path.display(), name);
+                return None;
+            }
+        }
+    }
+    // La proyeccion devuelve las columnas en el orden del archivo
+    columns.sort_unstable();
+    Some(columns)
+}
+
+/// Returns the columns of a record batch as text, the numbers are written as in a CSV line.
+fn get_text_columns(
+    path: &Path,
+    batch: &RecordBatch,
+    columns: &[(usize, &str)],
+) -> Option<Vec<(usize, ArrayRef)>> {
+    columns
+        .iter()
+        .map(|&(field, name)| {
+            let column = batch.column_by_name(name)?;
+            match column.data_type() {
+                DataType::Utf8 => Some((field, Arc::clone(column))),
+                data_type => arrow_cast::cast(column, &DataType::Utf8)
+                    .map(|text| (field, text))
+                    .map_err(|e| {
+                        eprintln!(
+                            "La columna {} del archivo {} no se puede leer como texto ({}): {}",
+                            name,
+                            path.display(),
+                            data_type,
+                            e
+                        )
+                    })
+                    .ok(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the part of a total that corresponds to the `index` of `parts` equal parts,
+/// such that the parts add up to the total.
+fn share(total: usize, index: usize, parts: usize) -> usize {
+    match parts {
+        0 => 0,
+        _ => total * (index + 1) / parts - total * index / parts,
+    }
+}
+
+fn open_file(path: &Path) -> Option<File> {
+    match File::open(path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("Error al abrir el archivo: {}", e);
+            None
+        }
+    }
+}
+
+fn get_file_bytes(file: &File) -> usize {
+    file.metadata()
+        .map_or(0, |metadata| metadata.len() as usize)
+}
@@ -0,0 +1,95 @@
+use crate::csvs_processor::{
+    KILLER_NAME, KILLER_PLACEMENT, KILLER_POSITION_X, KILLER_POSITION_Y, MAP, MATCH_ID, TIME,
+    VICTIM_NAME, VICTIM_PLACEMENT, VICTIM_POSITION_X, VICTIM_POSITION_Y, WEAPON,
+};
+
+/// A column that can be used in filters and queries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    /// A text field of the CSV line.
+    Text(usize),
+    /// A numeric field of the CSV line.
+    Number(usize),
+    /// The distance between the killer and the victim, calculated from their positions.
+    Distance,
+}
+
+/// Names of the columns, with the field of the CSV line they read.
+const COLUMNS: [(&str, Column); 14] = [
+    ("weapon", Column::Text(WEAPON)),
+    ("killed_by", Column::Text(WEAPON)),
+    ("killer_name", Column::Text(KILLER_NAME)),
+    ("killer_placement", Column::Number(KILLER_PLACEMENT)),
+    ("killer_position_x", Column::Number(KILLER_POSITION_X)),
+    ("killer_position_y", Column::Number(KILLER_POSITION_Y)),
+    ("map", Column::Text(MAP)),
+    ("match_id", Column::Text(MATCH_ID)),
+    ("time", Column::Number(TIME)),
+    ("victim_name", Column::Text(VICTIM_NAME)),
+    ("victim_placement", Column::Number(VICTIM_PLACEMENT)),
+    ("victim_position_x", Column::Number(VICTIM_POSITION_X)),
+    ("victim_position_y", Column::Number(VICTIM_POSITION_Y)),
+    ("distance", Column::Distance),
+];
+
+impl Column {
+    /// Returns the column with the given name, if it exists.
+    pub fn find(name: &str) -> Option<Column> {
+        COLUMNS
+            .iter()
+            .find(|(column_name, _)| *column_name == name)
+            .map(|(_, column)| *column)
+    }
+
+    /// Returns the names of all the columns, separated by commas.
+    pub fn get_names() -> String {
+        COLUMNS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    /// Returns the fields of the CSV line read by the column.
+    /// The distance reads the positions of the killer and the victim.
+    pub fn get_fields(&self) -> Vec<usize> {
+        match self {
+            Column::Text(field) | Column::Number(field) => vec![*field],
+            Column::Distance => vec![
+                KILLER_POSITION_X,
+                KILLER_POSITION_Y,
+                VICTIM_POSITION_X,
+                VICTIM_POSITION_Y,
+            ],
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, Column::Text(_))
+    }
+
+    /// Returns the text of the column in the line.
+    /// The distance has no text because it is not a field of the line.
+    pub fn get_text<'a>(&self, fields: &[&'a str]) -> Option<&'a str> {
+        match self {
+            Column::Text(field) | Column::Number(field) => fields.get(*field).copied(),
+            Column::Distance => None,
+        }
+    }
+
+    /// Returns the value of the column in the line, if it is a number.
+    pub fn get_number(&self, fields: &[&str], distance: Option<f64>) -> Option<f64> {
+        match self {
+            Column::Text(field) | Column::Number(field) => {
+                fields.get(*field).and_then(|f| parse_number(f))
+            }
+            Column::Distance => distance,
+        }
+    }
+}
+
+/// Parses a numeric field of a CSV line with a fast float parser,
+/// that accepts the same numbers as `str::parse::<f64>`.
+pub fn parse_number(field: &str) -> Option<f64> {
+    fast_float2::parse(field).ok()
+}
@@ -1,71 +1,294 @@
 use crate::{
-    argument_parser::ArgumentParser, top_calculator::TopCalculator, weapon_stats::WeaponStats,
+    analysis_state::{self, AnalysisState, CheckpointInterval, FileState, StateLog},
+    argument_parser::{ArgumentParser, Command},
+    backend::{Backend, ExecutionBackend},
+    cancellation::CancellationToken,
+    columnar_reader::{self, InputFormat},
+    columns::parse_number,
+    distinct_stats::{DistinctMode, DistinctStats},
+    filter::Filter,
+    graph_exporter::GraphExporter,
+    hyper_log_log,
+    kill_graph::KillGraph,
+    memory_usage::{self, AggregateSizes},
+    metrics::{Metrics, READ_PARSE_PHASE, REDUCE_PHASE, SCAN_PHASE, TOP_PHASE, WRITE_PHASE},
+    mmap_reader::IoMode,
+    pipeline::PipelineConfig,
+    placement_stats::PlacementStats,
+    player_profile::PlayerProfile,
+    progress::Progress,
+    query::{self, Query},
+    sharded_map::ShardedMap,
+    space_saving::SpaceSaving,
+    spill::{self, SpillStore, DEFAULT_SPILL_PARTITIONS},
+    thread_pool::ThreadPoolConfig,
+    tokenizer,
+    top_calculator::{TopCalculator, TOP_PLAYERS_OF_THE_GAME, TOP_USERS_OF_THE_WEAPON},
+    weapon_categories::WeaponCategories,
+    weapon_stats::WeaponStats,
     writer::Writer,
 };
-use rayon::iter::{IntoParallelRefIterator, IterBridge, ParallelBridge, ParallelIterator};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader, Lines},
+    fs,
     path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
     time::Instant,
 };
 
 type NumberOfDeathsAndDistances = HashMap<String, WeaponStats>;
 type PlayersWeapons = HashMap<String, HashMap<String, i32>>;
-type MappedItem = (NumberOfDeathsAndDistances, PlayersWeapons);
-type ProcessedData = (NumberOfDeathsAndDistances, PlayersWeapons);
-type ReducedItem = (NumberOfDeathsAndDistances, PlayersWeapons);
 
-const KILLER_NAME: usize = 1;
-const KILLER_POSITION_X: usize = 3;
-const KILLER_POSITION_Y: usize = 4;
-const VICTIM_POSITION_X: usize = 10;
-const VICTIM_POSITION_Y: usize = 11;
+const DEFAULT_TOP_KILLERS_CAPACITY: usize = 1000;
+/// The summary has to monitor at least the players of the top.
+const MIN_TOP_KILLERS_CAPACITY: usize = 10;
+
+pub const WEAPON: usize = 0;
+pub const KILLER_NAME: usize = 1;
+pub const KILLER_PLACEMENT: usize = 2;
+pub const KILLER_POSITION_X: usize = 3;
+pub const KILLER_POSITION_Y: usize = 4;
+pub const MAP: usize = 5;
+pub const MATCH_ID: usize = 6;
+pub const TIME: usize = 7;
+pub const VICTIM_NAME: usize = 8;
+pub const VICTIM_PLACEMENT: usize = 9;
+pub const VICTIM_POSITION_X: usize = 10;
+pub const VICTIM_POSITION_Y: usize = 11;
+/// Fields of a CSV line that are read, the ones after them are ignored.
+pub const NUMBER_OF_FIELDS: usize = VICTIM_POSITION_Y + 1;
+
+/// Statistics accumulated from the CSV lines.
+/// The optional statistics are only computed when they were requested.
+#[derive(Serialize, Deserialize)]
+pub struct ProcessedData {
+    weapons: NumberOfDeathsAndDistances,
+    players_weapons: PlayersWeapons,
+    /// Estimated size of `players_weapons`, to know when it has to be spilled to disk.
+    #[serde(skip)]
+    players_weapons_bytes: usize,
+    placement_stats: Option<PlacementStats>,
+    kill_graph: Option<KillGraph>,
+    player_profile: Option<PlayerProfile>,
+    distinct_stats: Option<DistinctStats>,
+    top_killers_summary: Option<SpaceSaving>,
+}
 
 pub struct Processor {
     start: Instant,
+    placement_enabled: bool,
+    kill_graph_enabled: bool,
+    weapon_categories: Option<WeaponCategories>,
+    distinct_mode: Option<DistinctMode>,
+    top_killers_capacity: Option<usize>,
+    player_name: Option<String>,
+    weapon_name: Option<String>,
+    filter: Option<Filter>,
+    players_weapons_shards: Option<ShardedMap<HashMap<String, i32>>>,
+    players_weapons_spill: Option<SpillStore>,
+    checkpoint_interval: CheckpointInterval,
+    aggregate_sizes: Mutex<AggregateSizes>,
+    backend: Backend,
+    input_format: InputFormat,
+    cancellation: CancellationToken,
+    completed_files: Mutex<Vec<String>>,
+    progress: Arc<Progress>,
+    metrics: Metrics,
+    metrics_enabled: bool,
 }
 
 impl Processor {
-    pub fn new(start: Instant) -> Self {
-        Self { start }
+    pub fn new(start: Instant, parser: &ArgumentParser) -> Self {
+        Self::new_with_threads(start, parser, parser.get_num_threads())
+    }
+
+    /// Creates a processor for a thread pool with a number of threads
+    /// other than the one of the arguments, as the runs of the benchmark.
+    pub fn new_with_threads(start: Instant, parser: &ArgumentParser, num_threads: usize) -> Self {
+        Self {
+            start,
+            placement_enabled: parser.is_placement_enabled(),
+            kill_graph_enabled: parser.is_rivalries_enabled()
+                || parser.get_graph_export_file().is_some(),
+            // Las categorias se leen antes de procesar los archivos, para fallar antes
+            weapon_categories: parser
+                .is_categories_enabled()
+                .then(|| load_weapon_categories(parser)),
+            distinct_mode: get_distinct_mode(parser),
+            top_killers_capacity: match parser.get_command() {
+                Command::Report | Command::Bench => get_top_killers_capacity(parser),
+                _ => None,
+            },
+            player_name: match parser.get_command() {
+                Command::Player(player_name) => Some(player_name.clone()),
+                _ => None,
+            },
+            weapon_name: match parser.get_command() {
+                Command::Weapon(weapon_name) => Some(weapon_name.clone()),
+                _ => None,
+            },
+            filter: parser.get_filter().map(compile_filter),
+            players_weapons_shards: get_players_weapons_shards(parser, num_threads),
+            players_weapons_spill: get_players_weapons_spill(parser, num_threads),
+            checkpoint_interval: get_checkpoint_interval(parser),
+            aggregate_sizes: Mutex::new(AggregateSizes::new()),
+            backend: get_backend(parser, num_threads),
+            input_format: get_input_format(parser),
+            cancellation: CancellationToken::new(),
+            completed_files: Mutex::new(Vec::new()),
+            progress: new_progress(parser, num_threads),
+            metrics: Metrics::new(num_threads),
+            metrics_enabled: parser.is_metrics_enabled(),
+        }
+    }
+
+    /// Returns the token that stops the processing when the run is cancelled.
+    pub fn get_cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Returns the counters of the files, bytes and lines processed, updated while processing.
+    pub fn get_progress(&self) -> Arc<Progress> {
+        Arc::clone(&self.progress)
+    }
+
+    /// Returns the timing of the phases, the files and the workers of the run.
+    pub fn get_metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Creates an empty `ProcessedData` with the statistics enabled for this run.
+    fn new_processed_data(&self) -> ProcessedData {
+        ProcessedData {
+            weapons: HashMap::new(),
+            players_weapons: HashMap::new(),
+            players_weapons_bytes: 0,
+            placement_stats: self.placement_enabled.then(PlacementStats::new),
+            kill_graph: self.kill_graph_enabled.then(KillGraph::new),
+            player_profile: self.player_name.as_ref().map(|_| PlayerProfile::new()),
+            distinct_stats: self.distinct_mode.map(DistinctStats::new),
+            top_killers_summary: self.top_killers_capacity.map(SpaceSaving::new),
+        }
+    }
+
+    /// Returns true if the line has to be aggregated.
+    /// The line has to satisfy the filter given with `--filter`, and when a player
+    /// profile was requested, the player has to be the killer or the victim.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    fn is_line_selected(&self, fields: &[&str], distance: Option<f64>) -> bool {
+        let is_player_line = match &self.player_name {
+            Some(player_name) => [KILLER_NAME, VICTIM_NAME]
+                .iter()
+                .any(|&field| fields.get(field) == Some(&player_name.as_str())),
+            None => true,
+        };
+        is_player_line
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(fields, distance))
     }
 
-    fn process_murder_coordinates(&self, fields: &[&str], weapon_stats: &mut WeaponStats) {
+    /// Calculates the distance between the killer and the victim.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    ///
+    /// # Returns
+    ///
+    /// Returns the distance, or `None` if any of the positions is missing.
+    fn calculate_murder_distance(&self, fields: &[&str]) -> Option<f64> {
         if let (Some(killer_x), Some(killer_y), Some(victim_x), Some(victim_y)) = (
-            fields
-                .get(KILLER_POSITION_X)
-                .and_then(|x| x.parse::<f64>().ok()),
-            fields
-                .get(KILLER_POSITION_Y)
-                .and_then(|y| y.parse::<f64>().ok()),
-            fields
-                .get(VICTIM_POSITION_X)
-                .and_then(|x| x.parse::<f64>().ok()),
-            fields
-                .get(VICTIM_POSITION_Y)
-                .and_then(|y| y.parse::<f64>().ok()),
+            fields.get(KILLER_POSITION_X).and_then(|x| parse_number(x)),
+            fields.get(KILLER_POSITION_Y).and_then(|y| parse_number(y)),
+            fields.get(VICTIM_POSITION_X).and_then(|x| parse_number(x)),
+            fields.get(VICTIM_POSITION_Y).and_then(|y| parse_number(y)),
         ) {
-            weapon_stats.set_death_distance(
-                ((killer_x - victim_x).powi(2) + (killer_y - victim_y).powi(2)).sqrt(),
-            );
+            Some(((killer_x - victim_x).powi(2) + (killer_y - victim_y).powi(2)).sqrt())
+        } else {
+            None
+        }
+    }
+
+    fn process_murder_coordinates(&self, distance: Option<f64>, weapon_stats: &mut WeaponStats) {
+        if let Some(distance) = distance {
+            weapon_stats.set_death_distance(distance);
             weapon_stats.set_number_of_kills_with_valid_distance(1);
         }
     }
 
+    /// Processes the detail of the kills made with the requested weapon.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    /// * `weapon_stats` - Statistics of the weapon used in the line.
+    fn process_weapon_detail(
+        &self,
+        fields: &[&str],
+        distance: Option<f64>,
+        weapon_stats: &mut WeaponStats,
+    ) {
+        if let Some(distance) = distance {
+            weapon_stats.add_kill_to_distance_distribution(distance);
+        }
+        if let Some(map) = fields.get(MAP) {
+            weapon_stats.add_kill_in_map(map);
+        }
+        if let Some(time) = fields.get(TIME).and_then(|t| parse_number(t)) {
+            weapon_stats.add_kill_to_time_profile(time as u32);
+        }
+    }
+
+    /// Returns true if the weapon of the line is the one requested with the subcommand weapon.
+    fn is_requested_weapon(&self, fields: &[&str]) -> bool {
+        match (&self.weapon_name, fields.first()) {
+            (Some(weapon_name), Some(weapon)) => weapon_name == weapon,
+            _ => false,
+        }
+    }
+
+    /// Processes the kill in the statistics of the weapon of the line.
+    /// The weapon is only copied the first time it is found.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    /// * `weapons` - Statistics of each weapon.
     fn process_weapon(
         &self,
         fields: &[&str],
-        mut weapon_stats: WeaponStats,
+        distance: Option<f64>,
         weapons: &mut NumberOfDeathsAndDistances,
     ) {
-        if let Some(weapon) = fields.first() {
-            weapon_stats.set_total_kills_caused_by_weapon(1);
-            weapons.insert(weapon.to_string(), weapon_stats);
+        let Some(weapon) = fields.first() else {
+            return;
+        };
+        let weapon_stats = match weapons.get_mut(*weapon) {
+            Some(weapon_stats) => weapon_stats,
+            None => weapons
+                .entry(weapon.to_string())
+                .or_insert(WeaponStats::new(0.0, 0, 0)),
+        };
+        weapon_stats.set_total_kills_caused_by_weapon(1);
+        self.process_murder_coordinates(distance, weapon_stats);
+        if self.is_requested_weapon(fields) {
+            self.process_weapon_detail(fields, distance, weapon_stats);
         }
     }
+
     /// Processes player data.
+    /// The player and the weapon are only copied the first time they are found.
     ///
     /// # Arguments
     ///
@@ -75,167 +298,1717 @@ impl Processor {
     ///
     /// # Returns
     ///
-    /// Returns a HashMap with player kills.
-    fn process_player(&self, fields: &[&str], player_kills: &mut PlayersWeapons) {
-        if let Some(player) = fields.get(KILLER_NAME) {
+    /// Returns the estimated bytes of the player and the weapon if they were not in the map.
+    fn process_player(&self, fields: &[&str], player_kills: &mut PlayersWeapons) -> usize {
+        let (Some(player), Some(weapon)) = (fields.get(KILLER_NAME), fields.first()) else {
+            return 0;
+        };
+        if player == &"" {
+            return 0;
+        }
+        let mut added_bytes = 0;
+        let player_weapons = match player_kills.get_mut(*player) {
+            Some(player_weapons) => player_weapons,
+            None => {
+                added_bytes += memory_usage::estimate_entry_bytes(player);
+                player_kills.entry(player.to_string()).or_default()
+            }
+        };
+        match player_weapons.get_mut(*weapon) {
+            Some(count) => *count += 1,
+            None => {
+                added_bytes += memory_usage::estimate_entry_bytes(weapon);
+                player_weapons.insert(weapon.to_string(), 1);
+            }
+        }
+        added_bytes
+    }
+
+    /// Processes the kill of the player in the sharded map shared by all the threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `players_weapons_shards` - Kills of each player with each weapon.
+    fn process_player_in_shards(
+        &self,
+        fields: &[&str],
+        players_weapons_shards: &ShardedMap<HashMap<String, i32>>,
+    ) {
+        if let (Some(player), Some(weapon)) = (fields.get(KILLER_NAME), fields.first()) {
             if player != &"" {
-                if let Some(weapon) = fields.first() {
-                    let player_weapons = player_kills.entry(player.to_string()).or_default();
-                    player_weapons.insert(weapon.to_string(), 1);
-                }
+                players_weapons_shards.update(player, |player_weapons| {
+                    match player_weapons.get_mut(*weapon) {
+                        Some(count) => *count += 1,
+                        None => {
+                            player_weapons.insert(weapon.to_string(), 1);
+                        }
+                    }
+                });
             }
         }
     }
 
-    /// Maps lines from a CSV file to processed data.
+    /// Processes the kill of the player in the summary of the top killers.
     ///
     /// # Arguments
-    /// * `lines_iter` - Parallel iterator over lines from a CSV file.
     ///
-    /// # Returns
-    /// Returns a parallel iterator over mapped items.
-    fn map_lines<'a>(
-        &'a self,
-        lines_iter: impl ParallelIterator<Item = Result<String, std::io::Error>> + 'a,
-    ) -> impl ParallelIterator<Item = MappedItem> + 'a {
-        lines_iter.filter_map(|l| match l {
-            Ok(line) => {
-                let fields: Vec<&str> = line.split(',').collect();
-                let mut player_kills = HashMap::new();
-                let mut weapon_stats = WeaponStats::new(0.0, 0, 0);
-                let mut weapons: HashMap<String, WeaponStats> = HashMap::new();
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `top_killers_summary` - Summary of the players with more kills.
+    fn process_player_in_summary(&self, fields: &[&str], top_killers_summary: &mut SpaceSaving) {
+        if let (Some(player), Some(weapon)) = (fields.get(KILLER_NAME), fields.first()) {
+            if player != &"" {
+                top_killers_summary.add_kill(player, weapon);
+            }
+        }
+    }
 
-                self.process_murder_coordinates(&fields, &mut weapon_stats);
-                self.process_weapon(&fields, weapon_stats, &mut weapons);
-                self.process_player(&fields, &mut player_kills);
+    /// Processes the placement of the killer.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    /// * `placement_stats` - Statistics grouped by placement.
+    fn process_placement(
+        &self,
+        fields: &[&str],
+        distance: Option<f64>,
+        placement_stats: &mut PlacementStats,
+    ) {
+        if let (Some(placement), Some(weapon)) = (
+            fields.get(KILLER_PLACEMENT).and_then(|p| parse_number(p)),
+            fields.first(),
+        ) {
+            // Los puestos negativos quedan en 0, que no es un puesto valido
+            placement_stats.add_kill(placement as u32, weapon, distance);
+        }
+    }
 
-                Some((weapons, player_kills))
+    /// Processes the edge between the killer and the victim.
+    /// The kills without a killer name are not part of the graph.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `kill_graph` - Graph with the kills between players.
+    fn process_kill_graph(&self, fields: &[&str], kill_graph: &mut KillGraph) {
+        if let (Some(killer), Some(victim)) = (fields.get(KILLER_NAME), fields.get(VICTIM_NAME)) {
+            if killer != &"" && victim != &"" {
+                kill_graph.add_kill(killer, victim);
             }
-            Err(e) => {
-                eprintln!("Error al leer la linea: {}", e);
-                None
+        }
+    }
+
+    /// Processes the map, the match, the players and the pair of players of the line.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distinct_stats` - Distinct counts of each map.
+    fn process_distinct(&self, fields: &[&str], distinct_stats: &mut DistinctStats) {
+        if let (Some(map), Some(match_id), Some(killer), Some(victim)) = (
+            fields.get(MAP),
+            fields.get(MATCH_ID),
+            fields.get(KILLER_NAME),
+            fields.get(VICTIM_NAME),
+        ) {
+            distinct_stats.add_kill(map, match_id, killer, victim);
+        }
+    }
+
+    /// Processes the line for the profile of the requested player.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    /// * `player_profile` - Statistics of the requested player.
+    fn process_player_profile(
+        &self,
+        fields: &[&str],
+        distance: Option<f64>,
+        player_profile: &mut PlayerProfile,
+    ) {
+        let Some(player_name) = self.player_name.as_deref() else {
+            return;
+        };
+        if fields.get(KILLER_NAME) == Some(&player_name) {
+            player_profile.add_kill(distance);
+        }
+        if fields.get(VICTIM_NAME) == Some(&player_name) {
+            player_profile.add_death();
+        }
+        if let (Some(map), Some(match_id)) = (fields.get(MAP), fields.get(MATCH_ID)) {
+            player_profile.add_match(map, match_id);
+        }
+    }
+
+    /// Adds the fields of a selected CSV line to the statistics of a task.
+    ///
+    /// # Arguments
+    /// * `processed_data` - Statistics accumulated by the task.
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    ///
+    /// # Returns
+    /// Returns the statistics with the line.
+    fn fold_fields(
+        &self,
+        mut processed_data: ProcessedData,
+        fields: &[&str],
+        distance: Option<f64>,
+    ) -> ProcessedData {
+        self.process_weapon(fields, distance, &mut processed_data.weapons);
+        // Con el subcomando weapon solo interesan los jugadores que usaron esa arma
+        if self.weapon_name.is_none() || self.is_requested_weapon(fields) {
+            match (
+                processed_data.top_killers_summary.as_mut(),
+                &self.players_weapons_shards,
+            ) {
+                (Some(top_killers_summary), _) => {
+                    self.process_player_in_summary(fields, top_killers_summary)
+                }
+                (None, Some(players_weapons_shards)) => {
+                    self.process_player_in_shards(fields, players_weapons_shards)
+                }
+                (None, None) => {
+                    let added_bytes =
+                        self.process_player(fields, &mut processed_data.players_weapons);
+                    if added_bytes > 0 {
+                        self.add_players_weapons_bytes(&mut processed_data, added_bytes);
+                    }
+                }
             }
-        })
+        }
+        if let Some(placement_stats) = processed_data.placement_stats.as_mut() {
+            self.process_placement(fields, distance, placement_stats);
+        }
+        if let Some(kill_graph) = processed_data.kill_graph.as_mut() {
+            self.process_kill_graph(fields, kill_graph);
+        }
+        if let Some(player_profile) = processed_data.player_profile.as_mut() {
+            self.process_player_profile(fields, distance, player_profile);
+        }
+        if let Some(distinct_stats) = processed_data.distinct_stats.as_mut() {
+            self.process_distinct(fields, distinct_stats);
+        }
+
+        processed_data
     }
 
-    /// Reduces a parallel iterator of mapped items into a single result.
+    /// Adds a line from a CSV file to a result with the given function.
+    /// The lines that are not selected by the filters are discarded.
     ///
     /// # Arguments
-    /// * `mapped_iter` - Parallel iterator over mapped items.
+    /// * `acc` - Result accumulated by the task.
+    /// * `line` - Line from a CSV file.
+    /// * `fold_fields` - Function that adds the fields of a line and its distance to a result.
     ///
     /// # Returns
-    /// Returns a single `Reduced` with accumulated statistics.
-    fn reduce_mapped_iter(
+    /// Returns the result with the line, or as it was if the line was discarded.
+    fn fold_line<T>(
         &self,
-        mapped_iter: impl ParallelIterator<Item = MappedItem>,
-    ) -> ReducedItem {
-        mapped_iter.reduce(
-            || (HashMap::new(), HashMap::new()),
-            |(mut acc_weapons, mut acc_players_weapons), (weapon, player_kills)| {
-                self.add_deaths_and_distances(&mut acc_weapons, &weapon);
-                self.add_players_weapons(&mut acc_players_weapons, &player_kills);
-                (acc_weapons, acc_players_weapons)
-            },
+        acc: T,
+        line: &str,
+        fold_fields: impl Fn(T, &[&str], Option<f64>) -> T,
+    ) -> T {
+        // Los campos se guardan en el stack, sin reservar memoria por cada linea
+        let mut fields = [""; NUMBER_OF_FIELDS];
+        let number_of_fields = tokenizer::split_fields(line, &mut fields);
+        self.fold_row(
+            acc,
+            &fields[..number_of_fields],
+            line.len() + 1,
+            fold_fields,
         )
     }
 
-    /// Processes CSV files and returns processed data.
+    /// Adds the fields of a row, from a CSV line or a columnar file, to a result with
+    /// the given function. The rows that are not selected by the filters are discarded.
     ///
     /// # Arguments
-    /// * `paths` - Vector of CSV file paths.
+    /// * `acc` - Result accumulated by the task.
+    /// * `fields` - Fields of the row, as the ones of a CSV line.
+    /// * `bytes` - Bytes of the file read for the row.
+    /// * `fold_fields` - Function that adds the fields of a row and its distance to a result.
     ///
     /// # Returns
-    /// Returns `ProcessedData` with weapon and player statistics.
-    fn process_csvs(&self, paths: &Vec<PathBuf>) -> ProcessedData {
-        let lines_iter = paths
-            .par_iter()
-            .filter_map(get_parallel_line_iterator)
-            .flat_map(|lines_iter| lines_iter);
+    /// Returns the result with the row, or as it was if the row was discarded.
+    fn fold_row<T>(
+        &self,
+        acc: T,
+        fields: &[&str],
+        bytes: usize,
+        fold_fields: impl Fn(T, &[&str], Option<f64>) -> T,
+    ) -> T {
+        self.progress.add_row(bytes);
+        let distance = self.calculate_murder_distance(fields);
+        match self.is_line_selected(fields, distance) {
+            true => fold_fields(acc, fields, distance),
+            false => acc,
+        }
+    }
+
+    /// Returns the fields read by the analyses of the run, that are the only columns
+    /// read from the columnar files.
+    ///
+    /// # Arguments
+    /// * `analysis_fields` - Fields read by the requested analysis, besides the filters.
+    fn get_needed_fields(&self, analysis_fields: &[usize]) -> Vec<usize> {
+        let mut fields = analysis_fields.to_vec();
+        if self.player_name.is_some() {
+            fields.extend([KILLER_NAME, VICTIM_NAME]);
+        }
+        if let Some(filter) = &self.filter {
+            fields.extend(filter.get_fields());
+        }
+        fields.sort_unstable();
+        fields.dedup();
+        fields
+    }
 
-        let mapped_iter = self.map_lines(lines_iter);
-        self.reduce_mapped_iter(mapped_iter)
+    /// Returns the fields read by `fold_fields`, with the statistics enabled for this run.
+    fn get_report_fields(&self) -> Vec<usize> {
+        let mut fields = vec![
+            WEAPON,
+            KILLER_NAME,
+            KILLER_POSITION_X,
+            KILLER_POSITION_Y,
+            VICTIM_POSITION_X,
+            VICTIM_POSITION_Y,
+        ];
+        if self.weapon_name.is_some() {
+            fields.extend([MAP, TIME]);
+        }
+        if self.placement_enabled {
+            fields.push(KILLER_PLACEMENT);
+        }
+        if self.kill_graph_enabled {
+            fields.push(VICTIM_NAME);
+        }
+        if self.player_name.is_some() || self.distinct_mode.is_some() {
+            fields.extend([MAP, MATCH_ID, VICTIM_NAME]);
+        }
+        self.get_needed_fields(&fields)
     }
 
-    fn add_deaths_and_distances(
+    /// Adds the statistics of a task to the accumulated ones.
+    ///
+    /// # Arguments
+    /// * `acc` - Accumulated statistics.
+    /// * `processed_data` - Statistics to add.
+    ///
+    /// # Returns
+    /// Returns the accumulated statistics.
+    fn merge_processed_data(
         &self,
-        acc_weapons: &mut NumberOfDeathsAndDistances,
-        current_weapon: &NumberOfDeathsAndDistances,
-    ) {
-        current_weapon.iter().for_each(|(k, v)| {
-            let acc_weapon_stats = acc_weapons
-                .entry(k.to_string())
-                .or_insert(WeaponStats::new(0.0, 0, 0));
-            acc_weapon_stats.set_total_kills_caused_by_weapon(v.get_total_kills_caused_by_weapon());
-            acc_weapon_stats.set_death_distance(v.get_death_distance());
-            acc_weapon_stats.set_number_of_kills_with_valid_distance(
-                v.get_number_of_kills_with_valid_distance(),
-            );
-        });
+        mut acc: ProcessedData,
+        processed_data: &ProcessedData,
+    ) -> ProcessedData {
+        self.add_deaths_and_distances(&mut acc.weapons, &processed_data.weapons);
+        let added_bytes =
+            self.add_players_weapons(&mut acc.players_weapons, &processed_data.players_weapons);
+        // Los jugadores de processed_data se descartan al terminar de combinarlos
+        if let Some(players_weapons_spill) = &self.players_weapons_spill {
+            players_weapons_spill.remove_resident_bytes(processed_data.players_weapons_bytes);
+        }
+        self.add_players_weapons_bytes(&mut acc, added_bytes);
+        if let (Some(acc_placement_stats), Some(placement_stats)) = (
+            acc.placement_stats.as_mut(),
+            processed_data.placement_stats.as_ref(),
+        ) {
+            acc_placement_stats.merge(placement_stats);
+        }
+        if let (Some(acc_kill_graph), Some(kill_graph)) =
+            (acc.kill_graph.as_mut(), processed_data.kill_graph.as_ref())
+        {
+            acc_kill_graph.merge(kill_graph);
+        }
+        if let (Some(acc_player_profile), Some(player_profile)) = (
+            acc.player_profile.as_mut(),
+            processed_data.player_profile.as_ref(),
+        ) {
+            acc_player_profile.merge(player_profile);
+        }
+        if let (Some(acc_distinct_stats), Some(distinct_stats)) = (
+            acc.distinct_stats.as_mut(),
+            processed_data.distinct_stats.as_ref(),
+        ) {
+            acc_distinct_stats.merge(distinct_stats);
+        }
+        if let (Some(acc_top_killers_summary), Some(top_killers_summary)) = (
+            acc.top_killers_summary.as_mut(),
+            processed_data.top_killers_summary.as_ref(),
+        ) {
+            acc_top_killers_summary.merge(top_killers_summary);
+        }
+        acc
     }
 
-    fn add_players_weapons(
+    /// Folds and merges the lines of CSV files with the backend of the run,
+    /// or the rows of columnar files with the thread pool of rayon.
+    /// A file is completed when all its lines were reduced before the run was cancelled.
+    ///
+    /// # Arguments
+    /// * `paths` - Paths of the CSV files.
+    /// * `fields` - Fields read by `fold_fields`, the only columns read from the columnar files.
+    /// * `fold_fields` - Function that adds the fields of a line and its distance to a result.
+    /// * `identity` - Function that creates an empty result.
+    /// * `merge` - Function that merges two results.
+    /// * `on_file_done` - Function called with the result of each completed file.
+    fn map_reduce_files<T: Send>(
         &self,
-        acc_players_weapons: &mut PlayersWeapons,
-        player_kills: &PlayersWeapons,
+        paths: &[PathBuf],
+        fields: &[usize],
+        fold_fields: impl Fn(T, &[&str], Option<f64>) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T) + Sync + Send,
     ) {
-        player_kills.iter().for_each(|(k, v)| {
-            let player_weapons = acc_players_weapons.entry(k.to_string()).or_default();
-            v.iter().for_each(|(weapon, count)| {
-                let player_weapon_count = player_weapons.entry(weapon.to_string()).or_default();
-                *player_weapon_count += count;
-            });
+        self.progress.add_pending_files(paths);
+        // Los backends miden el tiempo de los workers solo si se pidieron las metricas
+        let metrics = self.metrics_enabled.then_some(&self.metrics);
+        let on_file_done = |path: &PathBuf, result, duration| {
+            self.metrics.add_file_timing(path, duration);
+            self.add_completed_file(&path.to_string_lossy());
+            self.progress.add_completed_file();
+            on_file_done(path, result);
+        };
+        match self.input_format {
+            InputFormat::Csv => self.backend.map_reduce_files(
+                paths,
+                &self.cancellation,
+                metrics,
+                |acc, line| self.fold_line(acc, line, &fold_fields),
+                identity,
+                merge,
+                on_file_done,
+            ),
+            InputFormat::Columnar(format) => columnar_reader::map_reduce_files(
+                format,
+                paths,
+                fields,
+                &self.cancellation,
+                metrics,
+                |acc, row, bytes| self.fold_row(acc, row, bytes, &fold_fields),
+                identity,
+                merge,
+                on_file_done,
+            ),
+        }
+    }
+
+    /// Folds and merges the lines of CSV files.
+    /// The lines of each file are reduced first, and the result of each file is merged
+    /// as soon as the file is completed, so the results of all the files are not kept.
+    /// If the run is cancelled, only the completed files are reduced.
+    ///
+    /// # Arguments
+    /// * `paths` - Vector of CSV file paths.
+    /// * `fields` - Fields read by `fold_fields`.
+    /// * `fold_fields` - Function that adds the fields of a line and its distance to a result.
+    /// * `identity` - Function that creates an empty result.
+    /// * `merge` - Function that merges two results.
+    ///
+    /// # Returns
+    /// Returns the result of reducing all the selected lines.
+    fn map_reduce_csvs<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        fields: &[usize],
+        fold_fields: impl Fn(T, &[&str], Option<f64>) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+    ) -> T {
+        // Cada archivo se combina al terminar con el resultado que espera su par, o queda
+        // esperando; asi hay un solo resultado esperando y las combinaciones van en paralelo
+        let pending_result = Mutex::new(None);
+        self.metrics.measure(READ_PARSE_PHASE, || {
+            self.map_reduce_files(
+                paths,
+                fields,
+                fold_fields,
+                &identity,
+                &merge,
+                |_, mut result| loop {
+                    let mut pending_result = pending_result
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    match pending_result.take() {
+                        Some(pending) => {
+                            drop(pending_result);
+                            result = merge(pending, result);
+                        }
+                        None => {
+                            *pending_result = Some(result);
+                            break;
+                        }
+                    }
+                },
+            )
         });
+        self.metrics.measure(REDUCE_PHASE, || {
+            pending_result
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .unwrap_or_else(&identity)
+        })
     }
 
-    /// Returns the time since the stopwatch started
-    fn get_duration(&self) -> Instant {
-        self.start
+    /// Registers a file whose statistics are in the result of the run.
+    fn add_completed_file(&self, path: &str) {
+        self.completed_files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path.to_string());
     }
 
-    /// Processes CSV files and writes results to an output file.
-    /// 
+    /// Processes CSV files and returns processed data.
+    ///
     /// # Arguments
-    /// 
-    /// * `parser` - Argument parser with command line arguments.
-    /// 
+    /// * `paths` - Vector of CSV file paths.
+    ///
     /// # Returns
-    /// 
-    /// No return value.
-    pub fn process_and_write_results(&self, parser: &ArgumentParser) {
-        let top_calculator = TopCalculator::new();
-        let writer = Writer::new(parser.get_output_file_name());
-        let (weapons, player_kills) = self.process_csvs(&parser.get_vec_paths());
-        let duration = self.get_duration().elapsed();
-        let (top_killers, top_weapons) =
-            top_calculator.calculate_and_sort_results(weapons, player_kills);
-        match writer.write_results_in_file(top_killers, top_weapons) {
-            Ok(_) => println!("Archivo escrito correctamente"),
-            Err(e) => eprintln!("Error al escribir el archivo: {}", e),
+    /// Returns `ProcessedData` with weapon and player statistics.
+    fn process_csvs(&self, paths: &[PathBuf]) -> ProcessedData {
+        self.map_reduce_csvs(
+            paths,
+            &self.get_report_fields(),
+            |acc, fields, distance| self.fold_fields(acc, fields, distance),
+            || self.new_processed_data(),
+            |acc, processed_data| self.merge_processed_data(acc, &processed_data),
+        )
+    }
+
+    /// Processes the pending CSV files and adds the statistics of each one to the state.
+    /// The state is saved once, without the files that were discarded, and then the processed
+    /// files are appended to it each time the checkpoint interval passes, so they are not lost
+    /// if the run stops.
+    ///
+    /// # Arguments
+    /// * `state` - State with the statistics of the files that were already processed.
+    /// * `pending_paths` - Paths of the files to process.
+    /// * `checksums` - Checksum of each file.
+    /// * `state_file` - Path where the state is saved.
+    ///
+    /// # Returns
+    /// Returns the state with the statistics of the pending files.
+    fn process_pending_csvs(
+        &self,
+        state: AnalysisState<ProcessedData>,
+        pending_paths: &[PathBuf],
+        checksums: &HashMap<PathBuf, u64>,
+        state_file: &str,
+    ) -> AnalysisState<ProcessedData> {
+        let state_log = state
+            .save(state_file)
+            .and_then(|_| StateLog::open(state_file, self.checkpoint_interval));
+        if let Err(e) = &state_log {
+            eprintln!("Error al guardar el estado: {}", e);
         }
-        println!("Tiempo total de lectura: {:?}", duration);
+        let state_log = state_log.ok();
+        let state = Mutex::new(state);
+        self.metrics.measure(READ_PARSE_PHASE, || {
+            self.map_reduce_files(
+                pending_paths,
+                &self.get_report_fields(),
+                |acc, fields, distance| self.fold_fields(acc, fields, distance),
+                || self.new_processed_data(),
+                |acc, processed_data| self.merge_processed_data(acc, &processed_data),
+                |path, processed_data| {
+                    let file_state = FileState::new(path, checksums[path], processed_data);
+                    if let Some(Err(e)) = state_log.as_ref().map(|log| log.append(&file_state)) {
+                        eprintln!("Error al guardar el estado: {}", e);
+                    }
+                    // Si otro hilo entro en panico con el lock tomado, el estado sigue siendo valido
+                    state
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .add_file(file_state);
+                },
+            )
+        });
+        // Los archivos que esperaban el intervalo se guardan al terminar, aunque se haya cancelado
+        if let Some(Err(e)) = state_log.map(|log| log.flush()) {
+            eprintln!("Error al guardar el estado: {}", e);
+        }
+        state.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Merges the statistics of all the files of the state.
+    fn merge_state(&self, state: &AnalysisState<ProcessedData>) -> ProcessedData {
+        self.metrics.measure(REDUCE_PHASE, || {
+            state
+                .get_files()
+                .iter()
+                .fold(self.new_processed_data(), |acc, file_state| {
+                    self.merge_processed_data(acc, file_state.get_data())
+                })
+        })
     }
-}
 
+    /// Registers the files that were already processed in the state.
+    fn add_completed_files_of_state(&self, state: &AnalysisState<ProcessedData>) {
+        state
+            .get_files()
+            .iter()
+            .for_each(|file_state| self.add_completed_file(file_state.get_path()));
+    }
 
-/// Opens a CSV file and verifies that it was opened correctly.
-/// If the file was opened correctly, it returns a parallel iterator over lines from the CSV file.
-/// 
-/// # Arguments
-/// 
-/// * `path` - Path to a CSV file.
-/// 
-/// # Returns
-/// 
-/// Returns a parallel iterator over lines from a CSV file.
-fn get_parallel_line_iterator(path: &PathBuf) -> Option<IterBridge<Lines<BufReader<File>>>> {
-    match File::open(path) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            Some(reader.lines().par_bridge())
+    /// Processes only the CSV files that are new or changed since the state was saved,
+    /// saves their statistics in the state and merges the statistics of all the files.
+    /// If the state cannot be loaded or was computed with other options, the function exits the program
+    ///
+    /// # Arguments
+    /// * `paths` - Vector of CSV file paths.
+    /// * `state_file` - Path of the state file.
+    /// * `options` - Options that change the statistics, saved with the state.
+    ///
+    /// # Returns
+    /// Returns `ProcessedData` with the statistics of all the files.
+    fn process_csvs_incrementally(
+        &self,
+        paths: &[PathBuf],
+        state_file: &str,
+        options: serde_json::Value,
+    ) -> ProcessedData {
+        let mut state = load_state(state_file, options);
+        let checksums = self
+            .metrics
+            .measure(SCAN_PHASE, || calculate_checksums(paths));
+        let pending_paths = state.retain_unchanged_files(&checksums);
+        println!(
+            "Archivos sin cambios: {}, archivos nuevos o modificados: {}",
+            state.get_files().len(),
+            pending_paths.len()
+        );
+        self.add_completed_files_of_state(&state);
+
+        let checksums: HashMap<PathBuf, u64> = checksums.into_iter().collect();
+        let state = self.process_pending_csvs(state, &pending_paths, &checksums, state_file);
+        self.merge_state(&state)
+    }
+
+    /// Processes the CSV files saving a checkpoint each time a file is processed.
+    /// When resuming, the files of the checkpoint are not processed again, and the checkpoint
+    /// has to be from the same input files, with the same content, and the same options.
+    /// The checkpoint is removed when all the files are processed, and kept if the run is cancelled.
+    /// If the checkpoint cannot be resumed, the function exits the program
+    ///
+    /// # Arguments
+    /// * `paths` - Vector of CSV file paths.
+    /// * `checkpoint_file` - Path of the checkpoint file.
+    /// * `resume` - Whether to resume from the checkpoint.
+    /// * `options` - Options that change the statistics, saved with the checkpoint.
+    ///
+    /// # Returns
+    /// Returns `ProcessedData` with the statistics of all the files.
+    fn process_csvs_with_checkpoints(
+        &self,
+        paths: &[PathBuf],
+        checkpoint_file: &str,
+        resume: bool,
+        options: serde_json::Value,
+    ) -> ProcessedData {
+        let checksums = self
+            .metrics
+            .measure(SCAN_PHASE, || calculate_checksums(paths));
+        let mut inputs: Vec<_> = paths.iter().map(|path| path.to_string_lossy()).collect();
+        inputs.sort();
+        let options = json!({ "options": options, "inputs": inputs });
+        let mut checkpoint = match resume {
+            true => load_checkpoint(checkpoint_file, options, &checksums),
+            false => AnalysisState::new(options),
+        };
+        let pending_paths = checkpoint.retain_unchanged_files(&checksums);
+        if resume {
+            println!(
+                "Archivos completados en el checkpoint: {}, archivos pendientes: {}",
+                checkpoint.get_files().len(),
+                pending_paths.len()
+            );
         }
-        Err(e) => {
-            eprintln!("Error al abrir el archivo: {}", e);
-            None
+        self.add_completed_files_of_state(&checkpoint);
+
+        let checksums: HashMap<PathBuf, u64> = checksums.into_iter().collect();
+        let checkpoint =
+            self.process_pending_csvs(checkpoint, &pending_paths, &checksums, checkpoint_file);
+        if self.cancellation.is_cancelled() {
+            println!(
+                "Checkpoint guardado en {}, se puede continuar con --resume",
+                checkpoint_file
+            );
+        } else if let Err(e) = fs::remove_file(checkpoint_file) {
+            eprintln!("Error al borrar el checkpoint: {}", e);
         }
+        self.merge_state(&checkpoint)
+    }
+
+    /// Processes the input files of the arguments. If a state file was given,
+    /// only the new and changed files are processed.
+    ///
+    /// # Arguments
+    /// * `parser` - Argument parser with command line arguments.
+    ///
+    /// # Returns
+    /// Returns `ProcessedData` with the statistics of all the files.
+    fn process_input(&self, parser: &ArgumentParser) -> ProcessedData {
+        let paths = self.metrics.measure(SCAN_PHASE, || parser.get_vec_paths());
+        let processed_data = match (parser.get_state_file(), parser.get_checkpoint_file()) {
+            (Some(_), Some(_)) => {
+                eprintln!(
+                    "Las opciones --state y --checkpoint no se pueden usar juntas, \
+                     --state ya guarda cada archivo procesado"
+                );
+                std::process::exit(1);
+            }
+            (Some(state_file), None) => {
+                self.process_csvs_incrementally(&paths, state_file, self.get_options(parser))
+            }
+            (None, Some(checkpoint_file)) => self.process_csvs_with_checkpoints(
+                &paths,
+                checkpoint_file,
+                parser.is_resume_enabled(),
+                self.get_options(parser),
+            ),
+            (None, None) if parser.is_resume_enabled() => {
+                eprintln!("La opcion --resume necesita --checkpoint <path>");
+                std::process::exit(1);
+            }
+            (None, None) => {
+                let processed_data = self.process_csvs(&paths);
+                let processed_data = self.add_sharded_players_weapons(processed_data);
+                self.merge_spilled_players_weapons(processed_data)
+            }
+        };
+        self.record_aggregate_sizes(&processed_data);
+        processed_data
+    }
+
+    /// Records the number of entries and the estimated bytes of each aggregate of the run,
+    /// that are reported with the metrics.
+    ///
+    /// # Arguments
+    /// * `processed_data` - Statistics of the files.
+    fn record_aggregate_sizes(&self, processed_data: &ProcessedData) {
+        let mut aggregate_sizes = AggregateSizes::new();
+        aggregate_sizes.add(
+            "weapons",
+            &[("weapons", processed_data.weapons.len())],
+            memory_usage::estimate_map_bytes(&processed_data.weapons, WeaponStats::estimate_bytes),
+        );
+        let player_weapon_pairs = processed_data
+            .players_weapons
+            .values()
+            .map(HashMap::len)
+            .sum();
+        aggregate_sizes.add(
+            "players_weapons",
+            &[
+                ("players", processed_data.players_weapons.len()),
+                ("player_weapon_pairs", player_weapon_pairs),
+            ],
+            memory_usage::estimate_map_bytes(&processed_data.players_weapons, |weapons| {
+                memory_usage::estimate_map_bytes(weapons, |_| 0)
+            }),
+        );
+        if let Some(placement_stats) = &processed_data.placement_stats {
+            aggregate_sizes.add(
+                "placement_stats",
+                &[("placements", placement_stats.get_kills_by_placement().len())],
+                placement_stats.estimate_bytes(),
+            );
+        }
+        if let Some(kill_graph) = &processed_data.kill_graph {
+            aggregate_sizes.add(
+                "kill_graph",
+                &[("edges", kill_graph.get_edges().len())],
+                kill_graph.estimate_bytes(),
+            );
+        }
+        if let Some(player_profile) = &processed_data.player_profile {
+            aggregate_sizes.add(
+                "player_profile",
+                &[("maps", player_profile.get_matches_by_map().len())],
+                player_profile.estimate_bytes(),
+            );
+        }
+        if let Some(distinct_stats) = &processed_data.distinct_stats {
+            aggregate_sizes.add(
+                "distinct_stats",
+                &[("maps", distinct_stats.get_counts_by_map().len())],
+                distinct_stats.estimate_bytes(),
+            );
+        }
+        if let Some(top_killers_summary) = &processed_data.top_killers_summary {
+            aggregate_sizes.add(
+                "top_killers_summary",
+                &[("players", top_killers_summary.get_players().len())],
+                top_killers_summary.estimate_bytes(),
+            );
+        }
+        *self
+            .aggregate_sizes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = aggregate_sizes;
+    }
+
+    /// Returns the bytes of the players spilled to disk, if they can be spilled.
+    fn get_spilled_bytes(&self) -> Option<u64> {
+        self.players_weapons_spill
+            .as_ref()
+            .map(SpillStore::get_spilled_bytes)
+    }
+
+    /// Returns the peak of the estimated bytes of the players in memory, if they can be spilled.
+    fn get_peak_resident_bytes(&self) -> Option<usize> {
+        self.players_weapons_spill
+            .as_ref()
+            .map(SpillStore::get_peak_resident_bytes)
+    }
+
+    /// Adds the kills of the players aggregated in the sharded map, if it is used.
+    ///
+    /// # Arguments
+    /// * `processed_data` - Statistics of the files, without the kills of the players.
+    ///
+    /// # Returns
+    /// Returns the statistics with the kills of the players.
+    fn add_sharded_players_weapons(&self, mut processed_data: ProcessedData) -> ProcessedData {
+        if let Some(players_weapons_shards) = &self.players_weapons_shards {
+            processed_data.players_weapons = self
+                .metrics
+                .measure(REDUCE_PHASE, || players_weapons_shards.take_entries());
+        }
+        processed_data
+    }
+
+    /// Adds the estimated bytes of the kills added to the players of a result, and spills them
+    /// to disk when they grow past the memory budget of a thread.
+    /// If they cannot be spilled, the function exits the program
+    ///
+    /// # Arguments
+    /// * `processed_data` - Accumulated statistics.
+    /// * `added_bytes` - Estimated bytes of the kills added.
+    fn add_players_weapons_bytes(&self, processed_data: &mut ProcessedData, added_bytes: usize) {
+        processed_data.players_weapons_bytes += added_bytes;
+        let Some(players_weapons_spill) = &self.players_weapons_spill else {
+            return;
+        };
+        players_weapons_spill.add_resident_bytes(added_bytes);
+        if !players_weapons_spill.is_over_threshold(processed_data.players_weapons_bytes) {
+            return;
+        }
+        players_weapons_spill.remove_resident_bytes(processed_data.players_weapons_bytes);
+        processed_data.players_weapons_bytes = 0;
+        let players_weapons = std::mem::take(&mut processed_data.players_weapons);
+        if let Err(e) = players_weapons_spill.spill(players_weapons) {
+            eprintln!("Error al volcar los jugadores a disco: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    /// Merges the kills of the players that were spilled to disk, one partition at a time,
+    /// keeping from each partition only the players needed by the command.
+    /// If they cannot be merged, the function exits the program
+    ///
+    /// # Arguments
+    /// * `processed_data` - Statistics of the files, with the kills that were not spilled.
+    ///
+    /// # Returns
+    /// Returns the statistics with the kills of the players needed by the command.
+    fn merge_spilled_players_weapons(&self, mut processed_data: ProcessedData) -> ProcessedData {
+        let Some(players_weapons_spill) = &self.players_weapons_spill else {
+            return processed_data;
+        };
+        if !players_weapons_spill.has_spilled() {
+            return processed_data;
+        }
+        let players_weapons = std::mem::take(&mut processed_data.players_weapons);
+        let merged = self.metrics.measure(REDUCE_PHASE, || {
+            players_weapons_spill.spill(players_weapons)?;
+            players_weapons_spill.merge_partitions(
+                |acc_weapons: &mut HashMap<String, i32>, weapons| {
+                    weapons.into_iter().for_each(|(weapon, count)| {
+                        *acc_weapons.entry(weapon).or_default() += count;
+                    })
+                },
+                |players_weapons| self.retain_needed_players(players_weapons),
+            )
+        });
+        match merged {
+            Ok(players_weapons) => processed_data.players_weapons = players_weapons,
+            Err(e) => {
+                eprintln!("Error al combinar los jugadores volcados a disco: {}", e);
+                std::process::exit(1);
+            }
+        }
+        println!(
+            "Jugadores volcados a disco: {:.1} MB en {} particiones",
+            players_weapons_spill.get_spilled_bytes() as f64 / (1024.0 * 1024.0),
+            players_weapons_spill.get_number_of_partitions()
+        );
+        processed_data
+    }
+
+    /// Keeps the players of a partition that the command can need: the player of the profile,
+    /// or the players that can be in the top of the weapon or of the game, ties included.
+    ///
+    /// # Arguments
+    /// * `players_weapons` - Kills of the players of a partition.
+    ///
+    /// # Returns
+    /// Returns the kills of the players that are kept.
+    fn retain_needed_players(&self, mut players_weapons: PlayersWeapons) -> PlayersWeapons {
+        match (&self.player_name, &self.weapon_name) {
+            (Some(player_name), _) => {
+                players_weapons.retain(|player, _| player == player_name);
+                players_weapons
+            }
+            (None, Some(weapon_name)) => {
+                retain_top_players(players_weapons, TOP_USERS_OF_THE_WEAPON, |weapons| {
+                    weapons.get(weapon_name).copied().unwrap_or(0)
+                })
+            }
+            (None, None) => {
+                retain_top_players(players_weapons, TOP_PLAYERS_OF_THE_GAME, |weapons| {
+                    weapons.values().sum()
+                })
+            }
+        }
+    }
+
+    /// Returns the options that change the statistics of the lines.
+    ///
+    /// # Arguments
+    /// * `parser` - Argument parser with command line arguments.
+    fn get_options(&self, parser: &ArgumentParser) -> serde_json::Value {
+        json!({
+            "player": self.player_name,
+            "weapon": self.weapon_name,
+            "filter": parser.get_filter(),
+            "placement": self.placement_enabled,
+            "kill_graph": self.kill_graph_enabled,
+            "distinct": self.distinct_mode,
+            "approx_capacity": self.top_killers_capacity,
+        })
+    }
+
+    fn add_deaths_and_distances(
+        &self,
+        acc_weapons: &mut NumberOfDeathsAndDistances,
+        current_weapon: &NumberOfDeathsAndDistances,
+    ) {
+        current_weapon.iter().for_each(|(k, v)| {
+            let acc_weapon_stats = acc_weapons
+                .entry(k.to_string())
+                .or_insert(WeaponStats::new(0.0, 0, 0));
+            acc_weapon_stats.merge(v);
+        });
+    }
+
+    /// Adds the kills of the players to the accumulated ones.
+    ///
+    /// # Returns
+    /// Returns the estimated bytes of the players and weapons that were not accumulated before.
+    fn add_players_weapons(
+        &self,
+        acc_players_weapons: &mut PlayersWeapons,
+        player_kills: &PlayersWeapons,
+    ) -> usize {
+        let mut added_bytes = 0;
+        player_kills.iter().for_each(|(k, v)| {
+            let player_weapons = acc_players_weapons.entry(k.to_string()).or_insert_with(|| {
+                added_bytes += memory_usage::estimate_entry_bytes(k);
+                HashMap::new()
+            });
+            v.iter().for_each(|(weapon, count)| {
+                let player_weapon_count =
+                    player_weapons.entry(weapon.to_string()).or_insert_with(|| {
+                        added_bytes += memory_usage::estimate_entry_bytes(weapon);
+                        0
+                    });
+                *player_weapon_count += count;
+            });
+        });
+        added_bytes
+    }
+
+    /// Returns true if the run was cancelled and a partial output was not requested with `--partial`,
+    /// in that case the output file is not written.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    fn is_output_cancelled(&self, parser: &ArgumentParser) -> bool {
+        if !self.cancellation.is_cancelled() || parser.is_partial_enabled() {
+            return false;
+        }
+        eprintln!("Ejecucion cancelada, no se escribe el archivo de salida");
+        true
+    }
+
+    /// Returns the sections that mark the output as partial when the run was cancelled:
+    /// `partial` and the files whose statistics are in the output, `completed_files`.
+    /// If the run was not cancelled, there are no sections.
+    fn get_partial_sections(&self) -> Vec<(String, serde_json::Value)> {
+        if !self.cancellation.is_cancelled() {
+            return Vec::new();
+        }
+        let mut completed_files = self
+            .completed_files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        completed_files.sort();
+        vec![
+            ("partial".to_string(), json!(true)),
+            ("completed_files".to_string(), json!(completed_files)),
+        ]
+    }
+
+    /// Returns the sections with the metadata of the run that are added to the output:
+    /// the partial sections if the run was cancelled, and the metrics requested with `--metrics`.
+    /// The metrics do not include the write phase, that is not finished yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    fn get_metadata_sections(&self, parser: &ArgumentParser) -> Vec<(String, serde_json::Value)> {
+        let mut sections = self.get_partial_sections();
+        if parser.is_metrics_enabled() {
+            let metrics = self
+                .metrics
+                .to_json(self.progress.get_rows(), self.progress.get_bytes_read());
+            sections.push(("metrics".to_string(), metrics));
+            let memory = self
+                .aggregate_sizes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .to_json(self.get_spilled_bytes(), self.get_peak_resident_bytes());
+            sections.push(("memory".to_string(), memory));
+        }
+        sections
+    }
+
+    /// Writes a JSON object with the metadata of the run in the output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    /// * `writer` - Writer of the output file.
+    /// * `output` - JSON object to write.
+    fn write_json_output(
+        &self,
+        parser: &ArgumentParser,
+        writer: &Writer,
+        mut output: serde_json::Value,
+    ) {
+        if let Some(output_sections) = output.as_object_mut() {
+            output_sections.extend(self.get_metadata_sections(parser));
+        }
+        match self
+            .metrics
+            .measure(WRITE_PHASE, || writer.write_json_in_file(&output))
+        {
+            Ok(_) => println!("Archivo escrito correctamente"),
+            Err(e) => eprintln!("Error al escribir el archivo: {}", e),
+        }
+    }
+
+    /// Prints the time of each phase, the throughput and the utilization of the workers,
+    /// and the peak memory with the size of each aggregate.
+    /// Nothing is printed without `--metrics`, because the rows and the busy time are not counted.
+    fn print_metrics(&self) {
+        if !self.metrics_enabled {
+            return;
+        }
+        self.metrics
+            .print_summary(self.progress.get_rows(), self.progress.get_bytes_read());
+        self.aggregate_sizes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .print_summary(self.get_spilled_bytes(), self.get_peak_resident_bytes());
+    }
+
+    /// Returns the time since the stopwatch started
+    fn get_duration(&self) -> Instant {
+        self.start
+    }
+
+    /// Processes CSV files and writes results to an output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    ///
+    /// # Returns
+    ///
+    /// No return value.
+    pub fn process_and_write_results(&self, parser: &ArgumentParser) {
+        let writer = Writer::new(parser.get_output_file_name());
+        let processed_data = self.process_input(parser);
+        let duration = self.get_duration().elapsed();
+        if self.is_output_cancelled(parser) {
+            return;
+        }
+        match self.write_report(parser, &writer, processed_data) {
+            Ok(_) => println!("Archivo escrito correctamente"),
+            Err(e) => eprintln!("Error al escribir el archivo: {}", e),
+        }
+        println!("Tiempo total de lectura: {:?}", duration);
+        self.print_metrics();
+    }
+
+    /// Processes CSV files and writes the report to an output file, without printing the metrics.
+    /// The benchmark runs the whole pipeline with this function.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    /// * `writer` - Writer of the output file.
+    ///
+    /// # Returns
+    ///
+    /// A Result with the result of writing the report.
+    pub fn process_and_write_report(
+        &self,
+        parser: &ArgumentParser,
+        writer: &Writer,
+    ) -> std::io::Result<()> {
+        let processed_data = self.process_input(parser);
+        self.write_report(parser, writer, processed_data)
+    }
+
+    /// Calculates the report with the top killers, the top weapons and the requested sections,
+    /// and writes it to an output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    /// * `writer` - Writer of the output file.
+    /// * `processed_data` - Statistics of the input files.
+    ///
+    /// # Returns
+    ///
+    /// A Result with the result of writing the report.
+    fn write_report(
+        &self,
+        parser: &ArgumentParser,
+        writer: &Writer,
+        processed_data: ProcessedData,
+    ) -> std::io::Result<()> {
+        let top_calculator = TopCalculator::new();
+        let top_start = Instant::now();
+        let ProcessedData {
+            weapons,
+            players_weapons: player_kills,
+            placement_stats,
+            kill_graph,
+            distinct_stats,
+            top_killers_summary,
+            ..
+        } = processed_data;
+        let mut extra_sections = HashMap::new();
+        if let Some(categories) = &self.weapon_categories {
+            extra_sections.insert(
+                "weapon_categories".to_string(),
+                json!(top_calculator.calculate_top_categories(&weapons, categories)),
+            );
+        }
+        if let Some(placement_stats) = placement_stats {
+            extra_sections.insert(
+                "placement_stats".to_string(),
+                top_calculator.calculate_placement_stats(&placement_stats),
+            );
+        }
+        if let Some(kill_graph) = kill_graph {
+            if parser.is_rivalries_enabled() {
+                extra_sections.insert(
+                    "top_rivalries".to_string(),
+                    json!(top_calculator.calculate_top_rivalries(&kill_graph)),
+                );
+            }
+            if let Some(graph_export_file) = parser.get_graph_export_file() {
+                match GraphExporter::new(graph_export_file).export(&kill_graph) {
+                    Ok(_) => println!("Grafo exportado correctamente"),
+                    Err(e) => eprintln!("Error al exportar el grafo: {}", e),
+                }
+            }
+        }
+        if let Some(distinct_stats) = distinct_stats {
+            extra_sections.insert(
+                "distinct_counts".to_string(),
+                top_calculator.calculate_distinct_counts(&distinct_stats),
+            );
+        }
+        let (top_killers, top_weapons) = match top_killers_summary {
+            Some(top_killers_summary) => {
+                extra_sections.insert(
+                    "top_killers_error".to_string(),
+                    top_calculator.calculate_top_killers_error(&top_killers_summary),
+                );
+                (
+                    top_calculator.calculate_approximate_top_killers(&top_killers_summary),
+                    top_calculator.calculate_top_weapons(weapons),
+                )
+            }
+            None => top_calculator.calculate_and_sort_results(weapons, player_kills),
+        };
+        self.metrics.add_phase_time(TOP_PHASE, top_start.elapsed());
+        extra_sections.extend(self.get_metadata_sections(parser));
+        self.metrics.measure(WRITE_PHASE, || {
+            writer.write_results_in_file(top_killers, top_weapons, extra_sections)
+        })
+    }
+
+    /// Processes CSV files and writes the profile of a player to an output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    /// * `player_name` - Name of the player.
+    pub fn process_and_write_player_profile(&self, parser: &ArgumentParser, player_name: &str) {
+        let top_calculator = TopCalculator::new();
+        let writer = Writer::new(parser.get_output_file_name());
+        let processed_data = self.process_input(parser);
+        let duration = self.get_duration().elapsed();
+        if self.is_output_cancelled(parser) {
+            return;
+        }
+        let player_profile = processed_data.player_profile.unwrap_or_default();
+        let player_profile = self.metrics.measure(TOP_PHASE, || {
+            top_calculator.calculate_player_profile(
+                player_name,
+                &player_profile,
+                processed_data.players_weapons.get(player_name),
+            )
+        });
+        self.write_json_output(parser, &writer, player_profile);
+        println!("Tiempo total de lectura: {:?}", duration);
+        self.print_metrics();
+    }
+
+    /// Processes CSV files with a query and writes its rows to an output file.
+    /// If the query is not valid, the function exits the program
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    pub fn process_and_write_query_results(&self, parser: &ArgumentParser) {
+        if parser.get_state_file().is_some() || parser.get_checkpoint_file().is_some() {
+            eprintln!("Las opciones --state y --checkpoint no se pueden usar con query");
+            std::process::exit(1);
+        }
+        let query = compile_query(parser);
+        let top_calculator = TopCalculator::new();
+        let writer = Writer::new(parser.get_output_file_name());
+        let paths = self.metrics.measure(SCAN_PHASE, || parser.get_vec_paths());
+        let groups = self.map_reduce_csvs(
+            &paths,
+            &self.get_needed_fields(&query.get_fields()),
+            |groups, fields, distance| query.fold_fields(groups, fields, distance),
+            HashMap::new,
+            query::merge_groups,
+        );
+        let mut aggregate_sizes = AggregateSizes::new();
+        aggregate_sizes.add(
+            "query_groups",
+            &[("groups", groups.len())],
+            query::estimate_groups_bytes(&groups),
+        );
+        *self
+            .aggregate_sizes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = aggregate_sizes;
+        let duration = self.get_duration().elapsed();
+        if self.is_output_cancelled(parser) {
+            return;
+        }
+        let query_rows = self.metrics.measure(TOP_PHASE, || {
+            top_calculator.calculate_query_rows(&query, groups)
+        });
+        self.write_json_output(parser, &writer, query_rows);
+        println!("Tiempo total de lectura: {:?}", duration);
+        self.print_metrics();
+    }
+
+    /// Processes CSV files and writes the detail of a weapon to an output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - Argument parser with command line arguments.
+    /// * `weapon_name` - Name of the weapon, as it appears in the `killed_by` column.
+    pub fn process_and_write_weapon_detail(&self, parser: &ArgumentParser, weapon_name: &str) {
+        let top_calculator = TopCalculator::new();
+        let writer = Writer::new(parser.get_output_file_name());
+        let processed_data = self.process_input(parser);
+        let duration = self.get_duration().elapsed();
+        if self.is_output_cancelled(parser) {
+            return;
+        }
+        let weapon_detail = self.metrics.measure(TOP_PHASE, || {
+            top_calculator.calculate_weapon_detail(
+                weapon_name,
+                &processed_data.weapons,
+                &processed_data.players_weapons,
+            )
+        });
+        self.write_json_output(parser, &writer, weapon_detail);
+        println!("Tiempo total de lectura: {:?}", duration);
+        self.print_metrics();
+    }
+}
+
+/// Loads the state of the previous runs, or creates an empty one if the file does not exist.
+/// If the state cannot be loaded or was computed with other options, the function exits the program
+///
+/// # Arguments
+///
+/// * `state_file` - Path of the state file.
+/// * `options` - Options that change the statistics.
+///
+/// # Returns
+///
+/// Returns the state.
+fn load_state(state_file: &str, options: serde_json::Value) -> AnalysisState<ProcessedData> {
+    match AnalysisState::load(state_file) {
+        Ok(Some(state)) => {
+            if let Err(e) = state.check_options(&options) {
+                eprintln!("El estado {} no se puede usar: {}", state_file, e);
+                std::process::exit(1);
+            }
+            state
+        }
+        Ok(None) => AnalysisState::new(options),
+        Err(e) => {
+            eprintln!("Error al leer el estado {}: {}", state_file, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the checkpoint of a run that was stopped, and checks that it is from the same
+/// input files and options, and that the processed files did not change.
+/// If the checkpoint cannot be resumed, the function exits the program
+///
+/// # Arguments
+///
+/// * `checkpoint_file` - Path of the checkpoint file.
+/// * `options` - Options and input files of the run.
+/// * `checksums` - Current checksum of each input file.
+///
+/// # Returns
+///
+/// Returns the checkpoint.
+fn load_checkpoint(
+    checkpoint_file: &str,
+    options: serde_json::Value,
+    checksums: &[(PathBuf, u64)],
+) -> AnalysisState<ProcessedData> {
+    let checkpoint = match AnalysisState::load(checkpoint_file) {
+        Ok(Some(checkpoint)) => checkpoint,
+        Ok(None) => {
+            println!(
+                "No hay un checkpoint en {}, se procesa todo",
+                checkpoint_file
+            );
+            return AnalysisState::new(options);
+        }
+        Err(e) => {
+            eprintln!("Error al leer el checkpoint {}: {}", checkpoint_file, e);
+            std::process::exit(1);
+        }
+    };
+    let consistency = checkpoint
+        .check_options(&options)
+        .and_then(|_| checkpoint.check_unchanged_files(checksums));
+    if let Err(e) = consistency {
+        eprintln!(
+            "No se puede continuar desde el checkpoint {}: {}",
+            checkpoint_file, e
+        );
+        std::process::exit(1);
+    }
+    checkpoint
+}
+
+/// Calculates the checksum of each file. The files that cannot be read are left out.
+///
+/// # Arguments
+///
+/// * `paths` - Vector of CSV file paths.
+///
+/// # Returns
+///
+/// Returns each path with its checksum.
+fn calculate_checksums(paths: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+    paths
+        .par_iter()
+        .filter_map(|path| match analysis_state::calculate_checksum(path) {
+            Ok(checksum) => Some((path.clone(), checksum)),
+            Err(e) => {
+                eprintln!("Error al leer el archivo {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the backend given in the arguments, with a number of worker threads.
+/// Only the `pipeline` backend has I/O threads, so `--io-threads` cannot be used with the others.
+/// If the backend is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+/// * `num_threads` - Number of worker threads.
+///
+/// # Returns
+///
+/// Returns the backend.
+fn get_backend(parser: &ArgumentParser, num_threads: usize) -> Backend {
+    if parser.get_io_threads().is_some() && parser.get_backend() != Some("pipeline") {
+        eprintln!("La opcion --io-threads solo se puede usar con --backend pipeline");
+        std::process::exit(1);
+    }
+    let pipeline_config = PipelineConfig::parse(
+        parser.get_aggregator_shards(),
+        parser.get_channel_capacity(),
+    );
+    let thread_pool_config = ThreadPoolConfig::parse(
+        parser.get_thread_name(),
+        parser.get_stack_size(),
+        parser.is_pin_threads_enabled(),
+        parser.get_io_threads(),
+    );
+    let io_mode = IoMode::parse(parser.get_io_mode());
+    match pipeline_config.and_then(|pipeline_config| {
+        Backend::parse(
+            parser.get_backend(),
+            num_threads,
+            pipeline_config,
+            thread_pool_config?,
+            io_mode?,
+        )
+    }) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns the format of the input files. The columnar files are read by row group with the
+/// thread pool of rayon, so they cannot be read with other backends or with `--io`.
+/// If the format is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+fn get_input_format(parser: &ArgumentParser) -> InputFormat {
+    let input_format = match InputFormat::parse(parser.get_input_format()) {
+        Ok(input_format) => input_format,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let is_rayon_backend = parser
+        .get_backend()
+        .is_none_or(|backend| backend == "rayon");
+    if input_format != InputFormat::Csv && (!is_rayon_backend || parser.get_io_mode().is_some()) {
+        eprintln!(
+            "--input-format {} solo se puede usar con --backend rayon y sin --io, \
+             los archivos se leen por grupos de filas",
+            parser.get_input_format().unwrap_or("")
+        );
+        std::process::exit(1);
+    }
+    input_format
+}
+
+/// Creates the sharded map where the threads aggregate the kills of the players directly,
+/// when it was requested with `--aggregation sharded` and the exact top is calculated.
+/// The map is shared by all the files, so it cannot be used when the statistics of each file
+/// are kept apart. If the aggregation is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+/// * `num_threads` - Number of worker threads.
+///
+/// # Returns
+///
+/// Returns the sharded map, or `None` if the kills are merged with fold and reduce.
+fn get_players_weapons_shards(
+    parser: &ArgumentParser,
+    num_threads: usize,
+) -> Option<ShardedMap<HashMap<String, i32>>> {
+    match parser.get_aggregation() {
+        None | Some("reduce") => None,
+        Some("sharded")
+            if parser.get_state_file().is_some()
+                || parser.get_checkpoint_file().is_some()
+                || parser.is_partial_enabled() =>
+        {
+            eprintln!(
+                "--aggregation sharded no se puede usar con --state, --checkpoint ni --partial, \
+                 que necesitan las estadisticas de cada archivo por separado"
+            );
+            std::process::exit(1);
+        }
+        Some("sharded") => (!parser.is_approx_enabled()).then(|| ShardedMap::new(num_threads)),
+        Some(other) => {
+            eprintln!(
+                "Agregacion invalida '{}', las agregaciones validas son: reduce y sharded",
+                other
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Creates the store where the kills of the players are spilled when they grow past the
+/// `--memory-limit`. The limit is split among the threads, because each one accumulates its own
+/// kills, and the kills are spilled with the file they come from, so they cannot be kept apart
+/// by file. If the limit is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+/// * `num_threads` - Number of worker threads.
+///
+/// # Returns
+///
+/// Returns the store, or `None` if there is no memory limit.
+fn get_players_weapons_spill(parser: &ArgumentParser, num_threads: usize) -> Option<SpillStore> {
+    let memory_limit = parser.get_memory_limit()?;
+    if parser.get_state_file().is_some()
+        || parser.get_checkpoint_file().is_some()
+        || parser.is_partial_enabled()
+        || parser.get_aggregation() == Some("sharded")
+    {
+        eprintln!(
+            "--memory-limit no se puede usar con --state, --checkpoint, --partial \
+             ni --aggregation sharded"
+        );
+        std::process::exit(1);
+    }
+    match spill::parse_memory_size(memory_limit) {
+        // Cada hilo acumula sus jugadores, y mientras se combinan hay dos acumulados por hilo
+        Ok(memory_limit) => Some(SpillStore::new(
+            memory_limit / (2 * num_threads.max(1)),
+            DEFAULT_SPILL_PARTITIONS,
+        )),
+        Err(e) => {
+            eprintln!("Error en --memory-limit: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Keeps the players that can be among the `n` with more kills, ties included.
+///
+/// # Arguments
+///
+/// * `players_weapons` - Kills of each player with each weapon.
+/// * `n` - Number of players of the top.
+/// * `get_kills` - Function that returns the kills of a player counted by the top.
+///
+/// # Returns
+///
+/// Returns the kills of the players that are kept.
+fn retain_top_players(
+    mut players_weapons: PlayersWeapons,
+    n: usize,
+    get_kills: impl Fn(&HashMap<String, i32>) -> i32,
+) -> PlayersWeapons {
+    let mut kills: Vec<i32> = players_weapons.values().map(&get_kills).collect();
+    if kills.len() <= n || n == 0 {
+        return players_weapons;
+    }
+    let (_, &mut min_kills, _) = kills.select_nth_unstable_by(n - 1, |a, b| b.cmp(a));
+    players_weapons.retain(|_, weapons| get_kills(weapons) >= min_kills);
+    players_weapons
+}
+
+/// Compiles the filter given in the arguments.
+/// If the expression is not valid, the function shows where the error is and exits the program
+///
+/// # Arguments
+///
+/// * `expression` - The filter expression.
+///
+/// # Returns
+///
+/// Returns the compiled filter.
+fn compile_filter(expression: &str) -> Filter {
+    match Filter::parse(expression) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Error en el filtro: {}", e);
+            eprintln!("  {}", expression);
+            eprintln!("  {}^", " ".repeat(e.get_position()));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns how the distinct values have to be counted, if the counts were requested.
+/// If the mode or the precision are not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// Returns the mode, or `None` if the counts were not requested.
+fn get_distinct_mode(parser: &ArgumentParser) -> Option<DistinctMode> {
+    let precision = match parser.get_hll_precision().map(|p| p.parse::<u8>()) {
+        None => hyper_log_log::DEFAULT_PRECISION,
+        Some(Ok(precision))
+            if (hyper_log_log::MIN_PRECISION..=hyper_log_log::MAX_PRECISION)
+                .contains(&precision) =>
+        {
+            precision
+        }
+        Some(_) => {
+            eprintln!(
+                "La precision de HyperLogLog debe ser un entero entre {} y {}",
+                hyper_log_log::MIN_PRECISION,
+                hyper_log_log::MAX_PRECISION
+            );
+            std::process::exit(1);
+        }
+    };
+    match parser.get_distinct_mode()? {
+        "exact" => Some(DistinctMode::Exact),
+        "approx" => Some(DistinctMode::Approximate(precision)),
+        mode => {
+            eprintln!(
+                "Modo de conteo desconocido '{}', los modos validos son: exact y approx",
+                mode
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns the number of players monitored by the summary of the top killers,
+/// if the approximate top killers were requested with `--approx`.
+/// If the capacity is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// Returns the capacity, or `None` if the exact top killers have to be calculated.
+fn get_top_killers_capacity(parser: &ArgumentParser) -> Option<usize> {
+    if !parser.is_approx_enabled() {
+        return None;
+    }
+    match parser.get_approx_capacity().map(|c| c.parse::<usize>()) {
+        None => Some(DEFAULT_TOP_KILLERS_CAPACITY),
+        Some(Ok(capacity)) if capacity >= MIN_TOP_KILLERS_CAPACITY => Some(capacity),
+        Some(_) => {
+            eprintln!(
+                "La capacidad de --approx debe ser un entero mayor o igual a {}",
+                MIN_TOP_KILLERS_CAPACITY
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns how often the processed files are saved in the state or the checkpoint,
+/// given with `--checkpoint-interval`.
+/// If the interval is not valid or there is no state nor checkpoint, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// Returns the interval, each file by default.
+fn get_checkpoint_interval(parser: &ArgumentParser) -> CheckpointInterval {
+    let interval = parser.get_checkpoint_interval();
+    if interval.is_some()
+        && parser.get_state_file().is_none()
+        && parser.get_checkpoint_file().is_none()
+    {
+        eprintln!("La opcion --checkpoint-interval solo se puede usar con --state o --checkpoint");
+        std::process::exit(1);
+    }
+    match CheckpointInterval::parse(interval) {
+        Ok(interval) => interval,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Creates the progress counters of the run. The lines read are counted from the start
+/// when the metrics need them; otherwise only once a `ProgressReporter` is started.
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+/// * `num_threads` - Number of workers of the run.
+fn new_progress(parser: &ArgumentParser, num_threads: usize) -> Arc<Progress> {
+    let progress = Progress::new(num_threads);
+    if parser.is_metrics_enabled() {
+        progress.enable();
+    }
+    Arc::new(progress)
+}
+
+/// Compiles the query given in the arguments.
+/// If the query is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// Returns the compiled query.
+fn compile_query(parser: &ArgumentParser) -> Query {
+    match Query::parse(parser.get_group_by(), parser.get_aggregates()) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("Error en la consulta: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the weapon categories, overridden by the file given in the arguments if any.
+/// If the file cannot be read or parsed, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// Returns the mapping between weapons and categories.
+fn load_weapon_categories(parser: &ArgumentParser) -> WeaponCategories {
+    match parser.get_categories_file() {
+        Some(path) => match WeaponCategories::from_file(path) {
+            Ok(categories) => categories,
+            Err(e) => {
+                eprintln!("Error al leer el archivo de categorias: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => WeaponCategories::new(),
     }
 }
@@ -0,0 +1,291 @@
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hyper_log_log::{self, HyperLogLog},
+    memory_usage,
+};
+
+/// How the distinct values are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DistinctMode {
+    /// Keeps every value, the counts are exact but the memory grows with the values.
+    Exact,
+    /// Keeps a HyperLogLog sketch with the given precision, the memory is fixed.
+    Approximate(u8),
+}
+
+impl DistinctMode {
+    /// Returns the relative standard error of the counts, 0 for the exact ones.
+    pub fn get_relative_standard_error(&self) -> f64 {
+        match self {
+            DistinctMode::Exact => 0.0,
+            DistinctMode::Approximate(precision) => {
+                hyper_log_log::get_relative_standard_error(*precision)
+            }
+        }
+    }
+}
+
+/// Fields of a distinct value, owned by an exact counter or borrowed from a line. The counters
+/// look up the borrowed fields, so a value is only allocated when it is new.
+trait ValueFields {
+    fn get_field_count(&self) -> usize;
+
+    fn get_field(&self, index: usize) -> &str;
+}
+
+impl Hash for dyn ValueFields + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.get_field_count());
+        (0..self.get_field_count()).for_each(|index| self.get_field(index).hash(state));
+    }
+}
+
+impl PartialEq for dyn ValueFields + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_field_count() == other.get_field_count()
+            && (0..self.get_field_count())
+                .all(|index| self.get_field(index) == other.get_field(index))
+    }
+}
+
+impl Eq for dyn ValueFields + '_ {}
+
+impl ValueFields for &[&str] {
+    fn get_field_count(&self) -> usize {
+        self.len()
+    }
+
+    fn get_field(&self, index: usize) -> &str {
+        self[index]
+    }
+}
+
+/// Value kept by an exact counter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DistinctValue(Box<[Box<str>]>);
+
+impl ValueFields for DistinctValue {
+    fn get_field_count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get_field(&self, index: usize) -> &str {
+        &self.0[index]
+    }
+}
+
+impl<'a> Borrow<dyn ValueFields + 'a> for DistinctValue {
+    fn borrow(&self) -> &(dyn ValueFields + 'a) {
+        self
+    }
+}
+
+// Hash y Eq tienen que coincidir con los de dyn ValueFields para poder buscar los campos prestados
+impl Hash for DistinctValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Borrow::<dyn ValueFields>::borrow(self).hash(state);
+    }
+}
+
+impl PartialEq for DistinctValue {
+    fn eq(&self, other: &Self) -> bool {
+        Borrow::<dyn ValueFields>::borrow(self) == Borrow::<dyn ValueFields>::borrow(other)
+    }
+}
+
+impl Eq for DistinctValue {}
+
+/// Counter of distinct values, exact or approximate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum DistinctCounter {
+    Exact(HashSet<DistinctValue>),
+    Approximate(HyperLogLog),
+}
+
+impl DistinctCounter {
+    fn new(mode: DistinctMode) -> Self {
+        match mode {
+            DistinctMode::Exact => DistinctCounter::Exact(HashSet::new()),
+            DistinctMode::Approximate(precision) => {
+                DistinctCounter::Approximate(HyperLogLog::new(precision))
+            }
+        }
+    }
+
+    /// Adds a value, made of one or more fields.
+    fn add(&mut self, value: &[&str]) {
+        match self {
+            DistinctCounter::Exact(values) => {
+                if !values.contains(&value as &dyn ValueFields) {
+                    values.insert(DistinctValue(
+                        value.iter().map(|field| Box::from(*field)).collect(),
+                    ));
+                }
+            }
+            DistinctCounter::Approximate(sketch) => sketch.add(value),
+        }
+    }
+
+    fn merge(&mut self, other: &DistinctCounter) {
+        match (self, other) {
+            (DistinctCounter::Exact(values), DistinctCounter::Exact(other_values)) => {
+                other_values.iter().for_each(|value| {
+                    if !values.contains(value) {
+                        values.insert(value.clone());
+                    }
+                });
+            }
+            (DistinctCounter::Approximate(sketch), DistinctCounter::Approximate(other_sketch)) => {
+                sketch.merge(other_sketch);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_count(&self) -> u64 {
+        match self {
+            DistinctCounter::Exact(values) => values.len() as u64,
+            DistinctCounter::Approximate(sketch) => sketch.estimate(),
+        }
+    }
+
+    fn estimate_bytes(&self) -> usize {
+        match self {
+            DistinctCounter::Exact(values) => values
+                .iter()
+                .flat_map(|value| value.0.iter())
+                .map(|field| memory_usage::estimate_entry_bytes(field))
+                .sum(),
+            DistinctCounter::Approximate(sketch) => sketch.estimate_bytes(),
+        }
+    }
+}
+
+/// Distinct players, matches and killer-victim pairs of a set of lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistinctCounts {
+    players: DistinctCounter,
+    matches: DistinctCounter,
+    killer_victim_pairs: DistinctCounter,
+}
+
+impl DistinctCounts {
+    fn new(mode: DistinctMode) -> Self {
+        Self {
+            players: DistinctCounter::new(mode),
+            matches: DistinctCounter::new(mode),
+            killer_victim_pairs: DistinctCounter::new(mode),
+        }
+    }
+
+    fn merge(&mut self, other: &DistinctCounts) {
+        self.players.merge(&other.players);
+        self.matches.merge(&other.matches);
+        self.killer_victim_pairs.merge(&other.killer_victim_pairs);
+    }
+
+    pub fn get_players(&self) -> u64 {
+        self.players.get_count()
+    }
+
+    pub fn get_matches(&self) -> u64 {
+        self.matches.get_count()
+    }
+
+    pub fn get_killer_victim_pairs(&self) -> u64 {
+        self.killer_victim_pairs.get_count()
+    }
+
+    /// Returns the estimated bytes of the counters.
+    pub fn estimate_bytes(&self) -> usize {
+        self.players.estimate_bytes()
+            + self.matches.estimate_bytes()
+            + self.killer_victim_pairs.estimate_bytes()
+    }
+}
+
+/// Distinct counts of each map. The global counts are the union of the counts of the maps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistinctStats {
+    mode: DistinctMode,
+    counts_by_map: HashMap<String, DistinctCounts>,
+}
+
+impl DistinctStats {
+    pub fn new(mode: DistinctMode) -> Self {
+        Self {
+            mode,
+            counts_by_map: HashMap::new(),
+        }
+    }
+
+    /// Registers a kill. The empty names, like the killer of the deaths by the blue zone,
+    /// are not counted as players.
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - Map of the match.
+    /// * `match_id` - Identifier of the match.
+    /// * `killer_name` - Name of the killer.
+    /// * `victim_name` - Name of the victim.
+    pub fn add_kill(&mut self, map: &str, match_id: &str, killer_name: &str, victim_name: &str) {
+        let mode = self.mode;
+        let counts = match self.counts_by_map.get_mut(map) {
+            Some(counts) => counts,
+            None => self
+                .counts_by_map
+                .entry(map.to_string())
+                .or_insert_with(|| DistinctCounts::new(mode)),
+        };
+        counts.matches.add(&[match_id]);
+        [killer_name, victim_name]
+            .iter()
+            .filter(|name| !name.is_empty())
+            .for_each(|name| counts.players.add(&[name]));
+        if !killer_name.is_empty() && !victim_name.is_empty() {
+            counts.killer_victim_pairs.add(&[killer_name, victim_name]);
+        }
+    }
+
+    /// Adds the counts of other distinct statistics to these ones.
+    pub fn merge(&mut self, other: &DistinctStats) {
+        other.counts_by_map.iter().for_each(|(map, counts)| {
+            match self.counts_by_map.get_mut(map) {
+                Some(acc_counts) => acc_counts.merge(counts),
+                None => {
+                    self.counts_by_map.insert(map.clone(), counts.clone());
+                }
+            }
+        });
+    }
+
+    pub fn get_mode(&self) -> DistinctMode {
+        self.mode
+    }
+
+    pub fn get_counts_by_map(&self) -> &HashMap<String, DistinctCounts> {
+        &self.counts_by_map
+    }
+
+    /// Returns the counts over all the maps.
+    pub fn get_global_counts(&self) -> DistinctCounts {
+        self.counts_by_map
+            .values()
+            .fold(DistinctCounts::new(self.mode), |mut acc, counts| {
+                acc.merge(counts);
+                acc
+            })
+    }
+
+    /// Returns the estimated bytes of the counters of all the maps.
+    pub fn estimate_bytes(&self) -> usize {
+        memory_usage::estimate_map_bytes(&self.counts_by_map, DistinctCounts::estimate_bytes)
+    }
+}
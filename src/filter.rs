@@ -0,0 +1,422 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::columns::Column;
+
+/// Error found while compiling a filter expression.
+#[derive(Debug)]
+pub struct FilterError {
+    position: usize,
+    message: String,
+}
+
+impl FilterError {
+    fn new(position: usize, message: &str) -> Self {
+        Self {
+            position,
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns the position of the expression, starting at 0, where the error was found.
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (posicion {})", self.message, self.position + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::NotEqual => ordering != Ordering::Equal,
+            Operator::Less => ordering == Ordering::Less,
+            Operator::LessOrEqual => ordering != Ordering::Greater,
+            Operator::Greater => ordering == Ordering::Greater,
+            Operator::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(f64),
+}
+
+#[derive(Debug)]
+enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Comparison(Column, Operator, Value),
+    In(Column, Vec<Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Identifier(String),
+    Text(String),
+    Number(f64),
+    Operator(Operator),
+    And,
+    Or,
+    Not,
+    In,
+    OpenParenthesis,
+    CloseParenthesis,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+/// Filter over the lines of the dataset, compiled from an expression like
+/// `map == "ERANGEL" && time > 600`.
+///
+/// The expressions compare a column with a value (`==`, `!=`, `<`, `<=`, `>`, `>=`),
+/// or check that a column is one of a list of values (`match_id in ["a", "b"]`),
+/// and combine the comparisons with `&&`, `||`, `!` and parentheses.
+/// The text values go between double quotes and the numbers are written as they are.
+#[derive(Debug)]
+pub struct Filter {
+    expression: Expression,
+}
+
+impl Filter {
+    /// Compiles a filter expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The filter expression.
+    ///
+    /// # Returns
+    ///
+    /// The compiled filter, or the first error found in the expression.
+    pub fn parse(source: &str) -> Result<Self, FilterError> {
+        let tokens = tokenize(source)?;
+        let mut parser = FilterParser { tokens, next: 0 };
+        let expression = parser.parse_or()?;
+        let token = parser.peek();
+        if token.kind != TokenKind::End {
+            return Err(FilterError::new(
+                token.position,
+                "se esperaba && o || entre las condiciones",
+            ));
+        }
+        Ok(Self { expression })
+    }
+
+    /// Returns true if the line satisfies the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    pub fn matches(&self, fields: &[&str], distance: Option<f64>) -> bool {
+        evaluate(&self.expression, fields, distance)
+    }
+
+    /// Returns the fields of the CSV line read by the filter, which can be repeated.
+    pub fn get_fields(&self) -> Vec<usize> {
+        let mut fields = Vec::new();
+        add_fields(&self.expression, &mut fields);
+        fields
+    }
+}
+
+fn add_fields(expression: &Expression, fields: &mut Vec<usize>) {
+    match expression {
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            add_fields(left, fields);
+            add_fields(right, fields);
+        }
+        Expression::Not(inner) => add_fields(inner, fields),
+        Expression::Comparison(column, _, _) | Expression::In(column, _) => {
+            fields.extend(column.get_fields())
+        }
+    }
+}
+
+fn evaluate(expression: &Expression, fields: &[&str], distance: Option<f64>) -> bool {
+    match expression {
+        Expression::And(left, right) => {
+            evaluate(left, fields, distance) && evaluate(right, fields, distance)
+        }
+        Expression::Or(left, right) => {
+            evaluate(left, fields, distance) || evaluate(right, fields, distance)
+        }
+        Expression::Not(inner) => !evaluate(inner, fields, distance),
+        Expression::Comparison(column, operator, value) => {
+            compare(*column, value, fields, distance).is_some_and(|o| operator.accepts(o))
+        }
+        Expression::In(column, values) => values
+            .iter()
+            .any(|value| compare(*column, value, fields, distance) == Some(Ordering::Equal)),
+    }
+}
+
+/// Compares the column of the line with a value.
+/// Returns `None` if the column is missing or is not a number when it should be one.
+fn compare(
+    column: Column,
+    value: &Value,
+    fields: &[&str],
+    distance: Option<f64>,
+) -> Option<Ordering> {
+    match value {
+        Value::Text(text) => column.get_text(fields).map(|f| f.cmp(text.as_str())),
+        Value::Number(number) => column
+            .get_number(fields, distance)
+            .and_then(|f| f.partial_cmp(number)),
+    }
+}
+
+struct FilterParser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl FilterParser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].clone();
+        if token.kind != TokenKind::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, message: &str) -> Result<Token, FilterError> {
+        let token = self.advance();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(FilterError::new(token.position, message))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, FilterError> {
+        let mut expression = self.parse_and()?;
+        while self.peek().kind == TokenKind::Or {
+            self.advance();
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, FilterError> {
+        let mut expression = self.parse_unary()?;
+        while self.peek().kind == TokenKind::And {
+            self.advance();
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, FilterError> {
+        match self.peek().kind {
+            TokenKind::Not => {
+                self.advance();
+                Ok(Expression::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::OpenParenthesis => {
+                self.advance();
+                let expression = self.parse_or()?;
+                self.expect(TokenKind::CloseParenthesis, "falta cerrar el parentesis")?;
+                Ok(expression)
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, FilterError> {
+        let column = self.parse_column()?;
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Operator(operator) => {
+                let value = self.parse_value(column)?;
+                Ok(Expression::Comparison(column, operator, value))
+            }
+            TokenKind::In => {
+                self.expect(TokenKind::OpenBracket, "se esperaba [ despues de in")?;
+                let mut values = vec![self.parse_value(column)?];
+                while self.peek().kind == TokenKind::Comma {
+                    self.advance();
+                    values.push(self.parse_value(column)?);
+                }
+                self.expect(TokenKind::CloseBracket, "falta cerrar la lista con ]")?;
+                Ok(Expression::In(column, values))
+            }
+            _ => Err(FilterError::new(
+                token.position,
+                "se esperaba un operador (==, !=, <, <=, >, >= o in)",
+            )),
+        }
+    }
+
+    fn parse_column(&mut self) -> Result<Column, FilterError> {
+        let token = self.advance();
+        let TokenKind::Identifier(name) = &token.kind else {
+            return Err(FilterError::new(token.position, "se esperaba una columna"));
+        };
+        Column::find(name).ok_or_else(|| {
+            FilterError::new(
+                token.position,
+                &format!(
+                    "columna desconocida '{}', las columnas validas son: {}",
+                    name,
+                    Column::get_names()
+                ),
+            )
+        })
+    }
+
+    fn parse_value(&mut self, column: Column) -> Result<Value, FilterError> {
+        let token = self.advance();
+        match (column.is_numeric(), token.kind) {
+            (false, TokenKind::Text(text)) => Ok(Value::Text(text)),
+            (true, TokenKind::Number(number)) => Ok(Value::Number(number)),
+            (false, TokenKind::Number(_)) => Err(FilterError::new(
+                token.position,
+                "la columna es de texto, el valor tiene que ir entre comillas",
+            )),
+            (true, TokenKind::Text(_)) => Err(FilterError::new(
+                token.position,
+                "la columna es numerica, el valor tiene que ser un numero",
+            )),
+            _ => Err(FilterError::new(
+                token.position,
+                "se esperaba un valor, los textos van entre comillas",
+            )),
+        }
+    }
+}
+
+/// Splits the expression into tokens.
+fn tokenize(source: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < chars.len() {
+        let current = chars[position];
+        let next = chars.get(position + 1).copied();
+        let start = position;
+        let kind = match (current, next) {
+            (c, _) if c.is_whitespace() => {
+                position += 1;
+                continue;
+            }
+            ('&', Some('&')) => TokenKind::And,
+            ('|', Some('|')) => TokenKind::Or,
+            ('=', Some('=')) => TokenKind::Operator(Operator::Equal),
+            ('!', Some('=')) => TokenKind::Operator(Operator::NotEqual),
+            ('<', Some('=')) => TokenKind::Operator(Operator::LessOrEqual),
+            ('>', Some('=')) => TokenKind::Operator(Operator::GreaterOrEqual),
+            ('<', _) => TokenKind::Operator(Operator::Less),
+            ('>', _) => TokenKind::Operator(Operator::Greater),
+            ('!', _) => TokenKind::Not,
+            ('(', _) => TokenKind::OpenParenthesis,
+            (')', _) => TokenKind::CloseParenthesis,
+            ('[', _) => TokenKind::OpenBracket,
+            (']', _) => TokenKind::CloseBracket,
+            (',', _) => TokenKind::Comma,
+            ('"', _) => {
+                let length = chars[position + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| FilterError::new(start, "falta cerrar las comillas"))?;
+                let text: String = chars[position + 1..position + 1 + length].iter().collect();
+                position += length + 2;
+                tokens.push(Token {
+                    kind: TokenKind::Text(text),
+                    position: start,
+                });
+                continue;
+            }
+            (c, _) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let length = chars[position + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count();
+                let number: String = chars[position..position + 1 + length].iter().collect();
+                position += length + 1;
+                let number = number
+                    .parse::<f64>()
+                    .map_err(|_| FilterError::new(start, "numero invalido"))?;
+                tokens.push(Token {
+                    kind: TokenKind::Number(number),
+                    position: start,
+                });
+                continue;
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let length = chars[position..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '_')
+                    .count();
+                let word: String = chars[position..position + length].iter().collect();
+                position += length;
+                let kind = match word.as_str() {
+                    "in" => TokenKind::In,
+                    _ => TokenKind::Identifier(word),
+                };
+                tokens.push(Token {
+                    kind,
+                    position: start,
+                });
+                continue;
+            }
+            _ => {
+                return Err(FilterError::new(
+                    start,
+                    &format!("caracter inesperado '{}'", current),
+                ))
+            }
+        };
+        // Los tokens de uno o dos caracteres
+        position += match kind {
+            TokenKind::And
+            | TokenKind::Or
+            | TokenKind::Operator(Operator::Equal)
+            | TokenKind::Operator(Operator::NotEqual)
+            | TokenKind::Operator(Operator::LessOrEqual)
+            | TokenKind::Operator(Operator::GreaterOrEqual) => 2,
+            _ => 1,
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: chars.len(),
+    });
+    Ok(tokens)
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::kill_graph::KillGraph;
+
+pub struct GraphExporter {
+    output_file_name: String,
+}
+
+impl GraphExporter {
+    pub fn new(output_file_name: &str) -> Self {
+        Self {
+            output_file_name: output_file_name.to_string(),
+        }
+    }
+
+    fn get_output_file_name(&self) -> &str {
+        &self.output_file_name
+    }
+
+    /// Exports the kill graph in the format given by the extension of the output file:
+    /// GraphML (`.graphml`), Graphviz DOT (`.dot` or `.gv`) or an edge list (any other extension).
+    ///
+    /// # Arguments
+    ///
+    /// * `kill_graph` - The graph to export
+    ///
+    /// # Returns
+    ///
+    /// A Result with the result of the operation
+    pub fn export(&self, kill_graph: &KillGraph) -> std::io::Result<()> {
+        let mut edges: Vec<(&(String, String), &u32)> = kill_graph.get_edges().iter().collect();
+        // Ordenar las aristas para que el archivo sea reproducible
+        edges.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+        let mut file = BufWriter::new(File::create(self.get_output_file_name())?);
+        match Path::new(self.get_output_file_name())
+            .extension()
+            .and_then(|e| e.to_str())
+        {
+            Some("graphml") => write_graphml(&mut file, &edges)?,
+            Some("dot") | Some("gv") => write_dot(&mut file, &edges)?,
+            _ => write_edge_list(&mut file, &edges)?,
+        }
+        file.flush()
+    }
+}
+
+fn write_graphml(
+    file: &mut impl Write,
+    edges: &[(&(String, String), &u32)],
+) -> std::io::Result<()> {
+    let players: BTreeSet<&String> = edges
+        .iter()
+        .flat_map(|((killer, victim), _)| [killer, victim])
+        .collect();
+
+    writeln!(file, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        file,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        file,
+        r#"  <key id="kills" for="edge" attr.name="kills" attr.type="int"/>"#
+    )?;
+    writeln!(file, r#"  <graph id="kills" edgedefault="directed">"#)?;
+    for player in players {
+        writeln!(file, r#"    <node id="{}"/>"#, escape_xml(player))?;
+    }
+    for ((killer, victim), kills) in edges {
+        writeln!(
+            file,
+            r#"    <edge source="{}" target="{}"><data key="kills">{}</data></edge>"#,
+            escape_xml(killer),
+            escape_xml(victim),
+            kills
+        )?;
+    }
+    writeln!(file, "  </graph>")?;
+    writeln!(file, "</graphml>")
+}
+
+fn write_dot(file: &mut impl Write, edges: &[(&(String, String), &u32)]) -> std::io::Result<()> {
+    writeln!(file, "digraph kills {{")?;
+    for ((killer, victim), kills) in edges {
+        writeln!(
+            file,
+            r#"  "{}" -> "{}" [weight={}, label={}];"#,
+            escape_dot(killer),
+            escape_dot(victim),
+            kills,
+            kills
+        )?;
+    }
+    writeln!(file, "}}")
+}
+
+fn write_edge_list(
+    file: &mut impl Write,
+    edges: &[(&(String, String), &u32)],
+) -> std::io::Result<()> {
+    writeln!(file, "source,target,weight")?;
+    for ((killer, victim), kills) in edges {
+        writeln!(
+            file,
+            "{},{},{}",
+            escape_csv(killer),
+            escape_csv(victim),
+            kills
+        )?;
+    }
+    Ok(())
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, hash::Hasher};
+
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::memory_usage;
+
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 18;
+pub const DEFAULT_PRECISION: u8 = 14;
+
+/// Seed of the hash of the items. The sketches are saved in the state files, so changing it,
+/// or the hash function, requires a new `SketchHash`.
+const HASH_SEED: u64 = 0;
+/// Byte written after each field of an item, that cannot be part of UTF-8 text.
+const FIELD_SEPARATOR: u8 = 0xff;
+
+/// Hash function of the items of a sketch. It is saved with the sketch, so that a sketch saved
+/// with another hash function is rejected when it is loaded instead of being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum SketchHash {
+    /// XxHash64 with `HASH_SEED`, of the bytes of the fields each followed by `FIELD_SEPARATOR`.
+    #[serde(rename = "xxhash64")]
+    XxHash64,
+}
+
+/// Registers of the sketch. A sketch starts sparse, with only the registers that were set,
+/// so that the sketch of a single line is small, and becomes dense when it fills up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Registers {
+    Sparse(HashMap<u32, u8>),
+    Dense(Vec<u8>),
+}
+
+/// HyperLogLog sketch that estimates the number of distinct items added to it
+/// using `2^precision` one byte registers, whatever the number of items is.
+/// Two sketches with the same precision can be merged, so each thread can keep its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HyperLogLog {
+    hash: SketchHash,
+    precision: u8,
+    registers: Registers,
+}
+
+impl HyperLogLog {
+    /// Creates an empty sketch.
+    ///
+    /// # Arguments
+    ///
+    /// * `precision` - Number of bits of the hash used to choose the register,
+    ///   between `MIN_PRECISION` and `MAX_PRECISION`.
+    pub fn new(precision: u8) -> Self {
+        Self {
+            hash: SketchHash::XxHash64,
+            precision: precision.clamp(MIN_PRECISION, MAX_PRECISION),
+            registers: Registers::Sparse(HashMap::new()),
+        }
+    }
+
+    fn get_number_of_registers(&self) -> usize {
+        1 << self.precision
+    }
+
+    /// Adds an item, made of one or more fields, to the sketch.
+    pub fn add(&mut self, fields: &[&str]) {
+        let hash = hash_fields(fields);
+
+        let index = (hash >> (64 - self.precision)) as u32;
+        let remaining_bits = hash << self.precision;
+        let rank = (remaining_bits.leading_zeros() as u8 + 1).min(64 - self.precision + 1);
+        self.set_register(index, rank);
+    }
+
+    /// Keeps the highest rank seen by the register.
+    fn set_register(&mut self, index: u32, rank: u8) {
+        match &mut self.registers {
+            Registers::Sparse(registers) => {
+                let register = registers.entry(index).or_default();
+                *register = (*register).max(rank);
+                if registers.len() > self.get_number_of_registers() / 8 {
+                    self.make_dense();
+                }
+            }
+            Registers::Dense(registers) => {
+                let register = &mut registers[index as usize];
+                *register = (*register).max(rank);
+            }
+        }
+    }
+
+    fn make_dense(&mut self) {
+        if let Registers::Sparse(sparse) = &self.registers {
+            let mut dense = vec![0; self.get_number_of_registers()];
+            sparse
+                .iter()
+                .for_each(|(&index, &rank)| dense[index as usize] = rank);
+            self.registers = Registers::Dense(dense);
+        }
+    }
+
+    /// Adds the items of other sketch with the same precision to this one.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        match &other.registers {
+            Registers::Sparse(registers) => registers
+                .iter()
+                .for_each(|(&index, &rank)| self.set_register(index, rank)),
+            Registers::Dense(registers) => {
+                self.make_dense();
+                if let Registers::Dense(acc_registers) = &mut self.registers {
+                    acc_registers
+                        .iter_mut()
+                        .zip(registers)
+                        .for_each(|(acc_rank, &rank)| *acc_rank = (*acc_rank).max(rank));
+                }
+            }
+        }
+    }
+
+    /// Returns the estimated number of distinct items added to the sketch.
+    pub fn estimate(&self) -> u64 {
+        let number_of_registers = self.get_number_of_registers() as f64;
+        let (harmonic_sum, zero_registers) = match &self.registers {
+            Registers::Sparse(registers) => {
+                let zero_registers = number_of_registers - registers.len() as f64;
+                let sum: f64 = registers
+                    .values()
+                    .map(|&rank| 2f64.powi(-(rank as i32)))
+                    .sum();
+                (sum + zero_registers, zero_registers)
+            }
+            Registers::Dense(registers) => {
+                registers.iter().fold((0.0, 0.0), |(sum, zeros), &rank| {
+                    let zeros = if rank == 0 { zeros + 1.0 } else { zeros };
+                    (sum + 2f64.powi(-(rank as i32)), zeros)
+                })
+            }
+        };
+
+        let estimate = get_alpha(number_of_registers) * number_of_registers.powi(2) / harmonic_sum;
+        // Con pocos items la estimacion cruda tiene sesgo, se usa linear counting
+        if estimate <= 2.5 * number_of_registers && zero_registers > 0.0 {
+            (number_of_registers * (number_of_registers / zero_registers).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+
+    /// Returns the estimated bytes of the registers of the sketch.
+    pub fn estimate_bytes(&self) -> usize {
+        match &self.registers {
+            Registers::Sparse(registers) => {
+                registers.len() * memory_usage::estimate_entry_bytes("")
+            }
+            Registers::Dense(registers) => registers.len(),
+        }
+    }
+}
+
+/// Hashes the fields of an item. XxHash64 has a specified output, the same in every build and
+/// version of Rust, so the sketches of different threads and runs assign each item to the same
+/// register.
+fn hash_fields(fields: &[&str]) -> u64 {
+    let mut hasher = XxHash64::with_seed(HASH_SEED);
+    fields.iter().for_each(|field| {
+        hasher.write(field.as_bytes());
+        hasher.write(&[FIELD_SEPARATOR]);
+    });
+    hasher.finish()
+}
+
+/// Returns the relative standard error of the estimates of a sketch with the given precision,
+/// `1.04 / sqrt(2^precision)`.
+pub fn get_relative_standard_error(precision: u8) -> f64 {
+    1.04 / ((1u64 << precision) as f64).sqrt()
+}
+
+fn get_alpha(number_of_registers: f64) -> f64 {
+    match number_of_registers as u32 {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / number_of_registers),
+    }
+}
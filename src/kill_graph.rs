@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory_usage;
+
+/// Directed graph where each edge counts how many times a player killed another one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KillGraph {
+    #[serde(with = "edges_as_list")]
+    edges: HashMap<(String, String), u32>,
+}
+
+impl KillGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a kill to the edge that goes from the killer to the victim.
+    pub fn add_kill(&mut self, killer: &str, victim: &str) {
+        *self
+            .edges
+            .entry((killer.to_string(), victim.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Adds the edges of another graph to this one.
+    pub fn merge(&mut self, other: &KillGraph) {
+        other.edges.iter().for_each(|(edge, kills)| {
+            *self.edges.entry(edge.clone()).or_default() += kills;
+        });
+    }
+
+    /// Returns the number of times the killer killed the victim.
+    pub fn get_kills(&self, killer: &str, victim: &str) -> u32 {
+        self.edges
+            .get(&(killer.to_string(), victim.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn get_edges(&self) -> &HashMap<(String, String), u32> {
+        &self.edges
+    }
+
+    /// Returns the estimated bytes of the graph.
+    pub fn estimate_bytes(&self) -> usize {
+        self.edges
+            .keys()
+            .map(|(killer, victim)| memory_usage::estimate_entry_bytes(killer) + victim.len())
+            .sum()
+    }
+}
+
+/// Serializes the edges as a list of `[killer, victim, kills]`,
+/// because the keys of a JSON object can only be strings.
+mod edges_as_list {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        edges: &HashMap<(String, String), u32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let edges_list: Vec<(&String, &String, &u32)> = edges
+            .iter()
+            .map(|((killer, victim), kills)| (killer, victim, kills))
+            .collect();
+        edges_list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<(String, String), u32>, D::Error> {
+        let edges_list: Vec<(String, String, u32)> = Vec::deserialize(deserializer)?;
+        Ok(edges_list
+            .into_iter()
+            .map(|(killer, victim, kills)| ((killer, victim), kills))
+            .collect())
+    }
+}
@@ -0,0 +1,31 @@
+pub mod weapon_stats;
+pub mod weapon_categories;
+pub mod argument_parser;
+pub mod top_calculator;
+pub mod writer;
+pub mod csvs_processor;
+pub mod placement_stats;
+pub mod kill_graph;
+pub mod graph_exporter;
+pub mod player_profile;
+pub mod filter;
+pub mod columns;
+pub mod query;
+pub mod hyper_log_log;
+pub mod distinct_stats;
+pub mod space_saving;
+pub mod analysis_state;
+pub mod cancellation;
+pub mod progress;
+pub mod metrics;
+pub mod benchmark;
+pub mod backend;
+pub mod pipeline;
+pub mod sharded_map;
+pub mod spill;
+pub mod memory_usage;
+pub mod thread_pool;
+pub mod mmap_reader;
+pub mod tokenizer;
+pub mod columnar_reader;
+pub mod runner;
\ No newline at end of file
@@ -0,0 +1,173 @@
+use std::{collections::HashMap, fs};
+
+use serde_json::json;
+
+/// Bytes estimated for the bookkeeping of an entry of a hash map or set,
+/// besides the bytes of its key: the `String`, the value and the space left in the table.
+const ENTRY_OVERHEAD_BYTES: usize = 48;
+const BYTES_PER_MEGABYTE: f64 = 1024.0 * 1024.0;
+
+/// Returns the bytes estimated for an entry of a hash map or set with the given key.
+pub fn estimate_entry_bytes(key: &str) -> usize {
+    key.len() + ENTRY_OVERHEAD_BYTES
+}
+
+/// Returns the bytes estimated for a hash map with `String` keys.
+///
+/// # Arguments
+///
+/// * `map` - The map.
+/// * `estimate_value` - Function that returns the bytes that a value uses outside of the map,
+///   like the entries of a nested map.
+pub fn estimate_map_bytes<V>(
+    map: &HashMap<String, V>,
+    estimate_value: impl Fn(&V) -> usize,
+) -> usize {
+    map.iter()
+        .map(|(key, value)| estimate_entry_bytes(key) + estimate_value(value))
+        .sum()
+}
+
+/// Returns the maximum resident set size of the process, in bytes, or `None` if the
+/// operating system does not report it. It is read from `/proc/self/status` on Linux.
+pub fn get_peak_rss_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let peak_rss = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kilobytes: u64 = peak_rss.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Size of an aggregate.
+#[derive(Debug, Clone)]
+struct AggregateSize {
+    name: String,
+    counts: Vec<(String, usize)>,
+    estimated_bytes: usize,
+}
+
+/// Sizes of the aggregates of a run, to know how much memory each one uses.
+#[derive(Debug, Default)]
+pub struct AggregateSizes {
+    aggregates: Vec<AggregateSize>,
+}
+
+impl AggregateSizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the size of an aggregate.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the aggregate.
+    /// * `counts` - Number of each kind of entry of the aggregate, like players or pairs.
+    /// * `estimated_bytes` - Estimated bytes of the aggregate.
+    pub fn add(&mut self, name: &str, counts: &[(&str, usize)], estimated_bytes: usize) {
+        self.aggregates.push(AggregateSize {
+            name: name.to_string(),
+            counts: counts
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+            estimated_bytes,
+        });
+    }
+
+    /// Returns the estimated bytes of all the aggregates.
+    pub fn get_estimated_bytes(&self) -> usize {
+        self.aggregates
+            .iter()
+            .map(|aggregate| aggregate.estimated_bytes)
+            .sum()
+    }
+
+    /// Returns the memory usage in a JSON format, with the peak RSS of the process.
+    ///
+    /// # Arguments
+    ///
+    /// * `spilled_bytes` - Bytes spilled to disk, if the aggregates can be spilled.
+    /// * `peak_resident_bytes` - Peak of the estimated bytes in memory of the aggregates
+    ///   that can be spilled, adding up the ones of all the threads.
+    pub fn to_json(
+        &self,
+        spilled_bytes: Option<u64>,
+        peak_resident_bytes: Option<usize>,
+    ) -> serde_json::Value {
+        let aggregates: serde_json::Map<String, serde_json::Value> = self
+            .aggregates
+            .iter()
+            .map(|aggregate| {
+                let mut size: serde_json::Map<String, serde_json::Value> = aggregate
+                    .counts
+                    .iter()
+                    .map(|(kind, count)| (kind.clone(), json!(count)))
+                    .collect();
+                size.insert(
+                    "estimated_bytes".to_string(),
+                    json!(aggregate.estimated_bytes),
+                );
+                (aggregate.name.clone(), serde_json::Value::Object(size))
+            })
+            .collect();
+        json!({
+            "peak_rss_bytes": get_peak_rss_bytes(),
+            "estimated_bytes": self.get_estimated_bytes(),
+            "spilled_bytes": spilled_bytes,
+            "peak_resident_bytes": peak_resident_bytes,
+            "aggregates": aggregates,
+        })
+    }
+
+    /// Prints the peak RSS and the size of each aggregate.
+    ///
+    /// # Arguments
+    ///
+    /// * `spilled_bytes` - Bytes spilled to disk, if the aggregates can be spilled.
+    /// * `peak_resident_bytes` - Peak of the estimated bytes in memory of the aggregates
+    ///   that can be spilled.
+    pub fn print_summary(&self, spilled_bytes: Option<u64>, peak_resident_bytes: Option<usize>) {
+        let peak_rss = match get_peak_rss_bytes() {
+            Some(peak_rss_bytes) => format!("{:.1} MB", to_megabytes(peak_rss_bytes as usize)),
+            None => "desconocido".to_string(),
+        };
+        println!(
+            "Memoria: pico RSS {}, agregados ~{:.1} MB",
+            peak_rss,
+            to_megabytes(self.get_estimated_bytes())
+        );
+        self.aggregates.iter().for_each(|aggregate| {
+            let counts: Vec<String> = aggregate
+                .counts
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect();
+            println!(
+                "  {:<20} ~{:.1} MB ({})",
+                aggregate.name,
+                to_megabytes(aggregate.estimated_bytes),
+                counts.join(", ")
+            );
+        });
+        if let Some(spilled_bytes) = spilled_bytes {
+            println!(
+                "  {:<20} {:.1} MB",
+                "volcado a disco",
+                to_megabytes(spilled_bytes as usize)
+            );
+        }
+        if let Some(peak_resident_bytes) = peak_resident_bytes {
+            println!(
+                "  {:<20} ~{:.1} MB",
+                "pico en memoria",
+                to_megabytes(peak_resident_bytes)
+            );
+        }
+    }
+}
+
+fn to_megabytes(bytes: usize) -> f64 {
+    bytes as f64 / BYTES_PER_MEGABYTE
+}
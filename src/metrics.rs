@@ -0,0 +1,262 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::backend;
+
+/// Listing of the input files, and their checksums when a state or checkpoint is used.
+pub const SCAN_PHASE: &str = "scan";
+/// Reading, parsing and aggregating the lines of each file.
+pub const READ_PARSE_PHASE: &str = "read_parse";
+/// Merging the aggregates of the files.
+pub const REDUCE_PHASE: &str = "reduce";
+/// Calculating the tops and the sections of the output.
+pub const TOP_PHASE: &str = "top";
+/// Writing the output file.
+pub const WRITE_PHASE: &str = "write";
+
+const BYTES_PER_MEGABYTE: f64 = 1024.0 * 1024.0;
+/// The times are written in seconds with microsecond precision.
+const SECONDS_DECIMALS: i32 = 6;
+const RATIO_DECIMALS: i32 = 3;
+
+/// Busy time of a worker in nanoseconds. Each counter takes a whole cache line,
+/// so the workers do not slow each other down when updating their own counter.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct WorkerBusyTime(AtomicU64);
+
+/// Time spent processing a file.
+#[derive(Debug, Clone)]
+struct FileTiming {
+    path: String,
+    duration: Duration,
+    bytes: u64,
+}
+
+/// Timing of the phases of a run, of each file and of each worker.
+#[derive(Debug)]
+pub struct Metrics {
+    phases: Mutex<Vec<(String, Duration)>>,
+    files: Mutex<Vec<FileTiming>>,
+    workers: Vec<WorkerBusyTime>,
+}
+
+impl Metrics {
+    /// Creates empty metrics for a run with the given number of workers.
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            phases: Mutex::new(Vec::new()),
+            files: Mutex::new(Vec::new()),
+            workers: (0..num_threads.max(1))
+                .map(|_| WorkerBusyTime::default())
+                .collect(),
+        }
+    }
+
+    /// Runs a function and adds the time it takes to a phase.
+    pub fn measure<T>(&self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add_phase_time(phase, start.elapsed());
+        result
+    }
+
+    /// Adds time to a phase. A phase can be measured in several parts.
+    pub fn add_phase_time(&self, phase: &str, duration: Duration) {
+        let mut phases = self.phases.lock().unwrap_or_else(PoisonError::into_inner);
+        match phases.iter_mut().find(|(name, _)| name == phase) {
+            Some((_, phase_duration)) => *phase_duration += duration,
+            None => phases.push((phase.to_string(), duration)),
+        }
+    }
+
+    /// Registers the time spent processing a file.
+    pub fn add_file_timing(&self, path: &Path, duration: Duration) {
+        let bytes = path.metadata().map_or(0, |metadata| metadata.len());
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(FileTiming {
+                path: path.to_string_lossy().to_string(),
+                duration,
+                bytes,
+            });
+    }
+
+    /// Runs a function and adds the time it takes to the busy time of the current worker.
+    /// The backends measure each chunk of lines they fold (a part of a file, a block or
+    /// a whole file) and each merge, and only when the metrics were requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Metrics of the run, or `None` to run the function without measuring it.
+    /// * `f` - Work of the worker.
+    pub fn measure_busy_time<T>(metrics: Option<&Self>, f: impl FnOnce() -> T) -> T {
+        let Some(metrics) = metrics else {
+            return f();
+        };
+        let start = Instant::now();
+        let result = f();
+        metrics.add_busy_time(start.elapsed());
+        result
+    }
+
+    /// Adds time that the current worker spent processing lines.
+    /// The time spent outside of the workers of the backend is not counted.
+    fn add_busy_time(&self, duration: Duration) {
+        if let Some(worker) = backend::current_worker_index().and_then(|i| self.workers.get(i)) {
+            worker
+                .0
+                .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the time of each phase, in the order they were first measured.
+    pub fn get_phase_times(&self) -> Vec<(String, Duration)> {
+        self.phases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn get_phase_time(&self, phase: &str) -> Duration {
+        self.get_phase_times()
+            .into_iter()
+            .find(|(name, _)| name == phase)
+            .map_or(Duration::ZERO, |(_, duration)| duration)
+    }
+
+    /// Returns the time of each file, from the slowest to the fastest.
+    fn get_file_timings(&self) -> Vec<FileTiming> {
+        let mut files = self
+            .files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        files.sort_by_key(|file| std::cmp::Reverse(file.duration));
+        files
+    }
+
+    /// Returns the fraction of the read and parse phase that each worker was processing lines.
+    pub fn get_worker_utilization(&self) -> Vec<f64> {
+        let read_parse_seconds = self.get_phase_time(READ_PARSE_PHASE).as_secs_f64();
+        self.workers
+            .iter()
+            .map(|worker| {
+                let busy_seconds = worker.0.load(Ordering::Relaxed) as f64 / 1e9;
+                ratio(busy_seconds, read_parse_seconds).min(1.0)
+            })
+            .collect()
+    }
+
+    /// Returns the metrics in a JSON format.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Lines read.
+    /// * `bytes` - Bytes read.
+    pub fn to_json(&self, rows: u64, bytes: u64) -> serde_json::Value {
+        let phases = self.get_phase_times();
+        let read_parse_seconds = self.get_phase_time(READ_PARSE_PHASE).as_secs_f64();
+        let worker_utilization = self.get_worker_utilization();
+        let total_seconds: f64 = phases.iter().map(|(_, d)| d.as_secs_f64()).sum();
+        json!({
+            "phases": phases
+                .iter()
+                .map(|(name, duration)| (name.clone(), json!(round_seconds(*duration))))
+                .collect::<serde_json::Map<_, _>>(),
+            "total_seconds": round(total_seconds, SECONDS_DECIMALS),
+            "rows": rows,
+            "bytes": bytes,
+            "rows_per_second": ratio(rows as f64, read_parse_seconds).round() as u64,
+            "megabytes_per_second": round(
+                ratio(bytes as f64 / BYTES_PER_MEGABYTE, read_parse_seconds),
+                RATIO_DECIMALS
+            ),
+            "files": self
+                .get_file_timings()
+                .iter()
+                .map(|file| json!({
+                    "path": file.path,
+                    "seconds": round_seconds(file.duration),
+                    "megabytes_per_second": round(
+                        ratio(file.bytes as f64 / BYTES_PER_MEGABYTE, file.duration.as_secs_f64()),
+                        RATIO_DECIMALS
+                    ),
+                }))
+                .collect::<Vec<_>>(),
+            "worker_utilization": {
+                "average": round(get_average(&worker_utilization), RATIO_DECIMALS),
+                "by_worker": worker_utilization
+                    .iter()
+                    .map(|utilization| round(*utilization, RATIO_DECIMALS))
+                    .collect::<Vec<_>>(),
+            },
+        })
+    }
+
+    /// Prints a summary of the metrics.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Lines read.
+    /// * `bytes` - Bytes read.
+    pub fn print_summary(&self, rows: u64, bytes: u64) {
+        let metrics = self.to_json(rows, bytes);
+        println!("Tiempos por fase:");
+        self.get_phase_times().iter().for_each(|(name, duration)| {
+            println!("  {:<12} {:?}", name, duration);
+        });
+        println!(
+            "Filas: {} ({} filas/s), {:.1} MB ({} MB/s)",
+            rows,
+            metrics["rows_per_second"],
+            bytes as f64 / BYTES_PER_MEGABYTE,
+            metrics["megabytes_per_second"]
+        );
+        if let Some(slowest_file) = self.get_file_timings().first() {
+            println!(
+                "Archivo mas lento: {} ({:?})",
+                slowest_file.path, slowest_file.duration
+            );
+        }
+        let worker_utilization = self.get_worker_utilization();
+        let by_worker: Vec<String> = worker_utilization
+            .iter()
+            .map(|utilization| format!("{:.0}%", utilization * 100.0))
+            .collect();
+        println!(
+            "Utilizacion de los hilos: {:.0}% ({})",
+            get_average(&worker_utilization) * 100.0,
+            by_worker.join(", ")
+        );
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    match denominator > 0.0 {
+        true => numerator / denominator,
+        false => 0.0,
+    }
+}
+
+fn get_average(values: &[f64]) -> f64 {
+    ratio(values.iter().sum(), values.len() as f64)
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+fn round_seconds(duration: Duration) -> f64 {
+    round(duration.as_secs_f64(), SECONDS_DECIMALS)
+}
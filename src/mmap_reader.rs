@@ -0,0 +1,104 @@
+use std::{fs::File, path::PathBuf};
+
+use memmap2::Mmap;
+
+use crate::tokenizer;
+
+/// Bytes of each chunk of a mapped file processed as a task by rayon.
+/// A chunk is cut at its last end of line, so its lines are complete.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How the lines of the files are read, selected with `--io`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IoMode {
+    /// Each line is read into its own `String` with `BufRead::lines`.
+    #[default]
+    Buffered,
+    /// The file is mapped in memory and each line is a slice of the mapping, without copies.
+    Mmap,
+}
+
+impl IoMode {
+    /// Parses the mode given with `--io`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - `buffered` (by default) or `mmap`.
+    ///
+    /// # Returns
+    ///
+    /// The mode, or a message describing why it is not valid.
+    pub fn parse(mode: Option<&str>) -> Result<Self, String> {
+        match mode.unwrap_or("buffered") {
+            "buffered" => Ok(IoMode::Buffered),
+            "mmap" => Ok(IoMode::Mmap),
+            other => Err(format!(
+                "modo de lectura invalido '{}', los modos validos son: buffered y mmap",
+                other
+            )),
+        }
+    }
+}
+
+/// Maps a CSV file in memory.
+///
+/// # Arguments
+///
+/// * `path` - Path of the CSV file.
+///
+/// # Returns
+///
+/// Returns the mapping of the file, or `None` if it could not be opened or mapped.
+pub fn map_file(path: &PathBuf) -> Option<Mmap> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error al abrir el archivo: {}", e);
+            return None;
+        }
+    };
+    // SAFETY: el mapeo es valido mientras el archivo no cambie. Si otro proceso lo modifica
+    // mientras se procesa, las lineas leidas pueden mezclar datos viejos y nuevos, y si lo
+    // trunca, leer las paginas que quedaron fuera del archivo termina el proceso con SIGBUS.
+    // Por eso --io mmap solo se debe usar con archivos de entrada que no se modifiquen durante
+    // la corrida, como los del dataset; con archivos que pueden cambiar, usar --io buffered.
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => Some(mmap),
+        Err(e) => {
+            eprintln!("Error al mapear el archivo {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Splits a block of bytes in chunks of about `chunk_size` bytes, each one cut
+/// after an end of line, so the lines are not split between chunks.
+pub fn split_chunks(data: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while rest.len() > chunk_size {
+        let end = match tokenizer::find(b'\n', &rest[chunk_size..]) {
+            Some(end) => chunk_size + end + 1,
+            None => rest.len(),
+        };
+        let (chunk, next) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = next;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Adds a line that is a slice of a block of bytes to a result, without copying it.
+/// The lines that are not valid UTF-8 are discarded.
+pub(crate) fn fold_line_bytes<T>(acc: T, line: &[u8], fold_line: impl Fn(T, &str) -> T) -> T {
+    match std::str::from_utf8(line) {
+        Ok(line) => fold_line(acc, line),
+        Err(e) => {
+            eprintln!("Error al leer la linea: {}", e);
+            acc
+        }
+    }
+}
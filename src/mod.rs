@@ -1,6 +0,0 @@
-pub mod weapon_stats;
-pub mod argument_parser;
-pub mod top_calculator;
-pub mod writer;
-pub mod csvs_processor;
-pub mod runner;
\ No newline at end of file
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, Sender};
+
+use crate::{
+    backend::{self, ExecutionBackend},
+    cancellation::CancellationToken,
+    metrics::Metrics,
+    mmap_reader,
+    thread_pool::ThreadPoolConfig,
+    tokenizer,
+};
+
+pub const DEFAULT_AGGREGATOR_SHARDS: usize = 2;
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 16;
+/// Bytes read at once by the I/O threads. A block is cut at its last end of line,
+/// so its lines are complete.
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Sizes of the aggregator stage of the pipeline and of the channels between the stages.
+/// The I/O threads are sized with the `ThreadPoolConfig`.
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    aggregator_shards: usize,
+    channel_capacity: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            aggregator_shards: DEFAULT_AGGREGATOR_SHARDS,
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+        }
+    }
+}
+
+impl PipelineConfig {
+    /// Parses the configuration given with `--aggregator-shards` and `--channel-capacity`.
+    /// The missing values take the default ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `aggregator_shards` - Threads that merge the results of the blocks of each file.
+    /// * `channel_capacity` - Messages that each channel holds before the senders wait.
+    ///
+    /// # Returns
+    ///
+    /// The configuration, or a message describing why it is not valid.
+    pub fn parse(
+        aggregator_shards: Option<&str>,
+        channel_capacity: Option<&str>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            aggregator_shards: parse_size(
+                "--aggregator-shards",
+                aggregator_shards,
+                DEFAULT_AGGREGATOR_SHARDS,
+            )?,
+            channel_capacity: parse_size(
+                "--channel-capacity",
+                channel_capacity,
+                DEFAULT_CHANNEL_CAPACITY,
+            )?,
+        })
+    }
+
+    pub fn get_aggregator_shards(&self) -> usize {
+        self.aggregator_shards
+    }
+
+    pub fn get_channel_capacity(&self) -> usize {
+        self.channel_capacity
+    }
+}
+
+fn parse_size(option: &str, value: Option<&str>, default: usize) -> Result<usize, String> {
+    match value.map(|value| value.parse::<usize>()) {
+        None => Ok(default),
+        Some(Ok(size)) if size > 0 => Ok(size),
+        Some(_) => Err(format!(
+            "{} debe ser un entero mayor a 0: '{}'",
+            option,
+            value.unwrap_or_default()
+        )),
+    }
+}
+
+/// Lines of a file read by an I/O thread.
+struct Block {
+    file: usize,
+    data: Vec<u8>,
+}
+
+/// Message received by an aggregator shard.
+enum ShardMessage<T> {
+    /// Result of the lines of a block, sent by a parser.
+    Partial { file: usize, result: T },
+    /// Sent by an I/O thread when it finished reading a file, with the number of blocks sent.
+    FileEnd {
+        file: usize,
+        blocks: usize,
+        start: Instant,
+    },
+}
+
+/// Result of a file that an aggregator shard is merging.
+struct FileAggregate<T> {
+    result: Option<T>,
+    blocks_done: usize,
+    end: Option<(usize, Instant)>,
+}
+
+/// Staged pipeline: I/O threads read the files in large blocks, parser threads map and reduce
+/// the lines of each block, and aggregator shards merge the results of the blocks of each file.
+/// The stages are connected by bounded channels, so reading and parsing overlap and,
+/// when a stage is slower, the previous ones wait instead of filling the memory.
+#[derive(Debug)]
+pub struct PipelineBackend {
+    parsers: usize,
+    config: PipelineConfig,
+    thread_pool_config: ThreadPoolConfig,
+}
+
+impl PipelineBackend {
+    /// Creates a pipeline.
+    ///
+    /// # Arguments
+    ///
+    /// * `parsers` - Number of parser threads.
+    /// * `config` - Sizes of the aggregator stage and of the channels.
+    /// * `thread_pool_config` - Options of the threads, with the number of I/O threads.
+    pub fn new(
+        parsers: usize,
+        config: PipelineConfig,
+        thread_pool_config: ThreadPoolConfig,
+    ) -> Self {
+        Self {
+            parsers: parsers.max(1),
+            config,
+            thread_pool_config,
+        }
+    }
+}
+
+impl ExecutionBackend for PipelineBackend {
+    fn map_reduce_files<T: Send>(
+        &self,
+        paths: &[PathBuf],
+        cancellation: &CancellationToken,
+        metrics: Option<&Metrics>,
+        fold_line: impl Fn(T, &str) -> T + Sync + Send,
+        identity: impl Fn() -> T + Sync + Send,
+        merge: impl Fn(T, T) -> T + Sync + Send,
+        on_file_done: impl Fn(&PathBuf, T, Duration) + Sync + Send,
+    ) {
+        let shards = self.config.aggregator_shards;
+        let next_file = AtomicUsize::new(0);
+        let (block_sender, block_receiver) = bounded::<Block>(self.config.channel_capacity);
+        let (shard_senders, shard_receivers): (Vec<_>, Vec<_>) = (0..shards)
+            .map(|_| bounded::<ShardMessage<T>>(self.config.channel_capacity))
+            .unzip();
+        let (fold_line, identity, merge, on_file_done) =
+            (&fold_line, &identity, &merge, &on_file_done);
+        let thread_pool_config = &self.thread_pool_config;
+        thread::scope(|scope| {
+            (0..thread_pool_config.get_io_threads()).for_each(|io_thread| {
+                let (block_sender, shard_senders) = (block_sender.clone(), shard_senders.clone());
+                let next_file = &next_file;
+                let role = format!("io-{}", io_thread);
+                thread_pool_config.spawn_helper(scope, &role, move || {
+                    loop {
+                        let file = next_file.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(file) else {
+                            break;
+                        };
+                        let start = Instant::now();
+                        // Un archivo sin FileEnd nunca se completa y sus resultados se descartan
+                        if let Some(blocks) = read_blocks(file, path, cancellation, &block_sender) {
+                            let end = ShardMessage::FileEnd {
+                                file,
+                                blocks,
+                                start,
+                            };
+                            let _ = shard_senders[file % shards].send(end);
+                        }
+                    }
+                });
+            });
+            (0..self.parsers).for_each(|worker| {
+                let (block_receiver, shard_senders) =
+                    (block_receiver.clone(), shard_senders.clone());
+                thread_pool_config.spawn_worker(scope, worker, move || {
+                    backend::set_current_worker_index(worker);
+                    for block in block_receiver {
+                        // Los bloques pendientes se consumen igual para que los lectores no esperen
+                        if cancellation.is_cancelled() {
+                            continue;
+                        }
+                        let result = Metrics::measure_busy_time(metrics, || {
+                            parse_block(&block.data, fold_line, identity)
+                        });
+                        let partial = ShardMessage::Partial {
+                            file: block.file,
+                            result,
+                        };
+                        let _ = shard_senders[block.file % shards].send(partial);
+                    }
+                });
+            });
+            // Cada canal se cierra cuando terminan todos los hilos que le envian
+            drop((block_sender, block_receiver, shard_senders));
+            for (shard, shard_receiver) in shard_receivers.into_iter().enumerate() {
+                let role = format!("agg-{}", shard);
+                thread_pool_config.spawn_helper(scope, &role, move || {
+                    let mut files: HashMap<usize, FileAggregate<T>> = HashMap::new();
+                    for message in shard_receiver {
+                        let file = match message {
+                            ShardMessage::Partial { file, result } => {
+                                let aggregate = get_file_aggregate(&mut files, file);
+                                aggregate.result = Some(match aggregate.result.take() {
+                                    Some(acc) => merge(acc, result),
+                                    None => result,
+                                });
+                                aggregate.blocks_done += 1;
+                                file
+                            }
+                            ShardMessage::FileEnd {
+                                file,
+                                blocks,
+                                start,
+                            } => {
+                                get_file_aggregate(&mut files, file).end = Some((blocks, start));
+                                file
+                            }
+                        };
+                        let completed = matches!(
+                            files[&file].end,
+                            Some((blocks, _)) if blocks == files[&file].blocks_done
+                        );
+                        if !completed || cancellation.is_cancelled() {
+                            continue;
+                        }
+                        if let Some(aggregate) = files.remove(&file) {
+                            let start = aggregate.end.map_or_else(Instant::now, |(_, s)| s);
+                            let result = aggregate.result.unwrap_or_else(identity);
+                            on_file_done(&paths[file], result, start.elapsed());
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+fn get_file_aggregate<T>(
+    files: &mut HashMap<usize, FileAggregate<T>>,
+    file: usize,
+) -> &mut FileAggregate<T> {
+    files.entry(file).or_insert_with(|| FileAggregate {
+        result: None,
+        blocks_done: 0,
+        end: None,
+    })
+}
+
+/// Reads a file in blocks of complete lines and sends them to the parsers.
+///
+/// # Arguments
+///
+/// * `file` - Index of the file.
+/// * `path` - Path of the file.
+/// * `cancellation` - Token that stops the reading.
+/// * `block_sender` - Channel to the parsers.
+///
+/// # Returns
+///
+/// The number of blocks sent, or `None` if the file could not be read completely.
+fn read_blocks(
+    file: usize,
+    path: &PathBuf,
+    cancellation: &CancellationToken,
+    block_sender: &Sender<Block>,
+) -> Option<usize> {
+    let mut reader = match File::open(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("Error al abrir el archivo: {}", e);
+            return None;
+        }
+    };
+    let mut blocks = 0;
+    let mut pending_line = Vec::new();
+    loop {
+        if cancellation.is_cancelled() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut pending_line);
+        let read = match (&mut reader).take(BLOCK_SIZE as u64).read_to_end(&mut data) {
+            Ok(read) => read,
+            Err(e) => {
+                eprintln!("Error al leer el archivo {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if read > 0 {
+            // Lo que sigue al ultimo fin de linea pasa al proximo bloque
+            match tokenizer::rfind(b'\n', &data) {
+                Some(end) => pending_line = data.split_off(end + 1),
+                None => {
+                    pending_line = data;
+                    continue;
+                }
+            }
+        }
+        if data.is_empty() {
+            return Some(blocks);
+        }
+        block_sender.send(Block { file, data }).ok()?;
+        blocks += 1;
+    }
+}
+
+/// Folds the lines of a block into a single result, split as `BufRead::lines` does,
+/// without copying them.
+fn parse_block<T>(data: &[u8], fold_line: impl Fn(T, &str) -> T, identity: impl Fn() -> T) -> T {
+    tokenizer::lines(data).fold(identity(), |acc, line| {
+        mmap_reader::fold_line_bytes(acc, line, &fold_line)
+    })
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{memory_usage, weapon_stats::WeaponStats};
+
+const WINNER_BRACKET: &str = "winner";
+const TOP_10_BRACKET: &str = "top_10";
+const REST_BRACKET: &str = "rest";
+
+const WINNER_PLACEMENT: u32 = 1;
+const LAST_TOP_10_PLACEMENT: u32 = 10;
+
+/// Statistics of the kills grouped by the final placement of the killer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlacementStats {
+    weapons_by_bracket: HashMap<String, HashMap<String, i32>>,
+    kills_by_bracket: HashMap<String, WeaponStats>,
+    kills_by_placement: HashMap<u32, u32>,
+}
+
+impl PlacementStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a kill made by a player that finished the match in the given placement.
+    /// The kills with a placement below the winner's are not valid and are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `placement` - Final placement of the killer.
+    /// * `weapon` - Weapon used for the kill.
+    /// * `distance` - Distance between killer and victim, if the positions are known.
+    pub fn add_kill(&mut self, placement: u32, weapon: &str, distance: Option<f64>) {
+        let Some(bracket) = get_bracket(placement) else {
+            return;
+        };
+        // Las claves se copian solo la primera vez que aparecen
+        let weapons = match self.weapons_by_bracket.get_mut(bracket) {
+            Some(weapons) => weapons,
+            None => self
+                .weapons_by_bracket
+                .entry(bracket.to_string())
+                .or_default(),
+        };
+        match weapons.get_mut(weapon) {
+            Some(kills) => *kills += 1,
+            None => {
+                weapons.insert(weapon.to_string(), 1);
+            }
+        }
+
+        let bracket_stats = match self.kills_by_bracket.get_mut(bracket) {
+            Some(bracket_stats) => bracket_stats,
+            None => self
+                .kills_by_bracket
+                .entry(bracket.to_string())
+                .or_insert(WeaponStats::new(0.0, 0, 0)),
+        };
+        bracket_stats.set_total_kills_caused_by_weapon(1);
+        if let Some(distance) = distance {
+            bracket_stats.set_death_distance(distance);
+            bracket_stats.set_number_of_kills_with_valid_distance(1);
+        }
+
+        *self.kills_by_placement.entry(placement).or_default() += 1;
+    }
+
+    /// Adds the statistics of other placement statistics to these ones.
+    pub fn merge(&mut self, other: &PlacementStats) {
+        other
+            .weapons_by_bracket
+            .iter()
+            .for_each(|(bracket, weapons)| {
+                let acc_weapons = self.weapons_by_bracket.entry(bracket.clone()).or_default();
+                weapons.iter().for_each(|(weapon, count)| {
+                    *acc_weapons.entry(weapon.clone()).or_default() += count;
+                });
+            });
+        other
+            .kills_by_bracket
+            .iter()
+            .for_each(|(bracket, bracket_stats)| {
+                self.kills_by_bracket
+                    .entry(bracket.clone())
+                    .or_insert(WeaponStats::new(0.0, 0, 0))
+                    .merge(bracket_stats);
+            });
+        other
+            .kills_by_placement
+            .iter()
+            .for_each(|(placement, kills)| {
+                *self.kills_by_placement.entry(*placement).or_default() += kills;
+            });
+    }
+
+    pub fn get_weapons_by_bracket(&self) -> &HashMap<String, HashMap<String, i32>> {
+        &self.weapons_by_bracket
+    }
+
+    pub fn get_kills_by_bracket(&self) -> &HashMap<String, WeaponStats> {
+        &self.kills_by_bracket
+    }
+
+    pub fn get_kills_by_placement(&self) -> &HashMap<u32, u32> {
+        &self.kills_by_placement
+    }
+
+    /// Returns the estimated bytes of the statistics.
+    pub fn estimate_bytes(&self) -> usize {
+        memory_usage::estimate_map_bytes(&self.weapons_by_bracket, |weapons| {
+            memory_usage::estimate_map_bytes(weapons, |_| 0)
+        }) + memory_usage::estimate_map_bytes(&self.kills_by_bracket, WeaponStats::estimate_bytes)
+            + self.kills_by_placement.len() * memory_usage::estimate_entry_bytes("")
+    }
+}
+
+/// Returns the bracket of a final placement: the winner, the rest of the top 10 or the others,
+/// or `None` if the placement is not valid.
+fn get_bracket(placement: u32) -> Option<&'static str> {
+    match placement {
+        p if p < WINNER_PLACEMENT => None,
+        WINNER_PLACEMENT => Some(WINNER_BRACKET),
+        p if p <= LAST_TOP_10_PLACEMENT => Some(TOP_10_BRACKET),
+        _ => Some(REST_BRACKET),
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{memory_usage, weapon_stats::WeaponStats};
+
+/// Statistics of a single player, accumulated from the lines where the player
+/// is the killer or the victim.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    kill_stats: WeaponStats,
+    deaths: u32,
+    longest_kill: Option<f64>,
+    matches_by_map: HashMap<String, HashSet<String>>,
+}
+
+impl PlayerProfile {
+    pub fn new() -> Self {
+        Self {
+            kill_stats: WeaponStats::new(0.0, 0, 0),
+            deaths: 0,
+            longest_kill: None,
+            matches_by_map: HashMap::new(),
+        }
+    }
+
+    /// Registers a kill made by the player.
+    ///
+    /// # Arguments
+    ///
+    /// * `distance` - Distance between the player and the victim, if the positions are known.
+    pub fn add_kill(&mut self, distance: Option<f64>) {
+        self.kill_stats.set_total_kills_caused_by_weapon(1);
+        if let Some(distance) = distance {
+            self.kill_stats.set_death_distance(distance);
+            self.kill_stats.set_number_of_kills_with_valid_distance(1);
+            self.longest_kill = Some(self.longest_kill.map_or(distance, |l| l.max(distance)));
+        }
+    }
+
+    /// Registers a death of the player.
+    pub fn add_death(&mut self) {
+        self.deaths += 1;
+    }
+
+    /// Registers a match where the player was the killer or the victim.
+    pub fn add_match(&mut self, map: &str, match_id: &str) {
+        let matches = match self.matches_by_map.get_mut(map) {
+            Some(matches) => matches,
+            None => self.matches_by_map.entry(map.to_string()).or_default(),
+        };
+        if !matches.contains(match_id) {
+            matches.insert(match_id.to_string());
+        }
+    }
+
+    /// Adds the statistics of another profile of the same player to this one.
+    pub fn merge(&mut self, other: &PlayerProfile) {
+        self.kill_stats.merge(&other.kill_stats);
+        self.deaths += other.deaths;
+        self.longest_kill = match (self.longest_kill, other.longest_kill) {
+            (Some(longest), Some(other_longest)) => Some(longest.max(other_longest)),
+            (longest, other_longest) => longest.or(other_longest),
+        };
+        other.matches_by_map.iter().for_each(|(map, matches)| {
+            self.matches_by_map
+                .entry(map.clone())
+                .or_default()
+                .extend(matches.iter().cloned());
+        });
+    }
+
+    pub fn get_kill_stats(&self) -> &WeaponStats {
+        &self.kill_stats
+    }
+
+    pub fn get_deaths(&self) -> u32 {
+        self.deaths
+    }
+
+    pub fn get_longest_kill(&self) -> Option<f64> {
+        self.longest_kill
+    }
+
+    pub fn get_matches_by_map(&self) -> &HashMap<String, HashSet<String>> {
+        &self.matches_by_map
+    }
+
+    /// Returns the estimated bytes of the profile.
+    pub fn estimate_bytes(&self) -> usize {
+        self.kill_stats.estimate_bytes()
+            + memory_usage::estimate_map_bytes(&self.matches_by_map, |matches| {
+                matches
+                    .iter()
+                    .map(|match_id| memory_usage::estimate_entry_bytes(match_id))
+                    .sum()
+            })
+    }
+}
+
+impl Default for PlayerProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::backend;
+
+const TEXT_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+const JSON_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const BYTES_PER_MEGABYTE: f64 = 1024.0 * 1024.0;
+
+/// How the progress is shown on stderr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressMode {
+    Disabled,
+    /// A line that is rewritten in place, for a terminal.
+    Text,
+    /// A JSON object per line, for other programs.
+    Json,
+}
+
+impl ProgressMode {
+    /// Parses the mode given with `--progress`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - `auto`, `text`, `json` or `none`. Without a mode, or with `auto`,
+    ///   the progress is shown as text only when stderr is a terminal.
+    ///
+    /// # Returns
+    ///
+    /// The mode, or a message describing why it is not valid.
+    pub fn parse(mode: Option<&str>) -> Result<Self, String> {
+        match mode.unwrap_or("auto") {
+            "auto" if std::io::stderr().is_terminal() => Ok(ProgressMode::Text),
+            "auto" | "none" => Ok(ProgressMode::Disabled),
+            "text" => Ok(ProgressMode::Text),
+            "json" => Ok(ProgressMode::Json),
+            other => Err(format!(
+                "modo de progreso invalido '{}', los modos validos son: auto, text, json y none",
+                other
+            )),
+        }
+    }
+}
+
+/// Lines and bytes read by a worker. Each counter takes a whole cache line,
+/// so the workers do not slow each other down when counting their lines.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct WorkerRows {
+    rows: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Counters of the work done, updated by the workers and read by the `ProgressReporter`.
+/// The lines are only counted once the counters are enabled, when the progress is shown
+/// or the metrics need them.
+#[derive(Debug, Default)]
+pub struct Progress {
+    enabled: AtomicBool,
+    files_total: AtomicUsize,
+    files_done: AtomicUsize,
+    bytes_total: AtomicU64,
+    workers: Vec<WorkerRows>,
+    /// Lines read outside of the workers of the backend.
+    other_rows: WorkerRows,
+}
+
+impl Progress {
+    /// Creates disabled counters for a run with the given number of workers.
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            workers: (0..num_threads.max(1))
+                .map(|_| WorkerRows::default())
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Starts counting the lines read.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Adds files to the work to do. The files that cannot be read are counted with 0 bytes.
+    pub fn add_pending_files(&self, paths: &[PathBuf]) {
+        let bytes: u64 = paths
+            .iter()
+            .filter_map(|path| path.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        self.files_total.fetch_add(paths.len(), Ordering::Relaxed);
+        self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Registers a line read, with the bytes of the line, in the counter of the current worker.
+    /// Nothing is done while the counters are disabled.
+    pub fn add_row(&self, bytes: usize) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let worker = backend::current_worker_index()
+            .and_then(|i| self.workers.get(i))
+            .unwrap_or(&self.other_rows);
+        worker.rows.fetch_add(1, Ordering::Relaxed);
+        worker.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Registers a file whose lines were all processed.
+    pub fn add_completed_file(&self) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get_rows(&self) -> u64 {
+        self.sum_workers(|worker| &worker.rows)
+    }
+
+    pub fn get_bytes_read(&self) -> u64 {
+        self.sum_workers(|worker| &worker.bytes)
+    }
+
+    /// Adds up a counter of all the workers.
+    fn sum_workers(&self, counter: impl Fn(&WorkerRows) -> &AtomicU64) -> u64 {
+        self.workers
+            .iter()
+            .chain([&self.other_rows])
+            .map(|worker| counter(worker).load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns the progress as a JSON object, with the rate and the estimated time left
+    /// computed from the time elapsed since the processing started.
+    fn get_snapshot(&self, elapsed: Duration) -> serde_json::Value {
+        let files_total = self.files_total.load(Ordering::Relaxed);
+        let bytes_total = self.bytes_total.load(Ordering::Relaxed);
+        let bytes_read = self.get_bytes_read();
+        let elapsed_seconds = elapsed.as_secs_f64();
+        let rows_per_second = match elapsed_seconds > 0.0 {
+            true => self.get_rows() as f64 / elapsed_seconds,
+            false => 0.0,
+        };
+        // El tiempo restante se estima con los bytes, porque las lineas no se conocen de antemano
+        let eta_seconds = (bytes_read > 0).then(|| {
+            elapsed_seconds * bytes_total.saturating_sub(bytes_read) as f64 / bytes_read as f64
+        });
+        json!({
+            "files_done": self.files_done.load(Ordering::Relaxed),
+            "files_total": files_total,
+            "bytes_read": bytes_read,
+            "bytes_total": bytes_total,
+            "rows": self.get_rows(),
+            "rows_per_second": rows_per_second.round() as u64,
+            "elapsed_seconds": (elapsed_seconds * 10.0).round() / 10.0,
+            "eta_seconds": eta_seconds.map(|eta_seconds| eta_seconds.round() as u64),
+        })
+    }
+}
+
+/// Thread that shows the progress on stderr periodically until it is finished.
+pub struct ProgressReporter {
+    stop_sender: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl ProgressReporter {
+    /// Starts showing the progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - How the progress is shown.
+    /// * `progress` - Counters updated by the workers.
+    ///
+    /// # Returns
+    ///
+    /// The reporter, or `None` if the progress is disabled.
+    pub fn start(mode: ProgressMode, progress: Arc<Progress>) -> Option<Self> {
+        let interval = match mode {
+            ProgressMode::Disabled => return None,
+            ProgressMode::Text => TEXT_REFRESH_INTERVAL,
+            ProgressMode::Json => JSON_REFRESH_INTERVAL,
+        };
+        progress.enable();
+        let (stop_sender, stop_receiver) = mpsc::channel();
+        let start = Instant::now();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                print_progress(mode, &progress.get_snapshot(start.elapsed()));
+            }
+            print_progress(mode, &progress.get_snapshot(start.elapsed()));
+            if mode == ProgressMode::Text {
+                eprintln!();
+            }
+        });
+        Some(Self {
+            stop_sender,
+            handle,
+        })
+    }
+
+    /// Shows the final progress and stops the reporter.
+    pub fn finish(self) {
+        let _ = self.stop_sender.send(());
+        let _ = self.handle.join();
+    }
+}
+
+fn print_progress(mode: ProgressMode, snapshot: &serde_json::Value) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = match mode {
+        ProgressMode::Json => writeln!(stderr, "{}", snapshot),
+        _ => write!(stderr, "\r{}", format_progress(snapshot)),
+    };
+    let _ = stderr.flush();
+}
+
+/// Formats the progress as a line for a terminal, like
+/// `Archivos 3/10 | 120.5/400.0 MB | 250000 filas/s | ETA 12s`.
+fn format_progress(snapshot: &serde_json::Value) -> String {
+    let megabytes = |key: &str| snapshot[key].as_u64().unwrap_or(0) as f64 / BYTES_PER_MEGABYTE;
+    let eta = match snapshot["eta_seconds"].as_u64() {
+        Some(eta_seconds) => format!("{}s", eta_seconds),
+        None => "?".to_string(),
+    };
+    // Los espacios finales borran lo que quede de una linea anterior mas larga
+    format!(
+        "Archivos {}/{} | {:.1}/{:.1} MB | {} filas/s | ETA {}    ",
+        snapshot["files_done"],
+        snapshot["files_total"],
+        megabytes("bytes_read"),
+        megabytes("bytes_total"),
+        snapshot["rows_per_second"],
+        eta
+    )
+}
@@ -0,0 +1,344 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::json;
+
+use crate::{columns::Column, memory_usage};
+
+/// Aggregated values of each group, indexed by the values of the group-by columns.
+pub type QueryGroups = HashMap<Vec<String>, Vec<AggregateState>>;
+
+#[derive(Debug, Clone, Copy)]
+enum AggregateFunction {
+    Count,
+    Sum(Column),
+    Avg(Column),
+    Min(Column),
+    Max(Column),
+    Distinct(Column),
+}
+
+impl AggregateFunction {
+    /// Returns the column read by the function, `count` reads none.
+    fn get_column(&self) -> Option<Column> {
+        match self {
+            AggregateFunction::Count => None,
+            AggregateFunction::Sum(column)
+            | AggregateFunction::Avg(column)
+            | AggregateFunction::Min(column)
+            | AggregateFunction::Max(column)
+            | AggregateFunction::Distinct(column) => Some(*column),
+        }
+    }
+}
+
+/// Partial value of an aggregate function over the lines of a group.
+#[derive(Debug, Clone)]
+pub enum AggregateState {
+    Count(u64),
+    Sum(f64),
+    Avg { sum: f64, count: u64 },
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Distinct(HashSet<String>),
+}
+
+impl AggregateState {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => AggregateState::Count(0),
+            AggregateFunction::Sum(_) => AggregateState::Sum(0.0),
+            AggregateFunction::Avg(_) => AggregateState::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min(_) => AggregateState::Min(None),
+            AggregateFunction::Max(_) => AggregateState::Max(None),
+            AggregateFunction::Distinct(_) => AggregateState::Distinct(HashSet::new()),
+        }
+    }
+
+    /// Adds a line to the aggregate. The lines without a value for the column are skipped.
+    fn add_line(&mut self, function: AggregateFunction, fields: &[&str], distance: Option<f64>) {
+        match (self, function) {
+            (AggregateState::Count(count), _) => *count += 1,
+            (AggregateState::Sum(sum), AggregateFunction::Sum(column)) => {
+                *sum += column.get_number(fields, distance).unwrap_or(0.0);
+            }
+            (AggregateState::Avg { sum, count }, AggregateFunction::Avg(column)) => {
+                if let Some(value) = column.get_number(fields, distance) {
+                    *sum += value;
+                    *count += 1;
+                }
+            }
+            (AggregateState::Min(min), AggregateFunction::Min(column)) => {
+                if let Some(value) = column.get_number(fields, distance) {
+                    *min = Some(min.map_or(value, |m| m.min(value)));
+                }
+            }
+            (AggregateState::Max(max), AggregateFunction::Max(column)) => {
+                if let Some(value) = column.get_number(fields, distance) {
+                    *max = Some(max.map_or(value, |m| m.max(value)));
+                }
+            }
+            (AggregateState::Distinct(values), AggregateFunction::Distinct(column)) => {
+                if let Some(value) = column.get_text(fields).filter(|v| !v.is_empty()) {
+                    if !values.contains(value) {
+                        values.insert(value.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the partial value of the same aggregate over other lines.
+    fn merge(&mut self, other: AggregateState) {
+        match (self, other) {
+            (AggregateState::Count(count), AggregateState::Count(other_count)) => {
+                *count += other_count
+            }
+            (AggregateState::Sum(sum), AggregateState::Sum(other_sum)) => *sum += other_sum,
+            (
+                AggregateState::Avg { sum, count },
+                AggregateState::Avg {
+                    sum: other_sum,
+                    count: other_count,
+                },
+            ) => {
+                *sum += other_sum;
+                *count += other_count;
+            }
+            (AggregateState::Min(min), AggregateState::Min(other_min)) => {
+                *min = combine_optional(*min, other_min, f64::min);
+            }
+            (AggregateState::Max(max), AggregateState::Max(other_max)) => {
+                *max = combine_optional(*max, other_max, f64::max);
+            }
+            (AggregateState::Distinct(values), AggregateState::Distinct(other_values)) => {
+                values.extend(other_values);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the final value of the aggregate in a JSON format.
+    pub fn get_value(&self) -> serde_json::Value {
+        match self {
+            AggregateState::Count(count) => json!(count),
+            AggregateState::Sum(sum) => json!(round(*sum)),
+            AggregateState::Avg { sum, count } => match count {
+                0 => serde_json::Value::Null,
+                _ => json!(round(sum / *count as f64)),
+            },
+            AggregateState::Min(value) | AggregateState::Max(value) => json!(value.map(round)),
+            AggregateState::Distinct(values) => json!(values.len()),
+        }
+    }
+}
+
+/// Query that groups the lines by some columns and calculates aggregate functions over each group,
+/// like `count`, `avg(distance)` or `distinct(killer_name)` grouped by `map,weapon`.
+#[derive(Debug)]
+pub struct Query {
+    group_by: Vec<(String, Column)>,
+    aggregates: Vec<(String, AggregateFunction)>,
+}
+
+impl Query {
+    /// Compiles a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `group_by` - Columns to group by, separated by commas. Without columns there is one group.
+    /// * `aggregates` - Aggregate functions separated by commas: `count`, `sum(column)`,
+    ///   `avg(column)`, `min(column)`, `max(column)` and `distinct(column)`.
+    ///
+    /// # Returns
+    ///
+    /// The compiled query, or a message describing why it is not valid.
+    pub fn parse(group_by: Option<&str>, aggregates: &str) -> Result<Self, String> {
+        let group_by = split_list(group_by.unwrap_or(""))
+            .map(|name| match Column::find(name) {
+                Some(Column::Distance) => {
+                    Err("no se puede agrupar por distance, es un valor continuo".to_string())
+                }
+                Some(column) => Ok((name.to_string(), column)),
+                None => Err(unknown_column_message(name)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let aggregates = split_list(aggregates)
+            .map(|aggregate| parse_aggregate(aggregate).map(|f| (aggregate.to_string(), f)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if aggregates.is_empty() {
+            return Err("se necesita al menos una funcion de agregacion".to_string());
+        }
+        Ok(Self {
+            group_by,
+            aggregates,
+        })
+    }
+
+    /// Adds the fields of a CSV line to the aggregates of its group.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - Groups accumulated by the task.
+    /// * `fields` - Vector of fields from a CSV line.
+    /// * `distance` - Distance of the kill, if known.
+    ///
+    /// # Returns
+    ///
+    /// The groups with the line.
+    pub fn fold_fields(
+        &self,
+        mut groups: QueryGroups,
+        fields: &[&str],
+        distance: Option<f64>,
+    ) -> QueryGroups {
+        let key: Vec<String> = self
+            .group_by
+            .iter()
+            .map(|(_, column)| column.get_text(fields).unwrap_or("").to_string())
+            .collect();
+        let states = groups.entry(key).or_insert_with(|| {
+            self.aggregates
+                .iter()
+                .map(|(_, function)| AggregateState::new(*function))
+                .collect()
+        });
+        states
+            .iter_mut()
+            .zip(&self.aggregates)
+            .for_each(|(state, (_, function))| state.add_line(*function, fields, distance));
+        groups
+    }
+
+    /// Returns the fields of the CSV line read by the query, which can be repeated.
+    pub fn get_fields(&self) -> Vec<usize> {
+        let aggregate_columns = self
+            .aggregates
+            .iter()
+            .filter_map(|(_, function)| function.get_column());
+        self.group_by
+            .iter()
+            .map(|(_, column)| *column)
+            .chain(aggregate_columns)
+            .flat_map(|column| column.get_fields())
+            .collect()
+    }
+
+    /// Returns the names of the group-by columns.
+    pub fn get_group_by_names(&self) -> Vec<&str> {
+        self.group_by
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Returns the aggregate functions as they were written.
+    pub fn get_aggregate_names(&self) -> Vec<&str> {
+        self.aggregates
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+/// Adds the groups of other lines to the accumulated groups.
+pub fn merge_groups(mut acc: QueryGroups, groups: QueryGroups) -> QueryGroups {
+    groups
+        .into_iter()
+        .for_each(|(key, states)| match acc.get_mut(&key) {
+            Some(acc_states) => acc_states
+                .iter_mut()
+                .zip(states)
+                .for_each(|(acc_state, state)| acc_state.merge(state)),
+            None => {
+                acc.insert(key, states);
+            }
+        });
+    acc
+}
+
+/// Returns the estimated bytes of the groups: their keys and the states of their aggregates.
+pub fn estimate_groups_bytes(groups: &QueryGroups) -> usize {
+    groups
+        .iter()
+        .map(|(key, states)| {
+            key.iter()
+                .map(|field| memory_usage::estimate_entry_bytes(field))
+                .sum::<usize>()
+                + states
+                    .iter()
+                    .map(|state| {
+                        std::mem::size_of::<AggregateState>()
+                            + match state {
+                                AggregateState::Distinct(values) => values
+                                    .iter()
+                                    .map(|value| memory_usage::estimate_entry_bytes(value))
+                                    .sum(),
+                                _ => 0,
+                            }
+                    })
+                    .sum::<usize>()
+        })
+        .sum()
+}
+
+fn parse_aggregate(aggregate: &str) -> Result<AggregateFunction, String> {
+    if aggregate == "count" {
+        return Ok(AggregateFunction::Count);
+    }
+    let (name, argument) = aggregate
+        .strip_suffix(')')
+        .and_then(|a| a.split_once('('))
+        .ok_or_else(|| format!("funcion de agregacion invalida '{}'", aggregate))?;
+    let column =
+        Column::find(argument.trim()).ok_or_else(|| unknown_column_message(argument.trim()))?;
+    let function = match name.trim() {
+        "sum" => AggregateFunction::Sum(column),
+        "avg" => AggregateFunction::Avg(column),
+        "min" => AggregateFunction::Min(column),
+        "max" => AggregateFunction::Max(column),
+        "distinct" | "count_distinct" if column == Column::Distance => {
+            return Err("distinct no se puede usar con distance".to_string())
+        }
+        "distinct" | "count_distinct" => return Ok(AggregateFunction::Distinct(column)),
+        _ => {
+            return Err(format!(
+                "funcion de agregacion desconocida '{}', las funciones validas son: \
+                 count, sum, avg, min, max y distinct",
+                name
+            ))
+        }
+    };
+    if !column.is_numeric() {
+        return Err(format!(
+            "'{}' necesita una columna numerica, '{}' es de texto",
+            name, argument
+        ));
+    }
+    Ok(function)
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+}
+
+fn unknown_column_message(name: &str) -> String {
+    format!(
+        "columna desconocida '{}', las columnas validas son: {}",
+        name,
+        Column::get_names()
+    )
+}
+
+fn combine_optional(a: Option<f64>, b: Option<f64>, pick: fn(f64, f64) -> f64) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(pick(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
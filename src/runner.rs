@@ -1,7 +1,15 @@
-use rayon::ThreadPoolBuilder;
 use std::time::Instant;
 
-use crate::{argument_parser::ArgumentParser, csvs_processor::Processor};
+use crate::{
+    argument_parser::{ArgumentParser, Command},
+    benchmark::{self, Benchmark, DEFAULT_REPETITIONS, DEFAULT_WARMUP, TOTAL_PHASE},
+    cancellation::CANCELLED_EXIT_CODE,
+    csvs_processor::Processor,
+    progress::{ProgressMode, ProgressReporter},
+    thread_pool::ThreadPoolConfig,
+    writer::Writer,
+};
+#[derive(Default)]
 pub struct Runner {}
 
 impl Runner {
@@ -10,35 +18,210 @@ impl Runner {
     }
 
     /// Runs the application.
+    /// If the run is cancelled with SIGINT or SIGTERM, the process exits with `CANCELLED_EXIT_CODE`.
     pub fn run(&self) {
         let parser = ArgumentParser::new();
-        let pool = build_thread_pool(parser.get_num_threads());
-        let processor = Processor::new(Instant::now());
-        pool.install(|| {
-            processor.process_and_write_results(&parser);
+        if let Command::Bench = parser.get_command() {
+            run_benchmark(&parser);
+            return;
+        }
+        let pool = build_thread_pool(&get_thread_pool_config(&parser), parser.get_num_threads());
+        let processor = Processor::new(Instant::now(), &parser);
+        if let Err(e) = processor.get_cancellation_token().cancel_on_signals() {
+            eprintln!("No se pueden manejar las señales de cancelacion: {}", e);
+        }
+        let progress_reporter =
+            ProgressReporter::start(get_progress_mode(&parser), processor.get_progress());
+        pool.install(|| match parser.get_command() {
+            Command::Report | Command::Bench => processor.process_and_write_results(&parser),
+            Command::Player(player_name) => {
+                processor.process_and_write_player_profile(&parser, player_name)
+            }
+            Command::Weapon(weapon_name) => {
+                processor.process_and_write_weapon_detail(&parser, weapon_name)
+            }
+            Command::Query => processor.process_and_write_query_results(&parser),
         });
+        if let Some(progress_reporter) = progress_reporter {
+            progress_reporter.finish();
+        }
+        if processor.get_cancellation_token().is_cancelled() {
+            std::process::exit(CANCELLED_EXIT_CODE);
+        }
     }
 }
 
-/// Builds a thread pool with the given number of threads.
-/// It can be seen that as we increase the number of threads 
-/// there is an improvement in the processing time, up to a certain thread limit. 
-/// After a certain number of threads the program gets worse due to 
+/// Runs the report with each number of threads of the benchmark, and writes the statistics
+/// of the time of each phase, with the speedup and the efficiency relative to one thread.
+/// The reports of the runs are written to a temporary file that is removed at the end.
+/// If the options of the benchmark are not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+fn run_benchmark(parser: &ArgumentParser) {
+    let benchmark = get_benchmark(parser);
+    let report_file = std::env::temp_dir().join(format!(
+        "tp_fork_join_bench_report_{}.json",
+        std::process::id()
+    ));
+    let report_writer = Writer::new(&report_file.to_string_lossy());
+    let thread_pool_config = get_thread_pool_config(parser);
+    let results = benchmark.run(|threads| {
+        let pool = build_thread_pool(&thread_pool_config, threads);
+        let start = Instant::now();
+        let processor = Processor::new_with_threads(start, parser, threads);
+        let written = pool.install(|| processor.process_and_write_report(parser, &report_writer));
+        if let Err(e) = written {
+            eprintln!("Error al escribir el reporte: {}", e);
+        }
+        let mut phase_times = processor.get_metrics().get_phase_times();
+        phase_times.push((TOTAL_PHASE.to_string(), start.elapsed()));
+        phase_times
+    });
+    let _ = std::fs::remove_file(&report_file);
+
+    results.iter().for_each(|result| {
+        let total = result.get_phase(TOTAL_PHASE);
+        let speedup = benchmark::get_speedup(&results, result, TOTAL_PHASE);
+        println!(
+            "Hilos: {:>3} | total: {:.3}s ± {:.3}s (min {:.3}s) | speedup: {:.2} | eficiencia: {:.2}",
+            result.get_threads(),
+            total.map_or(0.0, |stats| stats.get_mean()),
+            total.map_or(0.0, |stats| stats.get_stddev()),
+            total.map_or(0.0, |stats| stats.get_min()),
+            speedup.map_or(0.0, |(speedup, _)| speedup),
+            speedup.map_or(0.0, |(_, efficiency)| efficiency)
+        );
+    });
+    match benchmark::write_results(parser.get_output_file_name(), &results) {
+        Ok(_) => println!("Archivo escrito correctamente"),
+        Err(e) => eprintln!("Error al escribir el archivo: {}", e),
+    }
+}
+
+/// Builds the benchmark from the arguments. Without `--threads`, it measures the powers of 2
+/// up to the number of threads of the arguments, and that number.
+/// If an option is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// The benchmark.
+fn get_benchmark(parser: &ArgumentParser) -> Benchmark {
+    if parser.get_state_file().is_some() || parser.get_checkpoint_file().is_some() {
+        eprintln!("Las opciones --state y --checkpoint no se pueden usar con bench");
+        std::process::exit(1);
+    }
+    let thread_counts = match parser.get_thread_counts() {
+        Some(thread_counts) => thread_counts
+            .split(',')
+            .map(|threads| parse_count("--threads", threads.trim()))
+            .collect(),
+        None => {
+            let max_threads = parser.get_num_threads().max(1);
+            let mut thread_counts: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2))
+                .take_while(|&n| n < max_threads)
+                .collect();
+            thread_counts.push(max_threads);
+            thread_counts
+        }
+    };
+    let warmup = parser
+        .get_warmup()
+        .map_or(DEFAULT_WARMUP, |warmup| parse_count("--warmup", warmup));
+    let repetitions = parser
+        .get_repetitions()
+        .map_or(DEFAULT_REPETITIONS, |repetitions| {
+            parse_count("--repetitions", repetitions)
+        });
+    if repetitions == 0 {
+        eprintln!("--repetitions tiene que ser al menos 1");
+        std::process::exit(1);
+    }
+    Benchmark::new(thread_counts, warmup, repetitions)
+}
+
+/// Parses a count given in an option.
+/// If it is not a non-negative integer, the function exits the program
+fn parse_count(option: &str, value: &str) -> usize {
+    match value.parse() {
+        Ok(count) => count,
+        Err(_) => {
+            eprintln!("{} tiene que ser un entero no negativo: {}", option, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns how the progress is shown, given with `--progress`.
+/// If the mode is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// The progress mode.
+fn get_progress_mode(parser: &ArgumentParser) -> ProgressMode {
+    match ProgressMode::parse(parser.get_progress_mode()) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("Error en --progress: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns the options of the threads given in the arguments.
+/// If an option is not valid, the function exits the program
+///
+/// # Arguments
+///
+/// * `parser` - Argument parser with command line arguments.
+///
+/// # Returns
+///
+/// The options of the threads.
+fn get_thread_pool_config(parser: &ArgumentParser) -> ThreadPoolConfig {
+    match ThreadPoolConfig::parse(
+        parser.get_thread_name(),
+        parser.get_stack_size(),
+        parser.is_pin_threads_enabled(),
+        parser.get_io_threads(),
+    ) {
+        Ok(thread_pool_config) => thread_pool_config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds a thread pool with the given number of threads and the options of the threads.
+/// It can be seen that as we increase the number of threads
+/// there is an improvement in the processing time, up to a certain thread limit.
+/// After a certain number of threads the program gets worse due to
 /// the fight for the CPU.
-/// 
+///
 /// # Arguments
 ///
+/// * `thread_pool_config` - The names, stack size and pinning of the threads.
 /// * `num_threads` - The number of threads to use in the thread pool.
 ///
 /// # Returns
 ///
 /// A thread pool with the given number of threads.
 /// If the thread pool cannot be built, the function panics.
-fn build_thread_pool(num_threads: usize) -> rayon::ThreadPool {
-    ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
+fn build_thread_pool(
+    thread_pool_config: &ThreadPoolConfig,
+    num_threads: usize,
+) -> rayon::ThreadPool {
+    thread_pool_config
+        .build_pool(num_threads)
         .expect("Failed to build thread pool")
 }
-
-
@@ -0,0 +1,73 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    sync::{Mutex, PoisonError},
+};
+
+/// Shards created for each thread, so two threads rarely update the same shard at once.
+const SHARDS_PER_THREAD: usize = 4;
+
+/// Shard of the map. Each shard takes a whole cache line, so the locks of
+/// neighbouring shards do not slow each other down.
+#[derive(Debug)]
+#[repr(align(64))]
+struct Shard<V>(Mutex<HashMap<String, V>>);
+
+/// Hash map that several threads update at once. The keys are split among shards by their hash,
+/// and each shard has its own lock, so the threads only wait for each other
+/// when they update keys of the same shard.
+#[derive(Debug)]
+pub struct ShardedMap<V> {
+    shards: Vec<Shard<V>>,
+    hasher: RandomState,
+}
+
+impl<V: Default> ShardedMap<V> {
+    /// Creates an empty map with enough shards for the given number of threads.
+    pub fn new(num_threads: usize) -> Self {
+        Self::with_shards(num_threads.max(1) * SHARDS_PER_THREAD)
+    }
+
+    /// Creates an empty map with the given number of shards, at least 1.
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| Shard(Mutex::new(HashMap::new())))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn get_number_of_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Updates the value of a key, inserting the default value first if the key is not in the map.
+    /// Only the shard of the key is locked while updating.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to update.
+    /// * `update` - Function that updates the value.
+    pub fn update(&self, key: &str, update: impl FnOnce(&mut V)) {
+        let shard = &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()];
+        // Si otro hilo entro en panico con el lock tomado, el shard sigue siendo valido
+        let mut entries = shard.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get_mut(key) {
+            Some(value) => update(value),
+            None => update(entries.entry(key.to_string()).or_default()),
+        }
+    }
+
+    /// Takes the entries of all the shards, leaving the map empty.
+    /// Each key is in a single shard, so the shards are joined without merging values.
+    pub fn take_entries(&self) -> HashMap<String, V> {
+        let mut entries = HashMap::new();
+        self.shards.iter().for_each(|shard| {
+            entries.extend(std::mem::take(
+                &mut *shard.0.lock().unwrap_or_else(PoisonError::into_inner),
+            ))
+        });
+        entries
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory_usage;
+
+/// Counter of a player monitored by the summary.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MonitoredPlayer {
+    kills: u64,
+    error: u64,
+    weapons: HashMap<String, i32>,
+}
+
+impl MonitoredPlayer {
+    /// Returns the estimated kills of the player. The real kills are between
+    /// `get_kills() - get_error()` and `get_kills()`.
+    pub fn get_kills(&self) -> u64 {
+        self.kills
+    }
+
+    /// Returns the maximum overestimation of the kills of the player.
+    pub fn get_error(&self) -> u64 {
+        self.error
+    }
+
+    /// Returns the kills with each weapon since the player is monitored.
+    pub fn get_weapons(&self) -> &HashMap<String, i32> {
+        &self.weapons
+    }
+}
+
+/// Kills and name of a player, that sort the players of the summary. It is owned by the summary
+/// or borrowed from a kill, so the players are looked up without copying their names.
+trait RankedKills {
+    fn get_kills(&self) -> u64;
+
+    fn get_player(&self) -> &str;
+}
+
+// Con el mismo numero de kills va primero el ultimo en orden alfabetico,
+// asi se reemplaza primero, igual que en el desempate del top
+impl Ord for dyn RankedKills + '_ {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_kills()
+            .cmp(&other.get_kills())
+            .then_with(|| other.get_player().cmp(self.get_player()))
+    }
+}
+
+impl PartialOrd for dyn RankedKills + '_ {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for dyn RankedKills + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for dyn RankedKills + '_ {}
+
+impl RankedKills for (u64, &str) {
+    fn get_kills(&self) -> u64 {
+        self.0
+    }
+
+    fn get_player(&self) -> &str {
+        self.1
+    }
+}
+
+/// Kills and name of a monitored player, in the order of the summary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RankedPlayer(u64, String);
+
+impl RankedKills for RankedPlayer {
+    fn get_kills(&self) -> u64 {
+        self.0
+    }
+
+    fn get_player(&self) -> &str {
+        &self.1
+    }
+}
+
+impl<'a> Borrow<dyn RankedKills + 'a> for RankedPlayer {
+    fn borrow(&self) -> &(dyn RankedKills + 'a) {
+        self
+    }
+}
+
+impl Ord for RankedPlayer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Borrow::<dyn RankedKills>::borrow(self).cmp(other.borrow())
+    }
+}
+
+impl PartialOrd for RankedPlayer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedPlayer {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedPlayer {}
+
+/// Space-Saving summary of the players with more kills. It monitors at most `capacity`
+/// players, so the memory does not grow with the number of players. When a new player
+/// arrives and the summary is full, the player with fewer kills is replaced and the new one
+/// inherits its kills as error, so the kills are overestimated by at most the minimum kills
+/// of the summary, that is never greater than `total kills / capacity`.
+///
+/// Two summaries with the same capacity can be merged, so each thread can keep its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceSaving {
+    capacity: usize,
+    total_kills: u64,
+    players: HashMap<String, MonitoredPlayer>,
+    players_by_kills: BTreeSet<RankedPlayer>,
+}
+
+impl SpaceSaving {
+    /// Creates an empty summary that monitors at most `capacity` players.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            total_kills: 0,
+            players: HashMap::new(),
+            players_by_kills: BTreeSet::new(),
+        }
+    }
+
+    /// Registers a kill made by the player with the weapon.
+    pub fn add_kill(&mut self, player: &str, weapon: &str) {
+        let monitored_player = MonitoredPlayer {
+            kills: 1,
+            ..MonitoredPlayer::default()
+        };
+        self.add_player(player, monitored_player, self.get_min_kills());
+        // El arma se copia solo la primera vez que el jugador la usa
+        if let Some(acc_player) = self.players.get_mut(player) {
+            match acc_player.weapons.get_mut(weapon) {
+                Some(kills) => *kills += 1,
+                None => {
+                    acc_player.weapons.insert(weapon.to_string(), 1);
+                }
+            }
+        }
+        self.total_kills += 1;
+        self.evict_players_with_fewer_kills();
+    }
+
+    /// Adds the kills of other summary to this one. The players missing in one of the summaries
+    /// may have up to the minimum kills of that summary, so those kills are added as error.
+    pub fn merge(&mut self, other: &SpaceSaving) {
+        let min_kills = self.get_min_kills();
+        let other_min_kills = other.get_min_kills();
+        if other_min_kills > 0 {
+            let missing_players: Vec<String> = self
+                .players
+                .keys()
+                .filter(|player| !other.players.contains_key(*player))
+                .cloned()
+                .collect();
+            missing_players.iter().for_each(|player| {
+                self.add_player(player, MonitoredPlayer::default(), other_min_kills)
+            });
+        }
+        other.players.iter().for_each(|(player, monitored_player)| {
+            self.add_player(player, monitored_player.clone(), min_kills)
+        });
+        self.total_kills += other.total_kills;
+        self.evict_players_with_fewer_kills();
+    }
+
+    /// Adds the kills of a player to its counter. If the player was not monitored,
+    /// the unseen kills are added to the kills and to the error.
+    fn add_player(&mut self, player: &str, monitored_player: MonitoredPlayer, unseen_kills: u64) {
+        // El nombre se copia solo cuando el jugador es nuevo, si ya estaba se reusa el del orden
+        let (acc_player, mut ranked_player) = match self.players.get_mut(player) {
+            Some(acc_player) => {
+                let ranked_player = self
+                    .players_by_kills
+                    .take(&(acc_player.kills, player) as &dyn RankedKills)
+                    .unwrap_or_else(|| RankedPlayer(acc_player.kills, player.to_string()));
+                (acc_player, ranked_player)
+            }
+            None => {
+                let ranked_player = RankedPlayer(unseen_kills, player.to_string());
+                let acc_player =
+                    self.players
+                        .entry(ranked_player.1.clone())
+                        .or_insert(MonitoredPlayer {
+                            kills: unseen_kills,
+                            error: unseen_kills,
+                            weapons: HashMap::new(),
+                        });
+                (acc_player, ranked_player)
+            }
+        };
+        acc_player.kills += monitored_player.kills;
+        acc_player.error += monitored_player.error;
+        monitored_player
+            .weapons
+            .into_iter()
+            .for_each(|(weapon, kills)| *acc_player.weapons.entry(weapon).or_default() += kills);
+        ranked_player.0 = acc_player.kills;
+        self.players_by_kills.insert(ranked_player);
+    }
+
+    fn evict_players_with_fewer_kills(&mut self) {
+        while self.players.len() > self.capacity {
+            if let Some(RankedPlayer(_, player)) = self.players_by_kills.pop_first() {
+                self.players.remove(&player);
+            }
+        }
+    }
+
+    /// Returns the kills of the monitored player with fewer kills if the summary is full,
+    /// or 0 if it is not. It bounds the kills of the players that are not monitored
+    /// and the overestimation of the kills of the monitored ones.
+    pub fn get_min_kills(&self) -> u64 {
+        if self.players.len() < self.capacity {
+            return 0;
+        }
+        self.players_by_kills
+            .first()
+            .map_or(0, |RankedPlayer(kills, _)| *kills)
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_total_kills(&self) -> u64 {
+        self.total_kills
+    }
+
+    pub fn get_players(&self) -> &HashMap<String, MonitoredPlayer> {
+        &self.players
+    }
+
+    /// Returns the estimated bytes of the summary.
+    pub fn estimate_bytes(&self) -> usize {
+        memory_usage::estimate_map_bytes(&self.players, |player| {
+            memory_usage::estimate_map_bytes(&player.weapons, |_| 0)
+        }) + self
+            .players_by_kills
+            .iter()
+            .map(|RankedPlayer(_, player)| memory_usage::estimate_entry_bytes(player))
+            .sum::<usize>()
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fs::{self, File},
+    hash::BuildHasher,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Partitions of the spilled entries. Each partition is merged alone,
+/// so only the entries of one partition are in memory at once.
+pub const DEFAULT_SPILL_PARTITIONS: usize = 64;
+
+/// Number of the next spill directory of the process, so each store has its own.
+static NEXT_SPILL_DIRECTORY: AtomicUsize = AtomicUsize::new(0);
+
+/// Parses a memory size given with `--memory-limit` or `--stack-size`,
+/// in bytes or with a `K`, `M` or `G` suffix.
+///
+/// # Arguments
+///
+/// * `size` - The size, like `512M` or `2G`.
+///
+/// # Returns
+///
+/// The size in bytes, or a message describing why it is not valid.
+pub fn parse_memory_size(size: &str) -> Result<usize, String> {
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&size[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&size[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    match number.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number.saturating_mul(multiplier)),
+        _ => Err(format!(
+            "tamaño invalido '{}', tiene que ser un entero mayor a 0 \
+             seguido opcionalmente de K, M o G",
+            size
+        )),
+    }
+}
+
+/// Temporary files where the entries of a map are spilled when the map grows past a budget.
+/// The entries are split into partitions by the hash of their key, so all the values of a key
+/// are in the same partition. The files are removed when the store is dropped.
+#[derive(Debug)]
+pub struct SpillStore {
+    directory: PathBuf,
+    partitions: Vec<Mutex<Option<BufWriter<File>>>>,
+    hasher: RandomState,
+    threshold_bytes: usize,
+    spilled_bytes: AtomicU64,
+    /// Estimated bytes of the maps that are in memory, adding up all the accumulators.
+    resident_bytes: AtomicUsize,
+    peak_resident_bytes: AtomicUsize,
+}
+
+impl SpillStore {
+    /// Creates an empty store. The directory is created with the first spill.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_bytes` - Estimated size past which a map is spilled.
+    /// * `partitions` - Number of partitions, at least 1.
+    pub fn new(threshold_bytes: usize, partitions: usize) -> Self {
+        let directory = std::env::temp_dir().join(format!(
+            "tp_fork_join_spill_{}_{}",
+            std::process::id(),
+            NEXT_SPILL_DIRECTORY.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            directory,
+            partitions: (0..partitions.max(1)).map(|_| Mutex::new(None)).collect(),
+            hasher: RandomState::new(),
+            threshold_bytes,
+            spilled_bytes: AtomicU64::new(0),
+            resident_bytes: AtomicUsize::new(0),
+            peak_resident_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns true if a map of the given estimated size has to be spilled.
+    pub fn is_over_threshold(&self, bytes: usize) -> bool {
+        bytes > self.threshold_bytes
+    }
+
+    pub fn has_spilled(&self) -> bool {
+        self.get_spilled_bytes() > 0
+    }
+
+    /// Returns the bytes written to the partitions.
+    pub fn get_spilled_bytes(&self) -> u64 {
+        self.spilled_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Registers entries added to a map in memory, keeping the peak of the bytes in memory.
+    pub fn add_resident_bytes(&self, bytes: usize) {
+        let resident_bytes = self.resident_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_resident_bytes
+            .fetch_max(resident_bytes, Ordering::Relaxed);
+    }
+
+    /// Registers a map that left the memory, because it was spilled or merged into another.
+    pub fn remove_resident_bytes(&self, bytes: usize) {
+        let _ = self.resident_bytes.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |resident_bytes| Some(resident_bytes.saturating_sub(bytes)),
+        );
+    }
+
+    /// Returns the peak of the estimated bytes of the maps in memory.
+    pub fn get_peak_resident_bytes(&self) -> usize {
+        self.peak_resident_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn get_number_of_partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    fn get_partition_path(&self, partition: usize) -> PathBuf {
+        self.directory
+            .join(format!("partition_{}.jsonl", partition))
+    }
+
+    /// Appends the entries of a map to their partitions, a JSON line per entry.
+    /// A key can be spilled several times, the values are merged with `merge_partitions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The entries to spill.
+    ///
+    /// # Returns
+    ///
+    /// A Result with the result of the operation
+    pub fn spill<V: Serialize>(&self, entries: HashMap<String, V>) -> io::Result<()> {
+        let mut partitioned: Vec<Vec<(String, V)>> =
+            (0..self.partitions.len()).map(|_| Vec::new()).collect();
+        entries.into_iter().for_each(|(key, value)| {
+            let partition = self.hasher.hash_one(&key) as usize % self.partitions.len();
+            partitioned[partition].push((key, value));
+        });
+        fs::create_dir_all(&self.directory)?;
+        for (partition, entries) in partitioned.into_iter().enumerate() {
+            if entries.is_empty() {
+                continue;
+            }
+            let mut writer = self.partitions[partition]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if writer.is_none() {
+                *writer = Some(BufWriter::new(File::create(
+                    self.get_partition_path(partition),
+                )?));
+            }
+            if let Some(writer) = writer.as_mut() {
+                for entry in entries {
+                    let line = serde_json::to_string(&entry)?;
+                    writeln!(writer, "{}", line)?;
+                    self.spilled_bytes
+                        .fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the spilled entries one partition at a time. Only the entries kept from each
+    /// partition stay in memory while the next partitions are merged.
+    ///
+    /// # Arguments
+    ///
+    /// * `merge` - Function that adds a spilled value to the value of the same key.
+    /// * `keep` - Function that returns the entries of a merged partition that are needed.
+    ///
+    /// # Returns
+    ///
+    /// The entries kept from all the partitions.
+    pub fn merge_partitions<V: Default + DeserializeOwned>(
+        &self,
+        merge: impl Fn(&mut V, V),
+        keep: impl Fn(HashMap<String, V>) -> HashMap<String, V>,
+    ) -> io::Result<HashMap<String, V>> {
+        let mut kept = HashMap::new();
+        for (partition, writer) in self.partitions.iter().enumerate() {
+            let Some(mut writer) = writer.lock().unwrap_or_else(PoisonError::into_inner).take()
+            else {
+                continue;
+            };
+            writer.flush()?;
+            let mut entries: HashMap<String, V> = HashMap::new();
+            let reader = BufReader::new(File::open(self.get_partition_path(partition))?);
+            for line in reader.lines() {
+                let (key, value): (String, V) = serde_json::from_str(&line?)?;
+                merge(entries.entry(key).or_default(), value);
+            }
+            kept.extend(keep(entries));
+            fs::remove_file(self.get_partition_path(partition))?;
+        }
+        Ok(kept)
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
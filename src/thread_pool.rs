@@ -0,0 +1,187 @@
+use std::thread::{self, Scope, ScopedJoinHandle};
+
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+use crate::spill;
+
+pub const DEFAULT_THREAD_NAME: &str = "tp-worker";
+pub const DEFAULT_IO_THREADS: usize = 1;
+
+/// Options of the threads that process the files: the thread pool of rayon,
+/// the workers of the other backends and the I/O threads of the pipeline.
+#[derive(Debug, Clone)]
+pub struct ThreadPoolConfig {
+    thread_name: String,
+    stack_size: Option<usize>,
+    pin_threads: bool,
+    io_threads: usize,
+}
+
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        Self {
+            thread_name: DEFAULT_THREAD_NAME.to_string(),
+            stack_size: None,
+            pin_threads: false,
+            io_threads: DEFAULT_IO_THREADS,
+        }
+    }
+}
+
+impl ThreadPoolConfig {
+    /// Parses the options given with `--thread-name`, `--stack-size`, `--pin-threads`
+    /// and `--io-threads`. The missing values take the default ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_name` - Prefix of the names of the threads.
+    /// * `stack_size` - Stack size of each thread, in bytes or with a `K`, `M` or `G` suffix.
+    /// * `pin_threads` - Whether each worker is pinned to a core.
+    /// * `io_threads` - Threads that read the files, apart from the workers.
+    ///
+    /// # Returns
+    ///
+    /// The options, or a message describing why they are not valid.
+    pub fn parse(
+        thread_name: Option<&str>,
+        stack_size: Option<&str>,
+        pin_threads: bool,
+        io_threads: Option<&str>,
+    ) -> Result<Self, String> {
+        let thread_name = thread_name.unwrap_or(DEFAULT_THREAD_NAME);
+        if thread_name.is_empty() || thread_name.contains('\0') {
+            return Err(format!("--thread-name invalido: '{}'", thread_name));
+        }
+        let stack_size = stack_size
+            .map(spill::parse_memory_size)
+            .transpose()
+            .map_err(|e| format!("Error en --stack-size: {}", e))?;
+        let io_threads = match io_threads.map(|value| (value, value.parse::<usize>())) {
+            None => DEFAULT_IO_THREADS,
+            Some((_, Ok(io_threads))) if io_threads > 0 => io_threads,
+            Some((value, _)) => {
+                return Err(format!(
+                    "--io-threads debe ser un entero mayor a 0: '{}'",
+                    value
+                ))
+            }
+        };
+        Ok(Self {
+            thread_name: thread_name.to_string(),
+            stack_size,
+            pin_threads,
+            io_threads,
+        })
+    }
+
+    pub fn get_thread_name(&self) -> &str {
+        &self.thread_name
+    }
+
+    pub fn get_stack_size(&self) -> Option<usize> {
+        self.stack_size
+    }
+
+    pub fn is_pin_threads_enabled(&self) -> bool {
+        self.pin_threads
+    }
+
+    pub fn get_io_threads(&self) -> usize {
+        self.io_threads
+    }
+
+    /// Builds a thread pool of rayon whose workers are named `<thread-name>-<index>`,
+    /// with the stack size and the pinning of the options.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_threads` - The number of threads of the pool.
+    ///
+    /// # Returns
+    ///
+    /// The thread pool, or the error of rayon if it could not be built.
+    pub fn build_pool(&self, num_threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+        let thread_name = self.thread_name.clone();
+        let pin_threads = self.pin_threads;
+        let mut builder = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(move |index| format!("{}-{}", thread_name, index))
+            .start_handler(move |index| {
+                if pin_threads {
+                    pin_current_thread(index);
+                }
+            });
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        builder.build()
+    }
+
+    /// Spawns a worker in a scope, named `<thread-name>-<index>` and pinned
+    /// to a core if it was requested.
+    /// If the thread cannot be spawned, the function panics, as `Scope::spawn` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - Scope of the thread.
+    /// * `index` - Index of the worker.
+    /// * `f` - Function run by the thread.
+    pub fn spawn_worker<'scope, 'env, T: Send + 'scope>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        index: usize,
+        f: impl FnOnce() -> T + Send + 'scope,
+    ) -> ScopedJoinHandle<'scope, T> {
+        let pin_threads = self.pin_threads;
+        self.spawn(scope, index.to_string(), move || {
+            if pin_threads {
+                pin_current_thread(index);
+            }
+            f()
+        })
+    }
+
+    /// Spawns a thread that helps the workers in a scope, like an I/O thread,
+    /// named `<thread-name>-<role>`. It is not pinned, so it runs on any free core.
+    /// If the thread cannot be spawned, the function panics, as `Scope::spawn` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - Scope of the thread.
+    /// * `role` - What the thread does, like `io-0`.
+    /// * `f` - Function run by the thread.
+    pub fn spawn_helper<'scope, 'env, T: Send + 'scope>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        role: &str,
+        f: impl FnOnce() -> T + Send + 'scope,
+    ) -> ScopedJoinHandle<'scope, T> {
+        self.spawn(scope, role.to_string(), f)
+    }
+
+    fn spawn<'scope, 'env, T: Send + 'scope>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        suffix: String,
+        f: impl FnOnce() -> T + Send + 'scope,
+    ) -> ScopedJoinHandle<'scope, T> {
+        let mut builder = thread::Builder::new().name(format!("{}-{}", self.thread_name, suffix));
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        builder
+            .spawn_scoped(scope, f)
+            .expect("Failed to spawn thread")
+    }
+}
+
+/// Pins the current thread to a core, the cores are assigned to the workers in turns.
+/// If the cores cannot be listed or the thread cannot be pinned, it keeps running unpinned.
+fn pin_current_thread(index: usize) {
+    let Some(core_ids) = core_affinity::get_core_ids().filter(|ids| !ids.is_empty()) else {
+        return;
+    };
+    if !core_affinity::set_for_current(core_ids[index % core_ids.len()]) {
+        eprintln!("No se pudo fijar el hilo {} a un nucleo", index);
+    }
+}
@@ -0,0 +1,133 @@
+//! Splitting of blocks of bytes in lines and of lines in fields.
+//!
+//! With the `simd` feature, enabled by default, the ends of line and the commas are searched
+//! with `memchr`, that compares many bytes at once with the SIMD instructions of the processor
+//! and falls back to comparing a word at a time when they are not available. Without it,
+//! the scalar tokenizer compares a byte at a time. Both split exactly as `BufRead::lines`
+//! and `str::split(',')` do.
+
+#[cfg(not(feature = "simd"))]
+pub use scalar::{find, lines, rfind, split_fields};
+#[cfg(feature = "simd")]
+pub use simd::{find, lines, rfind, split_fields};
+
+/// Searches a byte in a block of bytes, returning its position.
+type FindFn = fn(u8, &[u8]) -> Option<usize>;
+
+/// Iterator over the lines of a block of bytes, that searches the ends of line
+/// with the given function.
+struct Lines<'a> {
+    rest: &'a [u8],
+    find: FindFn,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        // Despues del ultimo fin de linea no queda otra linea, aunque sea vacia
+        if self.rest.is_empty() {
+            return None;
+        }
+        match (self.find)(b'\n', self.rest) {
+            Some(end) => {
+                let line = &self.rest[..end];
+                self.rest = &self.rest[end + 1..];
+                // Como BufRead::lines, el \r solo se quita si esta antes del fin de linea
+                Some(line.strip_suffix(b"\r").unwrap_or(line))
+            }
+            None => Some(std::mem::take(&mut self.rest)),
+        }
+    }
+}
+
+/// Returns the lines of a block of bytes, split as `BufRead::lines` does: without the `\n`
+/// nor the `\r` before it, and without an empty last line. A last line without `\n` is kept
+/// as it is, even if it ends in `\r`.
+fn lines_with(data: &[u8], find: FindFn) -> impl Iterator<Item = &[u8]> {
+    Lines { rest: data, find }
+}
+
+/// Splits a line in fields separated by commas, as `str::split(',')` does,
+/// keeping at most as many fields as fit in `fields`.
+///
+/// # Returns
+///
+/// The number of fields written in `fields`.
+fn split_fields_with<'a>(line: &'a str, fields: &mut [&'a str], find: FindFn) -> usize {
+    let mut rest = Some(line);
+    let mut number_of_fields = 0;
+    for slot in fields.iter_mut() {
+        let Some(field) = rest else {
+            break;
+        };
+        // La coma es ASCII, asi que cortar en ella deja texto UTF-8 valido
+        *slot = match find(b',', field.as_bytes()) {
+            Some(end) => {
+                rest = Some(&field[end + 1..]);
+                &field[..end]
+            }
+            None => {
+                rest = None;
+                field
+            }
+        };
+        number_of_fields += 1;
+    }
+    number_of_fields
+}
+
+/// Tokenizer that compares a byte at a time. It is the reference of the SIMD one.
+pub mod scalar {
+    /// Returns the position of the first occurrence of a byte.
+    pub fn find(byte: u8, data: &[u8]) -> Option<usize> {
+        data.iter().position(|&b| b == byte)
+    }
+
+    /// Returns the position of the last occurrence of a byte.
+    pub fn rfind(byte: u8, data: &[u8]) -> Option<usize> {
+        data.iter().rposition(|&b| b == byte)
+    }
+
+    /// Returns the lines of a block of bytes, split as `BufRead::lines` does.
+    pub fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+        super::lines_with(data, find)
+    }
+
+    /// Splits a line in fields separated by commas, keeping at most `fields.len()` fields.
+    ///
+    /// # Returns
+    ///
+    /// The number of fields written in `fields`.
+    pub fn split_fields<'a>(line: &'a str, fields: &mut [&'a str]) -> usize {
+        super::split_fields_with(line, fields, find)
+    }
+}
+
+/// Tokenizer that searches the bytes with `memchr`.
+#[cfg(feature = "simd")]
+pub mod simd {
+    /// Returns the position of the first occurrence of a byte.
+    pub fn find(byte: u8, data: &[u8]) -> Option<usize> {
+        memchr::memchr(byte, data)
+    }
+
+    /// Returns the position of the last occurrence of a byte.
+    pub fn rfind(byte: u8, data: &[u8]) -> Option<usize> {
+        memchr::memrchr(byte, data)
+    }
+
+    /// Returns the lines of a block of bytes, split as `BufRead::lines` does.
+    pub fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+        super::lines_with(data, find)
+    }
+
+    /// Splits a line in fields separated by commas, keeping at most `fields.len()` fields.
+    ///
+    /// # Returns
+    ///
+    /// The number of fields written in `fields`.
+    pub fn split_fields<'a>(line: &'a str, fields: &mut [&'a str]) -> usize {
+        super::split_fields_with(line, fields, find)
+    }
+}
@@ -1,17 +1,34 @@
 use std::{cmp::Ordering, collections::HashMap};
 
-use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator};
 use serde_json::json;
 
 use rayon::iter::IndexedParallelIterator;
 use rayon::iter::ParallelIterator;
+use rayon::slice::ParallelSliceMut;
 
-use crate::weapon_stats::WeaponStats;
+use crate::{
+    distinct_stats::{DistinctCounts, DistinctMode, DistinctStats},
+    kill_graph::KillGraph,
+    placement_stats::PlacementStats,
+    player_profile::PlayerProfile,
+    query::{Query, QueryGroups},
+    space_saving::{MonitoredPlayer, SpaceSaving},
+    weapon_categories::WeaponCategories,
+    weapon_stats::{WeaponStats, DISTANCE_BUCKET_LIMITS, TIME_BUCKET_SECONDS},
+};
 
-const TOP_PLAYERS_OF_THE_GAME: usize = 10;
+pub const TOP_PLAYERS_OF_THE_GAME: usize = 10;
 const TOP_WEAPONS_FOR_PLAYER: usize = 3;
 const TOP_WEAPONS_IN_THE_GAME: usize = 10;
+const TOP_RIVALRIES_OF_THE_GAME: usize = 10;
+const DISTINCT_ERROR_BOUND_IN_STANDARD_ERRORS: f64 = 3.0;
+pub const TOP_USERS_OF_THE_WEAPON: usize = 10;
 
+/// Name, total kills and kills with each weapon of a player.
+pub type PlayerKills<'a> = (&'a String, i32, &'a HashMap<String, i32>);
+
+#[derive(Default)]
 pub struct TopCalculator {}
 
 impl TopCalculator {
@@ -37,6 +54,313 @@ impl TopCalculator {
         top_weapons
     }
 
+    /// Groups the weapon statistics by weapon category.
+    ///
+    /// # Arguments
+    ///
+    /// * `weapons` - HashMap with the statistics of each weapon.
+    /// * `categories` - Mapping between weapons and categories.
+    ///
+    /// # Returns
+    ///
+    /// A HashMap <Category, kill share and average distance in a JSON format>
+    pub fn calculate_top_categories(
+        &self,
+        weapons: &HashMap<String, WeaponStats>,
+        categories: &WeaponCategories,
+    ) -> HashMap<String, serde_json::Value> {
+        let mut categories_stats: HashMap<String, WeaponStats> = HashMap::new();
+        weapons.iter().for_each(|(weapon, weapon_stats)| {
+            categories_stats
+                .entry(categories.get_category(weapon).to_string())
+                .or_insert(WeaponStats::new(0.0, 0, 0))
+                .merge(weapon_stats);
+        });
+        let total_deaths_caused_by_weapons = calculate_total_deaths(weapons);
+
+        categories_stats
+            .par_iter()
+            .map(|(category, category_stats)| {
+                calculate_total_stats_for(category, category_stats, total_deaths_caused_by_weapons)
+            })
+            .collect()
+    }
+
+    /// Calculates the statistics of the kills grouped by the placement of the killer.
+    ///
+    /// # Arguments
+    ///
+    /// * `placement_stats` - Kills aggregated by placement.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the kills, average distance and top weapons of each placement bracket,
+    /// and the number of kills made by the players of each placement.
+    pub fn calculate_placement_stats(&self, placement_stats: &PlacementStats) -> serde_json::Value {
+        let empty_weapons = HashMap::new();
+        let brackets: HashMap<String, serde_json::Value> = placement_stats
+            .get_kills_by_bracket()
+            .par_iter()
+            .map(|(bracket, bracket_stats)| {
+                let weapons = placement_stats
+                    .get_weapons_by_bracket()
+                    .get(bracket)
+                    .unwrap_or(&empty_weapons);
+                let total_kills = bracket_stats.get_total_kills_caused_by_weapon();
+                let top_weapons = get_top_weapons_for_player(
+                    get_sorted_vector_by_kills(weapons),
+                    total_kills as i32,
+                    TOP_WEAPONS_IN_THE_GAME,
+                );
+                (
+                    bracket.clone(),
+                    json!({
+                        "kills": total_kills,
+                        "average_distance": calculate_average_distance(bracket_stats),
+                        "weapons_percentage": top_weapons,
+                    }),
+                )
+            })
+            .collect();
+
+        json!({
+            "brackets": brackets,
+            "kills_by_placement": placement_stats.get_kills_by_placement(),
+        })
+    }
+
+    /// Calculates the pairs of players that killed each other the most times.
+    /// Only the pairs with kills in both directions are rivalries.
+    ///
+    /// # Arguments
+    ///
+    /// * `kill_graph` - Graph with the number of kills between players.
+    ///
+    /// # Returns
+    ///
+    /// A vector with the top rivalries in a JSON format, sorted by the total number of kills.
+    pub fn calculate_top_rivalries(&self, kill_graph: &KillGraph) -> Vec<serde_json::Value> {
+        let mut rivalries: Vec<(&String, &String, u32, u32)> = kill_graph
+            .get_edges()
+            .par_iter()
+            .filter_map(|((killer, victim), kills)| {
+                // Cada rivalidad tiene dos aristas, se conserva la que sale del nombre menor
+                if killer >= victim {
+                    return None;
+                }
+                let revenge_kills = kill_graph.get_kills(victim, killer);
+                (revenge_kills > 0).then_some((killer, victim, *kills, revenge_kills))
+            })
+            .collect();
+        rivalries.sort_unstable_by(|a, b| {
+            let total_cmp = (b.2 + b.3).cmp(&(a.2 + a.3)); // Ordenar por total en orden descendente
+            if total_cmp == Ordering::Equal {
+                (a.0, a.1).cmp(&(b.0, b.1)) // Si hay empate, ordenar alfabéticamente por los nombres
+            } else {
+                total_cmp
+            }
+        });
+
+        rivalries
+            .iter()
+            .take(TOP_RIVALRIES_OF_THE_GAME)
+            .map(|(player, rival, player_kills, rival_kills)| {
+                let kills = HashMap::from([(*player, player_kills), (*rival, rival_kills)]);
+                json!({
+                    "players": [player, rival],
+                    "kills": kills,
+                    "total_kills": player_kills + rival_kills,
+                })
+            })
+            .collect()
+    }
+
+    /// Calculates the profile of a player.
+    ///
+    /// # Arguments
+    ///
+    /// * `player_name` - Name of the player.
+    /// * `player_profile` - Kills, deaths and matches of the player.
+    /// * `player_weapons` - Kills of the player with each weapon, if the player killed anyone.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the kills, deaths, K/D ratio, weapons, distances and maps of the player.
+    pub fn calculate_player_profile(
+        &self,
+        player_name: &str,
+        player_profile: &PlayerProfile,
+        player_weapons: Option<&HashMap<String, i32>>,
+    ) -> serde_json::Value {
+        let kill_stats = player_profile.get_kill_stats();
+        let kills = kill_stats.get_total_kills_caused_by_weapon();
+        let deaths = player_profile.get_deaths();
+        let weapons_percentage = player_weapons
+            .map(|weapons| {
+                get_top_weapons_for_player(
+                    get_sorted_vector_by_kills(weapons),
+                    kills as i32,
+                    weapons.len(),
+                )
+            })
+            .unwrap_or_default();
+        let matches_by_map: HashMap<&String, usize> = player_profile
+            .get_matches_by_map()
+            .iter()
+            .map(|(map, matches)| (map, matches.len()))
+            .collect();
+
+        json!({
+            "player": player_name,
+            "kills": kills,
+            "deaths": deaths,
+            "kd_ratio": calculate_kill_death_ratio(kills, deaths),
+            "weapons_percentage": weapons_percentage,
+            "average_distance": calculate_average_distance(kill_stats),
+            "longest_kill": player_profile
+                .get_longest_kill()
+                .map(|distance| (distance * 100.0).round() / 100.0),
+            "maps_played": matches_by_map,
+        })
+    }
+
+    /// Calculates the detail of a weapon.
+    ///
+    /// # Arguments
+    ///
+    /// * `weapon_name` - Name of the weapon.
+    /// * `weapons` - HashMap with the statistics of each weapon, with the detail of the requested one.
+    /// * `player_kills` - HashMap with the kills of each player with the requested weapon.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the kills, share of deaths, distance distribution, top users,
+    /// kills by map and time profile of the weapon.
+    pub fn calculate_weapon_detail(
+        &self,
+        weapon_name: &str,
+        weapons: &HashMap<String, WeaponStats>,
+        player_kills: &HashMap<String, HashMap<String, i32>>,
+    ) -> serde_json::Value {
+        let empty_weapon_stats = WeaponStats::new(0.0, 0, 0);
+        let weapon_stats = weapons.get(weapon_name).unwrap_or(&empty_weapon_stats);
+        let total_kills = weapon_stats.get_total_kills_caused_by_weapon();
+
+        let users: HashMap<String, i32> = player_kills
+            .par_iter()
+            .filter_map(|(player, weapons)| {
+                weapons
+                    .get(weapon_name)
+                    .map(|kills| (player.clone(), *kills))
+            })
+            .collect();
+        let top_users: HashMap<&String, &i32> = get_sorted_vector_by_kills(&users)
+            .into_iter()
+            .take(TOP_USERS_OF_THE_WEAPON)
+            .collect();
+
+        let kills_by_map: HashMap<&String, serde_json::Value> = weapon_stats
+            .get_kills_by_map()
+            .iter()
+            .map(|(map, kills)| {
+                (
+                    map,
+                    json!({
+                        "kills": kills,
+                        "percentage": calculate_percentage(*kills, total_kills),
+                    }),
+                )
+            })
+            .collect();
+
+        json!({
+            "weapon": weapon_name,
+            "kills": total_kills,
+            "deaths_percentage": calculate_percentage(total_kills, calculate_total_deaths(weapons)),
+            "average_distance": calculate_average_distance(weapon_stats),
+            "distance_distribution": calculate_distance_distribution(weapon_stats),
+            "top_users": top_users,
+            "kills_by_map": kills_by_map,
+            "time_profile": calculate_time_profile(weapon_stats),
+        })
+    }
+
+    /// Calculates the distinct players, matches and killer-victim pairs, globally and by map.
+    /// The approximate counts come with their relative standard error and an error bound
+    /// of three standard errors, that holds for about 99.7% of the counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `distinct_stats` - Distinct counts of each map.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the mode, the errors, the global counts and the counts of each map.
+    pub fn calculate_distinct_counts(&self, distinct_stats: &DistinctStats) -> serde_json::Value {
+        let mode = distinct_stats.get_mode();
+        let relative_standard_error = mode.get_relative_standard_error();
+        let by_map: HashMap<&String, serde_json::Value> = distinct_stats
+            .get_counts_by_map()
+            .par_iter()
+            .map(|(map, counts)| (map, get_distinct_counts_json(counts)))
+            .collect();
+
+        json!({
+            "mode": match mode {
+                DistinctMode::Exact => "exact",
+                DistinctMode::Approximate(_) => "approx",
+            },
+            "precision": match mode {
+                DistinctMode::Exact => None,
+                DistinctMode::Approximate(precision) => Some(precision),
+            },
+            "relative_standard_error": relative_standard_error,
+            "error_bound": DISTINCT_ERROR_BOUND_IN_STANDARD_ERRORS * relative_standard_error,
+            "global": get_distinct_counts_json(&distinct_stats.get_global_counts()),
+            "by_map": by_map,
+        })
+    }
+
+    /// Calculates the rows of the result of a query, sorted by the values of the group-by columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query.
+    /// * `groups` - Aggregated values of each group.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the group-by columns, the aggregates and one row for each group.
+    pub fn calculate_query_rows(&self, query: &Query, groups: QueryGroups) -> serde_json::Value {
+        let mut groups_vec: Vec<_> = groups.into_iter().collect();
+        groups_vec.par_sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let group_by_names = query.get_group_by_names();
+        let aggregate_names = query.get_aggregate_names();
+        let rows: Vec<serde_json::Value> = groups_vec
+            .par_iter()
+            .map(|(key, states)| {
+                let mut row = serde_json::Map::new();
+                group_by_names.iter().zip(key).for_each(|(name, value)| {
+                    row.insert(name.to_string(), json!(value));
+                });
+                aggregate_names
+                    .iter()
+                    .zip(states)
+                    .for_each(|(name, state)| {
+                        row.insert(name.to_string(), state.get_value());
+                    });
+                serde_json::Value::Object(row)
+            })
+            .collect();
+
+        json!({
+            "group_by": group_by_names,
+            "aggregates": aggregate_names,
+            "rows": rows,
+        })
+    }
+
     pub fn calculate_and_sort_results(
         &self,
         weapons: HashMap<String, WeaponStats>,
@@ -51,17 +375,90 @@ impl TopCalculator {
         (top_killers, top_weapons)
     }
 
-    pub fn calculate_top_killers(
+    /// Calculates the top killers from the summary of the players with more kills.
+    /// The weapons percentages are calculated over the kills since each player is monitored.
+    ///
+    /// # Arguments
+    ///
+    /// * `top_killers_summary` - Summary of the players with more kills.
+    ///
+    /// # Returns
+    ///
+    /// A HashMap <Player, estimated kills and top weapons in a JSON format>
+    pub fn calculate_approximate_top_killers(
         &self,
-        player_kills: HashMap<String, HashMap<String, i32>>,
+        top_killers_summary: &SpaceSaving,
     ) -> HashMap<String, serde_json::Value> {
-        let players_weapons_vec = sort_players_by_kills(&player_kills);
-        let top_10_players: Vec<_> = players_weapons_vec
+        select_top_monitored_players(top_killers_summary, TOP_PLAYERS_OF_THE_GAME)
+            .par_iter()
+            .map(|(player, monitored_player)| {
+                let weapons = monitored_player.get_weapons();
+                let monitored_kills = weapons.values().sum();
+                (
+                    (*player).clone(),
+                    json!({
+                        "deaths": monitored_player.get_kills(),
+                        "weapons_percentage":
+                            calculate_top_weapons_for_player(weapons, monitored_kills),
+                    }),
+                )
+            })
+            .collect()
+    }
+
+    /// Calculates the error bounds of the approximate top killers.
+    /// The kills of each player are overestimated by at most its `max_overestimation`, and
+    /// the players that are not monitored have at most `error_bound` kills. A player is
+    /// `guaranteed` to be in the real top when its kills without the error are still
+    /// greater or equal than the kills of the first player left out of the top.
+    ///
+    /// # Arguments
+    ///
+    /// * `top_killers_summary` - Summary of the players with more kills.
+    ///
+    /// # Returns
+    ///
+    /// A JSON with the capacity, the total kills, the global bound and the bounds of each player.
+    pub fn calculate_top_killers_error(
+        &self,
+        top_killers_summary: &SpaceSaving,
+    ) -> serde_json::Value {
+        // Se selecciona tambien el primer jugador que queda fuera del top
+        let players_vec =
+            select_top_monitored_players(top_killers_summary, TOP_PLAYERS_OF_THE_GAME + 1);
+        let first_left_out_kills = players_vec.get(TOP_PLAYERS_OF_THE_GAME).map_or(
+            top_killers_summary.get_min_kills(),
+            |(_, monitored_player)| monitored_player.get_kills(),
+        );
+        let players: HashMap<&String, serde_json::Value> = players_vec
             .par_iter()
             .take(TOP_PLAYERS_OF_THE_GAME)
-            .cloned()
+            .map(|(player, monitored_player)| {
+                let min_kills = monitored_player.get_kills() - monitored_player.get_error();
+                (
+                    *player,
+                    json!({
+                        "max_overestimation": monitored_player.get_error(),
+                        "guaranteed": min_kills >= first_left_out_kills,
+                    }),
+                )
+            })
             .collect();
 
+        json!({
+            "capacity": top_killers_summary.get_capacity(),
+            "total_kills": top_killers_summary.get_total_kills(),
+            "error_bound": top_killers_summary.get_min_kills(),
+            "players": players,
+        })
+    }
+
+    pub fn calculate_top_killers(
+        &self,
+        player_kills: HashMap<String, HashMap<String, i32>>,
+    ) -> HashMap<String, serde_json::Value> {
+        let top_10_players = select_top_players_by_kills(&player_kills, TOP_PLAYERS_OF_THE_GAME);
+
         get_best_weapons_for(top_10_players)
     }
 }
@@ -85,17 +482,14 @@ fn calculate_total_stats_for(
     )
 }
 
-fn get_best_weapons_for(
-    top_10_players: Vec<(&String, &HashMap<String, i32>)>,
-) -> HashMap<String, serde_json::Value> {
+fn get_best_weapons_for(top_10_players: Vec<PlayerKills>) -> HashMap<String, serde_json::Value> {
     let top_killers = top_10_players
         .par_iter()
-        .map(|(player, weapons)| {
-            let total_deaths_caused_by_player = weapons.values().sum();
+        .map(|&(player, total_deaths_caused_by_player, weapons)| {
             let top_3_weapons =
                 calculate_top_weapons_for_player(weapons, total_deaths_caused_by_player);
             (
-                (*player).clone(),
+                player.clone(),
                 json!({
                     "deaths": total_deaths_caused_by_player,
                     "weapons_percentage": top_3_weapons
@@ -106,22 +500,84 @@ fn get_best_weapons_for(
     top_killers
 }
 
-fn sort_players_by_kills(
+/// Selects the `n` players with more kills, sorted by kills in descending order and
+/// alphabetically by name when there is a tie. The kills of each player are added once,
+/// and only the selected players are sorted.
+///
+/// # Arguments
+///
+/// * `player_kills` - HashMap with the kills of each player with each weapon.
+/// * `n` - Number of players to select.
+///
+/// # Returns
+///
+/// A vector with the name, the total kills and the weapons of each selected player.
+pub fn select_top_players_by_kills(
     player_kills: &HashMap<String, HashMap<String, i32>>,
-) -> Vec<(&String, &HashMap<String, i32>)> {
-    let mut players_weapons_vec: Vec<(&String, &HashMap<String, i32>)> =
-        player_kills.par_iter().collect();
-    players_weapons_vec.sort_unstable_by(|a, b| {
-        let sum_a = a.1.values().sum::<i32>();
-        let sum_b = b.1.values().sum::<i32>();
-        let sum_cmp = sum_b.cmp(&sum_a); // Ordenar por suma en orden descendente
+    n: usize,
+) -> Vec<PlayerKills<'_>> {
+    let players_vec = player_kills
+        .par_iter()
+        .map(|(player, weapons)| (player, weapons.values().sum(), weapons))
+        .collect();
+    select_top(players_vec, n, |a: &PlayerKills, b: &PlayerKills| {
+        let sum_cmp = b.1.cmp(&a.1); // Ordenar por suma en orden descendente
         if sum_cmp == Ordering::Equal {
             a.0.cmp(b.0) // Si hay empate, ordenar alfabéticamente por el nombre del jugador
         } else {
             sum_cmp
         }
-    });
-    players_weapons_vec
+    })
+}
+
+/// Returns the `n` first items of the vector in the order given by `compare`.
+/// The vector is split in a part for each thread, and the `n` first items of each part
+/// are selected in parallel in linear time. The top is selected among these candidates,
+/// and only its `n` items are sorted.
+fn select_top<T: Send>(
+    mut items: Vec<T>,
+    n: usize,
+    compare: impl Fn(&T, &T) -> Ordering + Sync,
+) -> Vec<T> {
+    if n == 0 {
+        return Vec::new();
+    }
+    if items.len() > n {
+        let part_size = items.len().div_ceil(rayon::current_num_threads()).max(n);
+        items.par_chunks_mut(part_size).for_each(|part| {
+            if part.len() > n {
+                part.select_nth_unstable_by(n - 1, &compare);
+            }
+        });
+        // Los candidatos son los n primeros de cada parte
+        items = items
+            .into_par_iter()
+            .enumerate()
+            .filter(|(index, _)| index % part_size < n)
+            .map(|(_, item)| item)
+            .collect();
+        if items.len() > n {
+            items.select_nth_unstable_by(n - 1, &compare);
+            items.truncate(n);
+        }
+    }
+    items.sort_unstable_by(compare);
+    items
+}
+
+fn select_top_monitored_players(
+    top_killers_summary: &SpaceSaving,
+    n: usize,
+) -> Vec<(&String, &MonitoredPlayer)> {
+    let players_vec = top_killers_summary.get_players().par_iter().collect();
+    select_top(players_vec, n, |a: &(&String, &MonitoredPlayer), b| {
+        let kills_cmp = b.1.get_kills().cmp(&a.1.get_kills());
+        if kills_cmp == Ordering::Equal {
+            a.0.cmp(b.0)
+        } else {
+            kills_cmp
+        }
+    })
 }
 
 fn calculate_top_weapons_for_player(
@@ -130,16 +586,21 @@ fn calculate_top_weapons_for_player(
 ) -> HashMap<String, f64> {
     let weapons_vec = get_sorted_vector_by_kills(weapons);
 
-    get_top_weapons_for_player(weapons_vec, total_deaths_caused_by_player)
+    get_top_weapons_for_player(
+        weapons_vec,
+        total_deaths_caused_by_player,
+        TOP_WEAPONS_FOR_PLAYER,
+    )
 }
 
 fn get_top_weapons_for_player(
     weapons_vec: Vec<(&String, &i32)>,
     total_deaths_caused_by_player: i32,
+    number_of_weapons: usize,
 ) -> HashMap<String, f64> {
     let top_weapons = weapons_vec
         .par_iter()
-        .take(TOP_WEAPONS_FOR_PLAYER)
+        .take(number_of_weapons)
         .map(|(weapon, &count)| {
             let percentage = (count as f64 / total_deaths_caused_by_player as f64) * 100.0;
             let rounded_percentage = (percentage * 100.0).round() / 100.0;
@@ -188,6 +649,48 @@ fn calculate_percentage(part: u32, total: u32) -> f64 {
     (percentage * 100.0).round() / 100.0
 }
 
+/// Returns the buckets of the distance distribution of a weapon, from the closest to the farthest.
+/// The last bucket has no upper limit.
+fn calculate_distance_distribution(weapon_stats: &WeaponStats) -> Vec<serde_json::Value> {
+    let distribution = weapon_stats.get_distance_distribution();
+    (0..=DISTANCE_BUCKET_LIMITS.len())
+        .map(|bucket| {
+            let from = match bucket {
+                0 => 0.0,
+                _ => DISTANCE_BUCKET_LIMITS[bucket - 1],
+            };
+            json!({
+                "from": from,
+                "to": DISTANCE_BUCKET_LIMITS.get(bucket),
+                "kills": distribution.get(bucket).copied().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Returns the kills of a weapon in each interval of the match, in seconds.
+fn calculate_time_profile(weapon_stats: &WeaponStats) -> Vec<serde_json::Value> {
+    weapon_stats
+        .get_time_profile()
+        .iter()
+        .enumerate()
+        .map(|(bucket, kills)| {
+            let from = bucket as u32 * TIME_BUCKET_SECONDS;
+            json!({
+                "from": from,
+                "to": from + TIME_BUCKET_SECONDS,
+                "kills": kills,
+            })
+        })
+        .collect()
+}
+
+/// Returns the kills per death. If the player never died, the ratio is the number of kills.
+fn calculate_kill_death_ratio(kills: u32, deaths: u32) -> f64 {
+    let ratio = kills as f64 / deaths.max(1) as f64;
+    (ratio * 100.0).round() / 100.0
+}
+
 fn calculate_average_distance(weapon_stats: &WeaponStats) -> f64 {
     (weapon_stats.get_death_distance()
         / weapon_stats.get_number_of_kills_with_valid_distance() as f64
@@ -195,3 +698,11 @@ fn calculate_average_distance(weapon_stats: &WeaponStats) -> f64 {
         .round()
         / 100.0
 }
+
+fn get_distinct_counts_json(counts: &DistinctCounts) -> serde_json::Value {
+    json!({
+        "players": counts.get_players(),
+        "matches": counts.get_matches(),
+        "killer_victim_pairs": counts.get_killer_victim_pairs(),
+    })
+}
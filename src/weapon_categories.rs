@@ -0,0 +1,122 @@
+use std::{collections::HashMap, fs, path::Path};
+
+const ASSAULT_RIFLE: &str = "AR";
+const SUBMACHINE_GUN: &str = "SMG";
+const SNIPER_RIFLE: &str = "SR";
+const DESIGNATED_MARKSMAN_RIFLE: &str = "DMR";
+const SHOTGUN: &str = "shotgun";
+const PISTOL: &str = "pistol";
+const THROWABLE: &str = "throwable";
+const MELEE: &str = "melee";
+const VEHICLE: &str = "vehicle";
+const OTHER: &str = "other";
+
+/// Built-in mapping between the `killed_by` values of the dataset and their category.
+/// The light machine guns are counted as assault rifles.
+const BUILT_IN_CATEGORIES: [(&str, &str); 52] = [
+    ("AKM", ASSAULT_RIFLE),
+    ("AUG", ASSAULT_RIFLE),
+    ("Groza", ASSAULT_RIFLE),
+    ("M16A4", ASSAULT_RIFLE),
+    ("M416", ASSAULT_RIFLE),
+    ("SCAR-L", ASSAULT_RIFLE),
+    ("M249", ASSAULT_RIFLE),
+    ("DP-28", ASSAULT_RIFLE),
+    ("Micro UZI", SUBMACHINE_GUN),
+    ("UMP9", SUBMACHINE_GUN),
+    ("Vector", SUBMACHINE_GUN),
+    ("Tommy Gun", SUBMACHINE_GUN),
+    ("AWM", SNIPER_RIFLE),
+    ("Kar98k", SNIPER_RIFLE),
+    ("M24", SNIPER_RIFLE),
+    ("Win94", SNIPER_RIFLE),
+    ("Mini 14", DESIGNATED_MARKSMAN_RIFLE),
+    ("Mk14", DESIGNATED_MARKSMAN_RIFLE),
+    ("SKS", DESIGNATED_MARKSMAN_RIFLE),
+    ("VSS", DESIGNATED_MARKSMAN_RIFLE),
+    ("Crossbow", DESIGNATED_MARKSMAN_RIFLE),
+    ("S12K", SHOTGUN),
+    ("S1897", SHOTGUN),
+    ("S686", SHOTGUN),
+    ("Sawed-off", SHOTGUN),
+    ("death.WeapSawnoff_C", SHOTGUN),
+    ("P18C", PISTOL),
+    ("P1911", PISTOL),
+    ("P92", PISTOL),
+    ("R1895", PISTOL),
+    ("R45", PISTOL),
+    ("Grenade", THROWABLE),
+    ("Molotov", THROWABLE),
+    ("Sticky Bomb", THROWABLE),
+    ("Punch", MELEE),
+    ("Crowbar", MELEE),
+    ("Machete", MELEE),
+    ("Pan", MELEE),
+    ("Sickle", MELEE),
+    ("Hit by Car", VEHICLE),
+    ("Run Over", VEHICLE),
+    ("Aquarail", VEHICLE),
+    ("Boat", VEHICLE),
+    ("Buggy", VEHICLE),
+    ("Dacia", VEHICLE),
+    ("Motorbike", VEHICLE),
+    ("Motorbike (SideCar)", VEHICLE),
+    ("Pickup Truck", VEHICLE),
+    ("Uaz", VEHICLE),
+    ("Van", VEHICLE),
+    ("Vehicle", VEHICLE),
+    ("Mirado", VEHICLE),
+];
+
+pub struct WeaponCategories {
+    categories: HashMap<String, String>,
+}
+
+impl WeaponCategories {
+    /// Creates the built-in weapon to category mapping.
+    pub fn new() -> Self {
+        let categories = BUILT_IN_CATEGORIES
+            .iter()
+            .map(|(weapon, category)| (weapon.to_string(), category.to_string()))
+            .collect();
+        Self { categories }
+    }
+
+    /// Creates the built-in mapping and overrides it with the entries of the given file.
+    /// The file maps each weapon to its category, for example `{"M249": "LMG"}` in JSON
+    /// or `M249 = "LMG"` in TOML. The format is chosen by the file extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to a `.json` or `.toml` file.
+    ///
+    /// # Returns
+    ///
+    /// The mapping, or a message describing why the file could not be used.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let overrides: HashMap<String, String> =
+            match Path::new(path).extension().and_then(|e| e.to_str()) {
+                Some("toml") => toml::from_str(&content).map_err(|e| e.to_string())?,
+                _ => serde_json::from_str(&content).map_err(|e| e.to_string())?,
+            };
+
+        let mut weapon_categories = Self::new();
+        weapon_categories.categories.extend(overrides);
+        Ok(weapon_categories)
+    }
+
+    /// Returns the category of the weapon, or `other` if the weapon is not mapped.
+    pub fn get_category(&self, weapon: &str) -> &str {
+        self.categories
+            .get(weapon)
+            .map(|category| category.as_str())
+            .unwrap_or(OTHER)
+    }
+}
+
+impl Default for WeaponCategories {
+    fn default() -> Self {
+        Self::new()
+    }
+}
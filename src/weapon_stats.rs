@@ -1,8 +1,24 @@
-#[derive(Debug, Clone)]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory_usage;
+
+/// Upper limits of the buckets of the distance distribution.
+/// The last bucket holds the kills farther than the last limit.
+pub const DISTANCE_BUCKET_LIMITS: [f64; 6] = [1000.0, 5000.0, 10000.0, 20000.0, 50000.0, 100000.0];
+/// Seconds of match covered by each bucket of the time profile.
+pub const TIME_BUCKET_SECONDS: u32 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeaponStats {
     death_distance: f64,
     number_of_kills_with_distance: u32,
     number_of_kills_without_distance: u32, // Representa
+    // Detalle que solo se calcula para el arma pedida con el subcomando weapon
+    distance_distribution: Vec<u32>,
+    kills_by_map: HashMap<String, u32>,
+    time_profile: Vec<u32>,
 }
 
 impl WeaponStats {
@@ -15,6 +31,9 @@ impl WeaponStats {
             death_distance,
             number_of_kills_with_distance,
             number_of_kills_without_distance,
+            distance_distribution: Vec::new(),
+            kills_by_map: HashMap::new(),
+            time_profile: Vec::new(),
         }
     }
 
@@ -30,6 +49,58 @@ impl WeaponStats {
         self.number_of_kills_without_distance += number_of_kills;
     }
 
+    /// Adds a kill to the bucket of the distance distribution that contains the distance.
+    pub fn add_kill_to_distance_distribution(&mut self, distance: f64) {
+        let bucket = DISTANCE_BUCKET_LIMITS
+            .iter()
+            .position(|&limit| distance < limit)
+            .unwrap_or(DISTANCE_BUCKET_LIMITS.len());
+        add_to_bucket(&mut self.distance_distribution, bucket, 1);
+    }
+
+    /// Adds a kill made in the given map.
+    pub fn add_kill_in_map(&mut self, map: &str) {
+        match self.kills_by_map.get_mut(map) {
+            Some(kills) => *kills += 1,
+            None => {
+                self.kills_by_map.insert(map.to_string(), 1);
+            }
+        }
+    }
+
+    /// Adds a kill to the bucket of the time profile that contains the second of the match.
+    pub fn add_kill_to_time_profile(&mut self, time: u32) {
+        add_to_bucket(
+            &mut self.time_profile,
+            (time / TIME_BUCKET_SECONDS) as usize,
+            1,
+        );
+    }
+
+    /// Adds the kills and distances of other statistics to these ones.
+    pub fn merge(&mut self, other: &WeaponStats) {
+        self.set_death_distance(other.get_death_distance());
+        self.set_number_of_kills_with_valid_distance(
+            other.get_number_of_kills_with_valid_distance(),
+        );
+        self.set_total_kills_caused_by_weapon(other.get_total_kills_caused_by_weapon());
+        other
+            .distance_distribution
+            .iter()
+            .enumerate()
+            .for_each(|(bucket, kills)| {
+                add_to_bucket(&mut self.distance_distribution, bucket, *kills)
+            });
+        other.kills_by_map.iter().for_each(|(map, kills)| {
+            *self.kills_by_map.entry(map.clone()).or_default() += kills;
+        });
+        other
+            .time_profile
+            .iter()
+            .enumerate()
+            .for_each(|(bucket, kills)| add_to_bucket(&mut self.time_profile, bucket, *kills));
+    }
+
     pub fn get_death_distance(&self) -> f64 {
         self.death_distance
     }
@@ -41,4 +112,34 @@ impl WeaponStats {
     pub fn get_total_kills_caused_by_weapon(&self) -> u32 {
         self.number_of_kills_without_distance
     }
+
+    /// Returns the kills of each bucket of the distance distribution.
+    pub fn get_distance_distribution(&self) -> &[u32] {
+        &self.distance_distribution
+    }
+
+    pub fn get_kills_by_map(&self) -> &HashMap<String, u32> {
+        &self.kills_by_map
+    }
+
+    /// Returns the kills of each bucket of the time profile.
+    pub fn get_time_profile(&self) -> &[u32] {
+        &self.time_profile
+    }
+
+    /// Returns the estimated bytes of the statistics.
+    pub fn estimate_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + (self.distance_distribution.len() + self.time_profile.len())
+                * std::mem::size_of::<u32>()
+            + memory_usage::estimate_map_bytes(&self.kills_by_map, |_| 0)
+    }
+}
+
+/// Adds kills to a bucket of a distribution, growing the distribution if needed.
+fn add_to_bucket(distribution: &mut Vec<u32>, bucket: usize, kills: u32) {
+    if distribution.len() <= bucket {
+        distribution.resize(bucket + 1, 0);
+    }
+    distribution[bucket] += kills;
 }
@@ -17,30 +17,46 @@ impl Writer {
         &self.output_file_name
     }
 
-
     /// Write the results in the output file
-    /// 
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `top_killers` - A HashMap <PlayerName, BestStats in a JSON format>
     /// * `top_weapons` - A HashMap <WeaponName, BestStats in a JSON format>
-    /// 
+    /// * `extra_sections` - Optional sections of the report, written under their own key
+    ///
     /// # Returns
-    /// 
+    ///
     /// A Result with the result of the operation
     pub fn write_results_in_file(
         &self,
         top_killers: HashMap<String, serde_json::Value>,
         top_weapons: HashMap<String, serde_json::Value>,
+        extra_sections: HashMap<String, serde_json::Value>,
     ) -> std::io::Result<()> {
-        let output = json!({
+        let mut output = json!({
             "padron": 108091,
             "top_killers": top_killers,
             "top_weapons": top_weapons
         });
+        if let Some(output_sections) = output.as_object_mut() {
+            output_sections.extend(extra_sections);
+        }
+        self.write_json_in_file(&output)
+    }
 
+    /// Write a JSON value in the output file
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The JSON value to write
+    ///
+    /// # Returns
+    ///
+    /// A Result with the result of the operation
+    pub fn write_json_in_file(&self, output: &serde_json::Value) -> std::io::Result<()> {
         let mut file = File::create(self.get_output_file_name())?;
-        file.write_all(serde_json::to_string_pretty(&output)?.as_bytes())?;
+        file.write_all(serde_json::to_string_pretty(output)?.as_bytes())?;
         Ok(())
     }
 }
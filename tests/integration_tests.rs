@@ -1,8 +1,17 @@
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::FileWriter;
 use assert_json_diff::assert_json_eq;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+use tp_fork_join::thread_pool::ThreadPoolConfig;
+use tp_fork_join::top_calculator::select_top_players_by_kills;
 
 #[test]
 fn test_output_with_expected_json() {
@@ -52,3 +61,1291 @@ fn test_output_with_expected_json() {
     // Comparar ambos JSON sin importar el orden de los campos
     assert_json_eq!(generated_json, expected_json);
 }
+
+const SAMPLE_INPUT_PATH: &str = "tests/data/deaths";
+
+/// Ejecuta el programa sobre el dataset de prueba y devuelve el JSON generado
+fn run_with_sample_dataset(test_name: &str, options: &[&str]) -> Value {
+    run_command_with_sample_dataset(test_name, &[], options)
+}
+
+/// Ejecuta un subcomando del programa sobre el dataset de prueba y devuelve el JSON generado
+fn run_command_with_sample_dataset(test_name: &str, command: &[&str], options: &[&str]) -> Value {
+    run_command_with_dataset(test_name, command, Path::new(SAMPLE_INPUT_PATH), options)
+}
+
+/// Ejecuta un subcomando del programa sobre un dataset y devuelve el JSON generado
+fn run_command_with_dataset(
+    test_name: &str,
+    command: &[&str],
+    input_path: &Path,
+    options: &[&str],
+) -> Value {
+    let output_file_path = std::env::temp_dir().join(format!("tp_fork_join_{}.json", test_name));
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args(command)
+        .arg(input_path)
+        .arg("2")
+        .arg(&output_file_path)
+        .args(options)
+        .output()
+        .expect("Error al ejecutar el programa");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let generated_content =
+        std::fs::read_to_string(&output_file_path).expect("Error al leer el archivo generado");
+    serde_json::from_str(&generated_content).expect("Error al parsear el JSON generado")
+}
+
+/// Genera un dataset en un directorio temporal con la cantidad de archivos y lineas indicadas.
+/// Cada linea se arma a partir de su numero, contando todas las lineas de todos los archivos
+fn write_generated_dataset(
+    test_name: &str,
+    files: usize,
+    lines_per_file: usize,
+    line: impl Fn(usize) -> String,
+) -> PathBuf {
+    let input_path = std::env::temp_dir().join(format!("tp_fork_join_{}", test_name));
+    std::fs::create_dir_all(&input_path).expect("Error al crear el directorio del dataset");
+    (0..files).for_each(|file| {
+        let content: String = (file * lines_per_file..(file + 1) * lines_per_file)
+            .map(|number| line(number) + "\n")
+            .collect();
+        std::fs::write(
+            input_path.join(format!("kill_match_stats_{}.csv", file)),
+            content,
+        )
+        .expect("Error al escribir el dataset");
+    });
+    input_path
+}
+
+/// Filas de cada lote de los archivos columnares generados, y de cada grupo de filas de Parquet
+const COLUMNAR_BATCH_ROWS: usize = 1000;
+
+/// Convierte las lineas de un CSV en un lote de filas con las columnas del dataset:
+/// las posiciones y los puestos como numeros reales, el tiempo como entero y el resto como texto.
+/// Los campos de texto vacios quedan nulos.
+fn csv_lines_to_batch(lines: &[&str]) -> RecordBatch {
+    let rows: Vec<Vec<&str>> = lines.iter().map(|line| line.split(',').collect()).collect();
+    let names = [
+        "killed_by",
+        "killer_name",
+        "killer_placement",
+        "killer_position_x",
+        "killer_position_y",
+        "map",
+        "match_id",
+        "time",
+        "victim_name",
+        "victim_placement",
+        "victim_position_x",
+        "victim_position_y",
+    ];
+    let columns = names.iter().enumerate().map(|(field, name)| {
+        let values = rows.iter().map(|row| row[field]);
+        let column: ArrayRef = match *name {
+            "time" => Arc::new(Int64Array::from_iter(values.map(|v| v.parse().ok()))),
+            name if name.ends_with("placement") || name.contains("position") => {
+                Arc::new(Float64Array::from_iter(values.map(|v| v.parse().ok())))
+            }
+            _ => Arc::new(StringArray::from_iter(
+                values.map(|v| (!v.is_empty()).then_some(v)),
+            )),
+        };
+        (*name, column, true)
+    });
+    RecordBatch::try_from_iter_with_nullable(columns).expect("Error al armar el lote de filas")
+}
+
+/// Escribe los CSV de un dataset como archivos Parquet o Arrow IPC, en varios lotes de filas
+fn write_columnar_dataset(test_name: &str, csv_path: &Path, format: &str) -> PathBuf {
+    let input_path = std::env::temp_dir().join(format!("tp_fork_join_{}_{}", test_name, format));
+    std::fs::create_dir_all(&input_path).expect("Error al crear el directorio del dataset");
+    for entry in std::fs::read_dir(csv_path).expect("Error al leer el dataset") {
+        let csv_file = entry.expect("Error al leer el dataset").path();
+        let content = std::fs::read_to_string(&csv_file).expect("Error al leer el dataset");
+        let lines: Vec<&str> = content.lines().collect();
+        let batches = lines.chunks(COLUMNAR_BATCH_ROWS).map(csv_lines_to_batch);
+        let file_path = input_path.join(csv_file.with_extension(format).file_name().unwrap());
+        let file = File::create(file_path).expect("Error al crear el archivo");
+        let schema = csv_lines_to_batch(&lines[..1]).schema();
+        match format {
+            "parquet" => {
+                let properties = WriterProperties::builder()
+                    .set_max_row_group_size(COLUMNAR_BATCH_ROWS)
+                    .build();
+                let mut writer = ArrowWriter::try_new(file, schema, Some(properties))
+                    .expect("Error al crear el archivo Parquet");
+                batches.for_each(|batch| writer.write(&batch).expect("Error al escribir"));
+                writer
+                    .close()
+                    .expect("Error al escribir el archivo Parquet");
+            }
+            _ => {
+                let mut writer =
+                    FileWriter::try_new(file, &schema).expect("Error al crear el archivo Arrow");
+                batches.for_each(|batch| writer.write(&batch).expect("Error al escribir"));
+                writer.finish().expect("Error al escribir el archivo Arrow");
+            }
+        }
+    }
+    input_path
+}
+
+#[test]
+fn test_weapon_categories() {
+    let generated_json = run_with_sample_dataset("weapon_categories", &["--categories"]);
+    let categories = &generated_json["weapon_categories"];
+
+    assert_eq!(categories["AR"]["deaths_percentage"], 42.86);
+    assert_eq!(categories["AR"]["average_distance"], 170.0);
+    assert_eq!(categories["vehicle"]["average_distance"], 35.0);
+
+    let generated_json = run_with_sample_dataset(
+        "weapon_categories_file",
+        &["--categories-file", "tests/data/categories.toml"],
+    );
+    let categories = &generated_json["weapon_categories"];
+
+    assert_eq!(categories["AR"]["deaths_percentage"], 28.57);
+    assert_eq!(categories["AR"]["average_distance"], 225.0);
+    assert_eq!(categories["AKM only"]["deaths_percentage"], 14.29);
+}
+
+#[test]
+fn test_missing_categories_file_fails_before_processing() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args([
+            "--categories-file",
+            "tests/data/missing.toml",
+            "--progress=json",
+        ])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error al leer el archivo de categorias"));
+    // No se llego a procesar ningun archivo, asi que no se mostro el progreso
+    assert!(!stderr.lines().any(|line| line.starts_with('{')));
+}
+
+#[test]
+fn test_placement_stats() {
+    let generated_json = run_with_sample_dataset("placement_stats", &["--placement"]);
+    let placement_stats = &generated_json["placement_stats"];
+
+    let winners = &placement_stats["brackets"]["winner"];
+    assert_eq!(winners["kills"], 6);
+    assert_eq!(winners["average_distance"], 138.33);
+    assert_eq!(winners["weapons_percentage"]["M416"], 50.0);
+    assert_eq!(placement_stats["brackets"]["top_10"]["kills"], 4);
+    assert_eq!(placement_stats["brackets"]["rest"]["kills"], 1);
+    assert_eq!(placement_stats["kills_by_placement"]["3"], 2);
+}
+
+#[test]
+fn test_placement_stats_skip_invalid_placements() {
+    let placements = ["0.0", "-3.0", "0.5", "1.0", "5.0", "20.0"];
+    let input_path = write_generated_dataset("invalid_placements", 1, placements.len(), |n| {
+        format!(
+            "M416,player{},{},0.0,0.0,ERANGEL,m1,100,victim{},2.0,3.0,4.0",
+            n, placements[n], n
+        )
+    });
+    let generated_json =
+        run_command_with_dataset("invalid_placements", &[], &input_path, &["--placement"]);
+    let placement_stats = &generated_json["placement_stats"];
+
+    assert_eq!(placement_stats["brackets"]["winner"]["kills"], 1);
+    assert_eq!(placement_stats["brackets"]["top_10"]["kills"], 1);
+    assert_eq!(placement_stats["brackets"]["rest"]["kills"], 1);
+    assert!(placement_stats["kills_by_placement"].get("0").is_none());
+}
+
+#[test]
+fn test_top_rivalries_and_graph_export() {
+    let graph_file_path = std::env::temp_dir().join("tp_fork_join_kill_graph.csv");
+    let generated_json = run_with_sample_dataset(
+        "top_rivalries",
+        &[
+            "--rivalries",
+            "--graph-export",
+            graph_file_path.to_str().unwrap(),
+        ],
+    );
+    let rivalries = generated_json["top_rivalries"].as_array().unwrap();
+
+    assert_eq!(rivalries.len(), 2);
+    assert_eq!(
+        rivalries[0]["players"],
+        serde_json::json!(["alice", "carol"])
+    );
+    assert_eq!(rivalries[0]["kills"]["carol"], 1);
+    assert_eq!(rivalries[1]["total_kills"], 2);
+
+    let edge_list = std::fs::read_to_string(graph_file_path).unwrap();
+    assert!(edge_list.starts_with("source,target,weight\n"));
+    assert!(edge_list.contains("\nalice,bob,1\n"));
+}
+
+#[test]
+fn test_player_profile() {
+    let profile = run_command_with_sample_dataset("player_profile", &["player", "alice"], &[]);
+
+    assert_eq!(profile["kills"], 3);
+    assert_eq!(profile["deaths"], 1);
+    assert_eq!(profile["kd_ratio"], 3.0);
+    assert_eq!(profile["weapons_percentage"]["M416"], 66.67);
+    assert_eq!(profile["average_distance"], 216.67);
+    assert_eq!(profile["longest_kill"], 500.0);
+    assert_eq!(profile["maps_played"]["ERANGEL"], 2);
+}
+
+#[test]
+fn test_weapon_detail() {
+    let detail = run_command_with_sample_dataset("weapon_detail", &["weapon", "M416"], &[]);
+
+    assert_eq!(detail["kills"], 4);
+    assert_eq!(detail["deaths_percentage"], 28.57);
+    assert_eq!(detail["average_distance"], 225.0);
+    assert_eq!(detail["distance_distribution"][0]["kills"], 4);
+    assert_eq!(detail["top_users"]["alice"], 2);
+    assert_eq!(detail["kills_by_map"]["ERANGEL"]["percentage"], 75.0);
+    assert_eq!(
+        detail["time_profile"],
+        serde_json::json!([
+            {"from": 0, "to": 300, "kills": 2},
+            {"from": 300, "to": 600, "kills": 1},
+            {"from": 600, "to": 900, "kills": 1},
+        ])
+    );
+}
+
+#[test]
+fn test_filter_expression() {
+    let generated_json = run_with_sample_dataset(
+        "filter_expression",
+        &["--filter", r#"map == "ERANGEL" && time > 600"#],
+    );
+
+    assert_eq!(
+        generated_json["top_weapons"]["AKM"]["deaths_percentage"],
+        40.0
+    );
+    assert_eq!(
+        generated_json["top_weapons"]["AKM"]["average_distance"],
+        60.0
+    );
+    assert_eq!(generated_json["top_killers"].as_object().unwrap().len(), 4);
+}
+
+#[test]
+fn test_filter_expression_with_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args(["--filter", "map == ERANGEL"])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("posicion 8"));
+}
+
+#[test]
+fn test_query_grouped_by_map() {
+    let result = run_command_with_sample_dataset(
+        "query_grouped_by_map",
+        &["query"],
+        &[
+            "--group-by",
+            "map",
+            "--aggregate",
+            "count,avg(distance),max(time),distinct(killer_name)",
+        ],
+    );
+
+    let rows = result["rows"].as_array().unwrap();
+    let erangel = rows.iter().find(|row| row["map"] == "ERANGEL").unwrap();
+    assert_eq!(erangel["count"], 9);
+    assert_eq!(erangel["avg(distance)"], 240.0);
+    assert_eq!(erangel["max(time)"], 1500.0);
+    assert_eq!(erangel["distinct(killer_name)"], 5);
+    let miramar = rows.iter().find(|row| row["map"] == "MIRAMAR").unwrap();
+    assert_eq!(miramar["count"], 3);
+    assert_eq!(miramar["avg(distance)"], 55.0);
+}
+
+#[test]
+fn test_query_with_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args(["query", SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args(["--aggregate", "avg(map)"])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("columna numerica"));
+}
+
+#[test]
+fn test_exact_distinct_counts() {
+    let generated_json = run_with_sample_dataset("exact_distinct_counts", &["--distinct", "exact"]);
+
+    let distinct_counts = &generated_json["distinct_counts"];
+    assert_eq!(distinct_counts["error_bound"], 0.0);
+    assert_eq!(distinct_counts["by_map"]["ERANGEL"]["players"], 7);
+    assert_eq!(distinct_counts["by_map"]["ERANGEL"]["matches"], 2);
+    assert_eq!(
+        distinct_counts["by_map"]["ERANGEL"]["killer_victim_pairs"],
+        8
+    );
+    assert_eq!(distinct_counts["by_map"]["MIRAMAR"]["players"], 4);
+}
+
+#[test]
+fn test_approximate_distinct_counts_within_error_bound() {
+    // 10000 victimas distintas, 500 asesinos y 100 partidas repartidos en 4 archivos
+    let input_path = write_generated_dataset("approximate_distinct_counts", 4, 2500, |n| {
+        format!(
+            "M416,killer{},1.0,0.0,0.0,ERANGEL,match{},100,victim{},2.0,3.0,4.0",
+            n % 500,
+            n % 100,
+            n
+        )
+    });
+    let generated_json = run_command_with_dataset(
+        "approximate_distinct_counts",
+        &[],
+        &input_path,
+        &["--distinct", "approx", "--hll-precision", "12"],
+    );
+
+    let distinct_counts = &generated_json["distinct_counts"];
+    assert_eq!(distinct_counts["precision"], 12);
+    let error_bound = distinct_counts["error_bound"].as_f64().unwrap();
+    assert!(error_bound > 0.0);
+    [
+        ("players", 10500.0),
+        ("matches", 100.0),
+        ("killer_victim_pairs", 10000.0),
+    ]
+    .iter()
+    .for_each(|(count, exact)| {
+        let estimate = distinct_counts["global"][count].as_f64().unwrap();
+        assert!(
+            (estimate - exact).abs() <= error_bound * exact,
+            "{}: {} no esta dentro del {} de {}",
+            count,
+            estimate,
+            error_bound,
+            exact
+        );
+    });
+}
+
+#[test]
+fn test_top_killers_among_many_players() {
+    // La mitad de las muertes son de 10 jugadores con pesos de 1 a 10,
+    // y el resto de jugadores con una sola muerte
+    let killer = |n: usize| match n % 2 {
+        0 => {
+            let slot = ((n / 2) % 55) as f64;
+            format!("pro{}", ((8.0 * slot + 1.0).sqrt() as usize - 1) / 2)
+        }
+        _ => format!("noob{}", n),
+    };
+    let input_path = write_generated_dataset("top_killers_among_many_players", 4, 3000, |n| {
+        format!(
+            "M416,{},1.0,0.0,0.0,ERANGEL,m1,100,victim{},2.0,3.0,4.0",
+            killer(n),
+            n
+        )
+    });
+    let generated_json =
+        run_command_with_dataset("top_killers_among_many_players", &[], &input_path, &[]);
+
+    let top_killers = generated_json["top_killers"].as_object().unwrap();
+    assert_eq!(top_killers.len(), 10);
+    (0..10).for_each(|pro| {
+        let player = format!("pro{}", pro);
+        let deaths = (0..12000).filter(|n| killer(*n) == player).count();
+        assert_eq!(top_killers[&player]["deaths"], deaths);
+    });
+}
+
+#[test]
+fn test_approximate_top_killers_within_error_bound() {
+    // Los 10 mejores jugadores hacen 1 de cada 4 muertes, con pesos de 1 a 10;
+    // el resto son jugadores con una sola muerte
+    let input_path = write_generated_dataset("approximate_top_killers", 4, 5000, |n| {
+        let killer = match n % 4 {
+            0 => {
+                let slot = ((n / 4) % 55) as f64;
+                format!("pro{}", ((8.0 * slot + 1.0).sqrt() as usize - 1) / 2)
+            }
+            _ => format!("noob{}", n),
+        };
+        format!(
+            "M416,{},1.0,0.0,0.0,ERANGEL,m1,100,victim{},2.0,3.0,4.0",
+            killer, n
+        )
+    });
+    let exact_json = run_command_with_dataset("exact_top_killers", &[], &input_path, &[]);
+    let approx_json = run_command_with_dataset(
+        "approximate_top_killers",
+        &[],
+        &input_path,
+        &["--approx", "--approx-capacity", "500"],
+    );
+
+    let exact_top_killers = exact_json["top_killers"].as_object().unwrap();
+    let approx_top_killers = approx_json["top_killers"].as_object().unwrap();
+    let mut exact_players: Vec<_> = exact_top_killers.keys().collect();
+    let mut approx_players: Vec<_> = approx_top_killers.keys().collect();
+    exact_players.sort();
+    approx_players.sort();
+    assert_eq!(approx_players, exact_players);
+
+    let top_killers_error = &approx_json["top_killers_error"];
+    assert_eq!(top_killers_error["total_kills"], 20000);
+    assert!(top_killers_error["error_bound"].as_u64().unwrap() <= 20000 / 500);
+    exact_top_killers.iter().for_each(|(player, exact)| {
+        let exact_kills = exact["deaths"].as_u64().unwrap();
+        let approx_kills = approx_top_killers[player]["deaths"].as_u64().unwrap();
+        let max_overestimation = top_killers_error["players"][player]["max_overestimation"]
+            .as_u64()
+            .unwrap();
+        assert!(approx_kills >= exact_kills && approx_kills - max_overestimation <= exact_kills);
+    });
+}
+
+#[test]
+fn test_select_top_players_keeps_tie_breaking() {
+    // Muchos jugadores con las mismas muertes para forzar los desempates por nombre
+    let player_kills: HashMap<String, HashMap<String, i32>> = (0..2000)
+        .map(|player| {
+            let weapons = HashMap::from([
+                ("M416".to_string(), player * 7 % 5),
+                ("AKM".to_string(), player % 3),
+            ]);
+            (format!("player{}", player), weapons)
+        })
+        .collect();
+    let mut expected: Vec<(&String, i32)> = player_kills
+        .iter()
+        .map(|(player, weapons)| (player, weapons.values().sum()))
+        .collect();
+    expected.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    [0, 1, 10, 500, 2000, 5000].iter().for_each(|&n| {
+        let selected: Vec<(&String, i32)> = select_top_players_by_kills(&player_kills, n)
+            .into_iter()
+            .map(|(player, kills, _)| (player, kills))
+            .collect();
+        assert_eq!(selected, expected[..n.min(expected.len())]);
+    });
+}
+
+#[test]
+fn test_incremental_state_matches_full_run() {
+    let input_path = std::env::temp_dir().join("tp_fork_join_incremental_state");
+    let state_path = std::env::temp_dir().join("tp_fork_join_incremental_state_file.json");
+    let _ = std::fs::remove_dir_all(&input_path);
+    let _ = std::fs::remove_file(&state_path);
+    std::fs::create_dir_all(&input_path).expect("Error al crear el directorio del dataset");
+    let copy_sample = |file_name: &str| {
+        std::fs::copy(
+            Path::new(SAMPLE_INPUT_PATH).join(file_name),
+            input_path.join(file_name),
+        )
+        .expect("Error al copiar el dataset");
+    };
+    let options = [
+        "--state",
+        state_path.to_str().unwrap(),
+        "--rivalries",
+        "--placement",
+        "--distinct",
+        "approx",
+        "--approx",
+    ];
+
+    // Primero un archivo, despues llega otro y por ultimo se modifica el primero
+    copy_sample("kill_match_stats_sample_0.csv");
+    run_command_with_dataset("incremental_state", &[], &input_path, &options);
+    copy_sample("kill_match_stats_sample_1.csv");
+    run_command_with_dataset("incremental_state", &[], &input_path, &options);
+    let mut modified_file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(input_path.join("kill_match_stats_sample_0.csv"))
+        .expect("Error al abrir el dataset");
+    std::io::Write::write_all(
+        &mut modified_file,
+        b"AKM,gina,1.0,0.0,0.0,MIRAMAR,m4,100,alice,2.0,30.0,40.0\n",
+    )
+    .expect("Error al modificar el dataset");
+    let incremental_json =
+        run_command_with_dataset("incremental_state", &[], &input_path, &options);
+
+    let full_json = run_command_with_dataset("full_run", &[], &input_path, &options[2..]);
+    assert_json_eq!(incremental_json, full_json);
+    assert_eq!(incremental_json["top_killers"]["gina"]["deaths"], 2);
+    // El archivo modificado reemplaza su linea, asi que queda la cabecera y una linea por archivo
+    let state_content =
+        std::fs::read_to_string(&state_path).expect("Error al leer el archivo de estado");
+    assert_eq!(state_content.lines().count(), 3);
+}
+
+#[test]
+fn test_state_with_sketches_of_another_hash_is_rejected() {
+    let state_path = std::env::temp_dir().join("tp_fork_join_sketch_hash_state_file.json");
+    let _ = std::fs::remove_file(&state_path);
+    let options = [
+        "--state",
+        state_path.to_str().unwrap(),
+        "--distinct",
+        "approx",
+    ];
+    run_with_sample_dataset("sketch_hash_state", &options);
+
+    // Los sketches del primer archivo pasan a tener otra funcion de hash
+    let state_content =
+        std::fs::read_to_string(&state_path).expect("Error al leer el archivo de estado");
+    let mut lines: Vec<String> = state_content.lines().map(str::to_string).collect();
+    assert!(lines[1].contains("\"hash\":\"xxhash64\""));
+    lines[1] = lines[1].replace("\"hash\":\"xxhash64\"", "\"hash\":\"siphash13\"");
+    std::fs::write(&state_path, lines.join("\n") + "\n")
+        .expect("Error al escribir el archivo de estado");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args(options)
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error al leer el estado"));
+}
+
+#[test]
+fn test_state_with_checkpoint_interval_saves_all_files() {
+    let input_path = Path::new(SAMPLE_INPUT_PATH);
+    let state_path = std::env::temp_dir().join("tp_fork_join_checkpoint_interval_file.json");
+    let _ = std::fs::remove_file(&state_path);
+    let options = [
+        "--state",
+        state_path.to_str().unwrap(),
+        "--checkpoint-interval",
+        "60s",
+    ];
+
+    // Los archivos que esperan el intervalo se guardan al terminar
+    let interval_json = run_command_with_dataset("checkpoint_interval", &[], input_path, &options);
+    let state_content =
+        std::fs::read_to_string(&state_path).expect("Error al leer el archivo de estado");
+    assert_eq!(state_content.lines().count(), 3);
+
+    let full_json = run_command_with_dataset("checkpoint_interval_full", &[], input_path, &[]);
+    assert_json_eq!(interval_json, full_json);
+}
+
+/// Arma un checkpoint como el de una ejecucion interrumpida despues de procesar el primer archivo,
+/// mientras agregaba el segundo al final del checkpoint
+fn write_interrupted_checkpoint(input_path: &Path, checkpoint_path: &Path, options: &[&str]) {
+    let state_path = checkpoint_path.with_extension("state.json");
+    let _ = std::fs::remove_file(&state_path);
+    let state_options = [&["--state", state_path.to_str().unwrap()], options].concat();
+    let test_name = checkpoint_path.file_stem().unwrap().to_str().unwrap();
+    run_command_with_dataset(test_name, &[], input_path, &state_options);
+
+    // El estado tiene una linea con la version y las opciones, y una linea por archivo
+    let state_content =
+        std::fs::read_to_string(&state_path).expect("Error al leer el archivo de estado");
+    let mut lines = state_content.lines();
+    let mut header: Value =
+        serde_json::from_str(lines.next().unwrap()).expect("Error al parsear el archivo de estado");
+    let files: Vec<&str> = lines.collect();
+    let mut inputs: Vec<Value> = files
+        .iter()
+        .map(|file| {
+            let file: Value = serde_json::from_str(file).unwrap();
+            file["path"].clone()
+        })
+        .collect();
+    inputs.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+    header["options"] = serde_json::json!({
+        "options": header["options"],
+        "inputs": inputs,
+    });
+    let cut_line = &files[1][..files[1].len() / 2];
+    let checkpoint = format!("{}\n{}\n{}", header, files[0], cut_line);
+    std::fs::write(checkpoint_path, checkpoint).expect("Error al escribir el checkpoint");
+}
+
+#[test]
+fn test_resume_from_checkpoint_matches_full_run() {
+    let input_path = Path::new(SAMPLE_INPUT_PATH);
+    let checkpoint_path = std::env::temp_dir().join("tp_fork_join_resume_checkpoint_file.json");
+    let options = ["--rivalries", "--placement", "--distinct", "exact"];
+    write_interrupted_checkpoint(input_path, &checkpoint_path, &options);
+
+    let resume_options = [
+        &[
+            "--checkpoint",
+            checkpoint_path.to_str().unwrap(),
+            "--resume",
+        ],
+        &options[..],
+    ]
+    .concat();
+    let resumed_json =
+        run_command_with_dataset("resume_checkpoint", &[], input_path, &resume_options);
+
+    let full_json = run_command_with_dataset("resume_full_run", &[], input_path, &options);
+    assert_json_eq!(resumed_json, full_json);
+    assert!(!checkpoint_path.exists());
+}
+
+#[test]
+fn test_resume_from_checkpoint_with_other_options() {
+    let input_path = Path::new(SAMPLE_INPUT_PATH);
+    let checkpoint_path = std::env::temp_dir().join("tp_fork_join_resume_mismatch_file.json");
+    write_interrupted_checkpoint(input_path, &checkpoint_path, &["--placement"]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args([
+            "--checkpoint",
+            checkpoint_path.to_str().unwrap(),
+            "--resume",
+        ])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No se puede continuar"));
+    assert!(checkpoint_path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cancelled_run_writes_partial_report() {
+    let input_path = std::env::temp_dir().join("tp_fork_join_cancelled_run");
+    let output_file_path = std::env::temp_dir().join("tp_fork_join_cancelled_run.json");
+    let _ = std::fs::remove_dir_all(&input_path);
+    let _ = std::fs::remove_file(&output_file_path);
+    std::fs::create_dir_all(&input_path).expect("Error al crear el directorio del dataset");
+    let completed_file = input_path.join("kill_match_stats_sample_0.csv");
+    std::fs::copy(
+        Path::new(SAMPLE_INPUT_PATH).join("kill_match_stats_sample_0.csv"),
+        &completed_file,
+    )
+    .expect("Error al copiar el dataset");
+    // La lectura de un FIFO se bloquea hasta que se escribe, asi la ejecucion no termina sola
+    let fifo_path = input_path.join("kill_match_stats_fifo.csv");
+    let mkfifo_status = Command::new("mkfifo")
+        .arg(&fifo_path)
+        .status()
+        .expect("Error al ejecutar mkfifo");
+    assert!(mkfifo_status.success());
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .arg(&input_path)
+        .arg("2")
+        .arg(&output_file_path)
+        .arg("--partial")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Error al ejecutar el programa");
+    let line = b"AKM,gina,1.0,0.0,0.0,MIRAMAR,m4,100,alice,2.0,30.0,40.0\n";
+    let mut fifo = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&fifo_path)
+        .expect("Error al abrir el FIFO");
+    std::io::Write::write_all(&mut fifo, line).expect("Error al escribir en el FIFO");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let kill_status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .expect("Error al ejecutar kill");
+    assert!(kill_status.success());
+    // Una linea mas despierta al hilo bloqueado en el FIFO, que ve la cancelacion
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    std::io::Write::write_all(&mut fifo, line).expect("Error al escribir en el FIFO");
+    drop(fifo);
+    let status = child.wait().expect("Error al esperar el programa");
+
+    assert_eq!(status.code(), Some(130));
+    let generated_content =
+        std::fs::read_to_string(&output_file_path).expect("Error al leer el archivo generado");
+    let generated_json: Value =
+        serde_json::from_str(&generated_content).expect("Error al parsear el JSON generado");
+    assert_eq!(generated_json["partial"], true);
+    assert_eq!(
+        generated_json["completed_files"],
+        serde_json::json!([completed_file.to_string_lossy()])
+    );
+    assert!(generated_json["top_killers"].get("gina").is_none());
+}
+
+#[test]
+fn test_json_progress_reports_all_files() {
+    let output_file_path = std::env::temp_dir().join("tp_fork_join_json_progress.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2"])
+        .arg(&output_file_path)
+        .arg("--progress=json")
+        .output()
+        .expect("Error al ejecutar el programa");
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let last_progress: Value = stderr
+        .lines()
+        .rev()
+        .find(|line| line.starts_with('{'))
+        .map(|line| serde_json::from_str(line).expect("Error al parsear el progreso"))
+        .expect("No se mostro el progreso");
+    assert_eq!(last_progress["files_done"], 2);
+    assert_eq!(last_progress["files_total"], 2);
+    assert_eq!(last_progress["rows"], 14);
+    assert_eq!(last_progress["bytes_read"], last_progress["bytes_total"]);
+}
+
+#[test]
+fn test_progress_without_mode_does_not_take_the_next_option() {
+    let generated_json =
+        run_with_sample_dataset("progress_without_mode", &["--progress", "--metrics"]);
+    assert_eq!(generated_json["metrics"]["rows"], 14);
+}
+
+#[test]
+fn test_metrics_summary_only_with_metrics() {
+    let output_file_path = std::env::temp_dir().join("tp_fork_join_metrics_summary.json");
+    let run = |options: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+            .args([SAMPLE_INPUT_PATH, "2"])
+            .arg(&output_file_path)
+            .args(options)
+            .output()
+            .expect("Error al ejecutar el programa");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    assert!(!run(&[]).lines().any(|line| line.starts_with("Filas:")));
+    assert!(run(&["--metrics"])
+        .lines()
+        .any(|line| line.starts_with("Filas: 14 ")));
+}
+
+#[test]
+fn test_metrics_in_output() {
+    let generated_json = run_with_sample_dataset("metrics_in_output", &["--metrics"]);
+    let metrics = &generated_json["metrics"];
+
+    let phases = metrics["phases"].as_object().unwrap();
+    ["scan", "read_parse", "reduce", "top"]
+        .iter()
+        .for_each(|phase| assert!(phases[*phase].as_f64().unwrap() >= 0.0));
+    assert!(phases.get("write").is_none());
+    assert_eq!(metrics["rows"], 14);
+    assert_eq!(metrics["files"].as_array().unwrap().len(), 2);
+    let by_worker = metrics["worker_utilization"]["by_worker"]
+        .as_array()
+        .unwrap();
+    assert_eq!(by_worker.len(), 2);
+    assert!(by_worker
+        .iter()
+        .all(|utilization| (0.0..=1.0).contains(&utilization.as_f64().unwrap())));
+}
+
+#[test]
+fn test_memory_usage_in_output() {
+    let generated_json = run_with_sample_dataset("memory_usage_in_output", &["--metrics"]);
+    let memory = &generated_json["memory"];
+    let aggregates = &memory["aggregates"];
+
+    assert_eq!(aggregates["weapons"]["weapons"], 9);
+    assert_eq!(aggregates["players_weapons"]["players"], 8);
+    assert_eq!(aggregates["players_weapons"]["player_weapon_pairs"], 11);
+    let aggregates_bytes: u64 = aggregates
+        .as_object()
+        .unwrap()
+        .values()
+        .map(|aggregate| aggregate["estimated_bytes"].as_u64().unwrap())
+        .sum();
+    assert!(aggregates_bytes > 0);
+    assert_eq!(memory["estimated_bytes"], aggregates_bytes);
+    assert!(memory["spilled_bytes"].is_null());
+    if cfg!(target_os = "linux") {
+        assert!(memory["peak_rss_bytes"].as_u64().unwrap() > 0);
+    }
+}
+
+#[test]
+fn test_bench_sweeps_thread_counts() {
+    let generated_json = run_command_with_sample_dataset(
+        "bench_sweep",
+        &["bench"],
+        &["--threads", "2", "--warmup", "0", "--repetitions", "2"],
+    );
+    let results = generated_json.as_array().unwrap();
+
+    let threads: Vec<u64> = results
+        .iter()
+        .map(|result| result["threads"].as_u64().unwrap())
+        .collect();
+    assert_eq!(threads, vec![1, 2]);
+    results.iter().for_each(|result| {
+        ["scan", "read_parse", "reduce", "top", "write", "total"]
+            .iter()
+            .for_each(|phase| {
+                let stats = &result["phases"][*phase];
+                assert!(
+                    stats["min_seconds"].as_f64().unwrap()
+                        <= stats["mean_seconds"].as_f64().unwrap()
+                );
+                assert!(stats["stddev_seconds"].as_f64().unwrap() >= 0.0);
+            });
+    });
+    assert_eq!(results[0]["phases"]["total"]["speedup"], 1.0);
+    assert_eq!(results[0]["phases"]["total"]["efficiency"], 1.0);
+}
+
+#[test]
+fn test_bench_writes_csv() {
+    let output_file_path = std::env::temp_dir().join("tp_fork_join_bench_csv.csv");
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args(["bench", SAMPLE_INPUT_PATH, "2"])
+        .arg(&output_file_path)
+        .args(["--warmup", "0", "--repetitions", "1"])
+        .output()
+        .expect("Error al ejecutar el programa");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let generated_content =
+        std::fs::read_to_string(&output_file_path).expect("Error al leer el archivo generado");
+    let mut lines = generated_content.lines();
+    assert_eq!(
+        lines.next(),
+        Some("threads,phase,mean_seconds,stddev_seconds,min_seconds,speedup,efficiency")
+    );
+    assert!(lines.any(|line| line.starts_with("2,total,")));
+}
+
+#[test]
+fn test_backends_produce_identical_reports() {
+    let input_path = write_generated_dataset("backends", 6, 500, |n| {
+        format!(
+            "{},killer{},{},{}.5,0.0,MIRAMAR,match{},{},victim{},{},3.0,4.0",
+            ["M416", "AKM", "Kar98k"][n % 3],
+            n % 37,
+            n % 20 + 1,
+            n % 1000,
+            n % 11,
+            n % 1800,
+            n % 53,
+            n % 25 + 1
+        )
+    });
+    let options = ["--placement", "--rivalries", "--distinct", "exact"];
+    let expected_json = run_command_with_dataset("backends_rayon", &[], &input_path, &options);
+
+    ["scoped", "channels", "pipeline"]
+        .iter()
+        .for_each(|backend| {
+            let backend_options: Vec<&str> = options
+                .iter()
+                .copied()
+                .chain(["--backend", backend])
+                .collect();
+            let generated_json = run_command_with_dataset(
+                &format!("backends_{}", backend),
+                &[],
+                &input_path,
+                &backend_options,
+            );
+            assert_json_eq!(generated_json, expected_json);
+
+            let query_options = [
+                "--group-by",
+                "map",
+                "--aggregate",
+                "count,avg(distance)",
+                "--backend",
+                backend,
+            ];
+            let generated_query = run_command_with_dataset(
+                &format!("backends_query_{}", backend),
+                &["query"],
+                &input_path,
+                &query_options,
+            );
+            let expected_query = run_command_with_dataset(
+                &format!("backends_query_rayon_{}", backend),
+                &["query"],
+                &input_path,
+                &query_options[..4],
+            );
+            assert_json_eq!(generated_query, expected_query);
+        });
+}
+
+#[test]
+fn test_pipeline_with_files_of_several_blocks() {
+    // Archivos de mas de 1 MB, para que se lean en varios bloques
+    let input_path = write_generated_dataset("pipeline_blocks", 2, 25000, |n| {
+        format!(
+            "M416,killer{},{},{}.25,7.5,ERANGEL,match{},{},victim{},3,1.0,2.0",
+            n % 211,
+            n % 30 + 1,
+            n % 700,
+            n % 17,
+            n % 2000,
+            n
+        )
+    });
+    let expected_json = run_command_with_dataset("pipeline_blocks_rayon", &[], &input_path, &[]);
+
+    let generated_json = run_command_with_dataset(
+        "pipeline_blocks",
+        &[],
+        &input_path,
+        &[
+            "--backend",
+            "pipeline",
+            "--io-threads",
+            "2",
+            "--aggregator-shards",
+            "3",
+            "--channel-capacity",
+            "1",
+            "--metrics",
+        ],
+    );
+    assert_eq!(generated_json["metrics"]["rows"], 50000);
+    assert_eq!(
+        generated_json["metrics"]["files"].as_array().unwrap().len(),
+        2
+    );
+    let mut generated_json = generated_json;
+    let generated_sections = generated_json.as_object_mut().unwrap();
+    generated_sections.remove("metrics");
+    generated_sections.remove("memory");
+    assert_json_eq!(generated_json, expected_json);
+}
+
+#[test]
+fn test_thread_pool_options_keep_the_report() {
+    let expected_json = run_with_sample_dataset("thread_pool_default", &[]);
+    let thread_options = [
+        "--thread-name",
+        "tp-test",
+        "--stack-size",
+        "4M",
+        "--pin-threads",
+    ];
+
+    ["rayon", "scoped", "channels", "pipeline"]
+        .iter()
+        .for_each(|backend| {
+            // Solo el backend pipeline tiene hilos de I/O
+            let io_threads: &[&str] = match *backend {
+                "pipeline" => &["--io-threads", "2"],
+                _ => &[],
+            };
+            let options: Vec<&str> = thread_options
+                .iter()
+                .chain(io_threads)
+                .copied()
+                .chain(["--backend", backend])
+                .collect();
+            let generated_json = run_command_with_dataset(
+                &format!("thread_pool_{}", backend),
+                &[],
+                Path::new(SAMPLE_INPUT_PATH),
+                &options,
+            );
+            assert_json_eq!(generated_json, expected_json);
+        });
+}
+
+#[test]
+fn test_io_threads_rejects_other_backends() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args(["--io-threads", "2"])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--backend pipeline"));
+}
+
+#[test]
+fn test_thread_pool_names_its_workers() {
+    let thread_pool_config = ThreadPoolConfig::parse(Some("tp-test"), Some("4M"), true, None)
+        .expect("Opciones de hilos invalidas");
+    let pool = thread_pool_config
+        .build_pool(2)
+        .expect("Error al crear el pool de hilos");
+
+    let mut names: Vec<String> = pool.broadcast(|_| {
+        std::thread::current()
+            .name()
+            .unwrap_or_default()
+            .to_string()
+    });
+    names.sort();
+    assert_eq!(names, vec!["tp-test-0", "tp-test-1"]);
+    assert!(ThreadPoolConfig::parse(None, Some("0"), false, None).is_err());
+    assert!(ThreadPoolConfig::parse(None, None, false, Some("0")).is_err());
+}
+
+#[test]
+fn test_mmap_matches_buffered_reading() {
+    // Archivos de mas de 1 MB, para que se repartan en varios pedazos del archivo mapeado
+    let input_path = write_generated_dataset("mmap", 2, 25000, |n| {
+        format!(
+            "{},killer{},{},{}.25,-0.0,ERANGEL,match{},{},victim{},{},1e1,4{}",
+            ["M416", "AKM", "Kar98k", "S1897"][n % 4],
+            n % 91,
+            n % 30 + 1,
+            n % 2000,
+            n % 13,
+            n % 1800,
+            n % 67,
+            n % 25 + 1,
+            if n % 7 == 0 { "\r" } else { ".5" }
+        )
+    });
+    let options = ["--placement", "--distinct", "exact"];
+    let expected_json = run_command_with_dataset("mmap_buffered", &[], &input_path, &options);
+
+    ["rayon", "scoped", "channels"].iter().for_each(|backend| {
+        let mmap_options: Vec<&str> = options
+            .iter()
+            .copied()
+            .chain(["--io", "mmap", "--backend", backend])
+            .collect();
+        let generated_json = run_command_with_dataset(
+            &format!("mmap_{}", backend),
+            &[],
+            &input_path,
+            &mmap_options,
+        );
+        assert_json_eq!(generated_json, expected_json);
+    });
+
+    let query_options = ["--group-by", "weapon", "--aggregate", "count,avg(distance)"];
+    let expected_json = run_command_with_dataset(
+        "mmap_query_buffered",
+        &["query"],
+        &input_path,
+        &query_options,
+    );
+    let mmap_query_options: Vec<&str> = query_options
+        .iter()
+        .copied()
+        .chain(["--io", "mmap"])
+        .collect();
+    let generated_json =
+        run_command_with_dataset("mmap_query", &["query"], &input_path, &mmap_query_options);
+    assert_json_eq!(generated_json, expected_json);
+}
+
+#[test]
+fn test_sharded_aggregation_matches_reduce() {
+    let input_path = write_generated_dataset("sharded_aggregation", 4, 1000, |n| {
+        format!(
+            "{},killer{},1.0,0.0,0.0,ERANGEL,match{},100,victim{},2.0,3.0,4.0",
+            ["M416", "AKM", "Kar98k", "Punch", "UMP9"][n % 5],
+            n % 1500,
+            n % 10,
+            n
+        )
+    });
+    let expected_json =
+        run_command_with_dataset("sharded_aggregation_reduce", &[], &input_path, &[]);
+
+    ["rayon", "scoped"].iter().for_each(|backend| {
+        let generated_json = run_command_with_dataset(
+            &format!("sharded_aggregation_{}", backend),
+            &[],
+            &input_path,
+            &["--aggregation", "sharded", "--backend", backend],
+        );
+        assert_json_eq!(generated_json, expected_json);
+    });
+}
+
+#[test]
+fn test_memory_limit_spills_and_matches_full_run() {
+    let input_path = write_generated_dataset("memory_limit", 4, 2000, |n| {
+        format!(
+            "{},killer{},1.0,0.0,0.0,ERANGEL,match{},100,victim{},2.0,3.0,4.0",
+            ["M416", "AKM", "Kar98k"][n % 3],
+            (n * 7919) % 3000,
+            n % 10,
+            n
+        )
+    });
+    [&[][..], &["weapon", "AKM"][..]]
+        .iter()
+        .for_each(|command| {
+            let name = command.join("_");
+            let expected_json = run_command_with_dataset(
+                &format!("memory_limit_full_{}", name),
+                command,
+                &input_path,
+                &[],
+            );
+
+            let output_file_path =
+                std::env::temp_dir().join(format!("tp_fork_join_memory_limit_{}.json", name));
+            let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+                .args(*command)
+                .arg(&input_path)
+                .arg("2")
+                .arg(&output_file_path)
+                .args(["--memory-limit", "32K"])
+                .output()
+                .expect("Error al ejecutar el programa");
+            assert!(output.status.success());
+            assert!(String::from_utf8_lossy(&output.stdout).contains("Jugadores volcados a disco"));
+
+            let generated_content = std::fs::read_to_string(&output_file_path)
+                .expect("Error al leer el archivo generado");
+            let generated_json: Value = serde_json::from_str(&generated_content)
+                .expect("Error al parsear el JSON generado");
+            assert_json_eq!(generated_json, expected_json);
+        });
+}
+
+#[test]
+fn test_memory_limit_bounds_the_players_in_memory() {
+    // Mas archivos que hilos, cada uno con jugadores que superan el limite de un hilo
+    let input_path = write_generated_dataset("memory_limit_many_files", 12, 1000, |n| {
+        format!(
+            "M416,killer{},1.0,0.0,0.0,ERANGEL,m1,100,victim{},2.0,3.0,4.0",
+            n, n
+        )
+    });
+    let memory_limit = 32 * 1024;
+    let generated_json = run_command_with_dataset(
+        "memory_limit_many_files",
+        &[],
+        &input_path,
+        &["--memory-limit", "32K", "--metrics"],
+    );
+
+    let peak_resident_bytes = generated_json["memory"]["peak_resident_bytes"]
+        .as_u64()
+        .unwrap();
+    assert!(peak_resident_bytes > 0);
+    assert!(
+        peak_resident_bytes <= memory_limit,
+        "{} bytes en memoria",
+        peak_resident_bytes
+    );
+}
+
+#[test]
+fn test_columnar_input_matches_csv() {
+    // Varios grupos de filas por archivo, con algunos asesinos sin nombre
+    let csv_path = write_generated_dataset("columnar", 3, 2500, |n| {
+        format!(
+            "{},{},{:?},{:?},{:?},{},match{},{},victim{},{:?},{:?},{:?}",
+            ["M416", "AKM", "Kar98k", "Punch"][n % 4],
+            if n % 11 == 0 {
+                String::new()
+            } else {
+                format!("killer{}", n % 97)
+            },
+            (n % 30 + 1) as f64,
+            (n % 2000) as f64 * 0.25,
+            -((n % 13) as f64),
+            ["ERANGEL", "MIRAMAR"][n % 2],
+            n % 41,
+            n % 1800,
+            n % 89,
+            (n % 25 + 1) as f64,
+            (n % 700) as f64 * 1.5,
+            (n % 5) as f64
+        )
+    });
+    let runs: [(&str, &[&str], &[&str]); 4] = [
+        (
+            "report",
+            &[],
+            &["--placement", "--rivalries", "--distinct", "exact"],
+        ),
+        ("weapon", &["weapon", "AKM"], &[]),
+        (
+            "player",
+            &["player", "killer5"],
+            &["--filter", "time > 600"],
+        ),
+        (
+            "query",
+            &["query"],
+            &[
+                "--group-by",
+                "map",
+                "--aggregate",
+                "count,avg(distance),distinct(victim_name)",
+                "--filter",
+                "killer_placement <= 10",
+            ],
+        ),
+    ];
+    ["parquet", "arrow"].iter().for_each(|format| {
+        let input_path = write_columnar_dataset("columnar", &csv_path, format);
+        runs.iter().for_each(|(name, command, options)| {
+            let expected_json = run_command_with_dataset(
+                &format!("columnar_{}_csv", name),
+                command,
+                &csv_path,
+                options,
+            );
+            let columnar_options: Vec<&str> = options
+                .iter()
+                .copied()
+                .chain(["--input-format", format])
+                .collect();
+            let generated_json = run_command_with_dataset(
+                &format!("columnar_{}_{}", name, format),
+                command,
+                &input_path,
+                &columnar_options,
+            );
+            assert_json_eq!(generated_json, expected_json);
+        });
+    });
+}
+
+#[test]
+fn test_columnar_input_rejects_other_backends() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-fork-join"))
+        .args([SAMPLE_INPUT_PATH, "2", "output_with_error.json"])
+        .args(["--input-format", "parquet", "--backend", "scoped"])
+        .output()
+        .expect("Error al ejecutar el programa");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--backend rayon"));
+}
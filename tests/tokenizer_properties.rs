@@ -0,0 +1,111 @@
+use std::io::{BufRead, Cursor};
+
+use proptest::prelude::*;
+use tp_fork_join::tokenizer;
+
+/// Splits a block of bytes in lines as `BufRead::lines` does, without requiring UTF-8:
+/// each line is read up to its `\n`, and the `\r` is removed only if it is before the `\n`.
+fn reference_lines(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut cursor = Cursor::new(data);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        let read = cursor
+            .read_until(b'\n', &mut line)
+            .expect("Error al leer la linea");
+        if read == 0 {
+            return lines;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        lines.push(line);
+    }
+}
+
+/// Returns the lines of a block of bytes with both tokenizers.
+fn tokenizer_lines(data: &[u8]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    (
+        tokenizer::lines(data).map(<[u8]>::to_vec).collect(),
+        tokenizer::scalar::lines(data).map(<[u8]>::to_vec).collect(),
+    )
+}
+
+/// Bytes with many ends of line and commas, the ones that the tokenizer searches.
+fn csv_bytes() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(
+        prop_oneof![
+            3 => prop::sample::select(b"\n\r,".to_vec()),
+            1 => any::<u8>(),
+            4 => prop::sample::select(b"abc019.".to_vec()),
+        ],
+        0..600,
+    )
+}
+
+/// Lines with many commas and some characters of more than one byte.
+fn csv_line() -> impl Strategy<Value = String> {
+    "([a-z0-9,.]|[ñé€🎯]){0,300}"
+}
+
+proptest! {
+    #[test]
+    fn test_lines_match_the_reference(data in csv_bytes()) {
+        let expected = reference_lines(&data);
+        let (lines, scalar_lines) = tokenizer_lines(&data);
+        prop_assert_eq!(&lines, &expected);
+        prop_assert_eq!(&scalar_lines, &expected);
+    }
+
+    #[test]
+    fn test_lines_match_bufread_lines(text in "([a-z,ñ]|\r|\n){0,300}") {
+        let expected: Vec<Vec<u8>> = Cursor::new(&text)
+            .lines()
+            .map(|line| line.expect("Error al leer la linea").into_bytes())
+            .collect();
+        let (lines, scalar_lines) = tokenizer_lines(text.as_bytes());
+        prop_assert_eq!(&lines, &expected);
+        prop_assert_eq!(&scalar_lines, &expected);
+    }
+
+    #[test]
+    fn test_split_fields_matches_the_reference(line in csv_line(), max_fields in 0usize..40) {
+        let expected: Vec<&str> = line.split(',').take(max_fields).collect();
+        let mut fields = vec![""; max_fields];
+        let number_of_fields = tokenizer::split_fields(&line, &mut fields);
+        prop_assert_eq!(&fields[..number_of_fields], &expected[..]);
+        let mut scalar_fields = vec![""; max_fields];
+        let number_of_fields = tokenizer::scalar::split_fields(&line, &mut scalar_fields);
+        prop_assert_eq!(&scalar_fields[..number_of_fields], &expected[..]);
+    }
+
+    #[test]
+    fn test_find_matches_the_reference(data in csv_bytes(), byte in prop::sample::select(b"\n\r,a".to_vec())) {
+        prop_assert_eq!(tokenizer::find(byte, &data), data.iter().position(|&b| b == byte));
+        prop_assert_eq!(tokenizer::rfind(byte, &data), data.iter().rposition(|&b| b == byte));
+        prop_assert_eq!(tokenizer::scalar::find(byte, &data), data.iter().position(|&b| b == byte));
+        prop_assert_eq!(tokenizer::scalar::rfind(byte, &data), data.iter().rposition(|&b| b == byte));
+    }
+}
+
+#[test]
+fn test_lines_keep_the_last_carriage_return_without_newline() {
+    let cases: [(&[u8], &[&[u8]]); 6] = [
+        (b"abc\r", &[b"abc\r"]),
+        (b"abc\r\n", &[b"abc"]),
+        (b"a\r\nb\r", &[b"a", b"b\r"]),
+        (b"\r", &[b"\r"]),
+        (b"\r\n\r", &[b"", b"\r"]),
+        (b"a\n\n", &[b"a", b""]),
+    ];
+    for (data, expected) in cases {
+        let expected: Vec<Vec<u8>> = expected.iter().map(|line| line.to_vec()).collect();
+        let (lines, scalar_lines) = tokenizer_lines(data);
+        assert_eq!(lines, expected);
+        assert_eq!(scalar_lines, expected);
+        assert_eq!(reference_lines(data), expected);
+    }
+}